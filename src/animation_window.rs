@@ -0,0 +1,105 @@
+use eframe::egui;
+use itertools::Itertools;
+
+use crate::{
+    animations::{Animation, missing_anim_types},
+    hats_data::HatType,
+    ui_text::{Translatable, UiText},
+};
+
+pub fn draw_animations(
+    ui: &mut egui::Ui,
+    text: &UiText,
+    hat_type: HatType,
+    frames_amount: u32,
+    default_delay: f32,
+    animations: &mut Vec<Animation>,
+) {
+    ui.heading(text.get("49"));
+    let missing_animations = missing_anim_types(hat_type, animations);
+    if ui
+        .add_enabled(
+            !missing_animations.is_empty(),
+            egui::Button::new(text.get("85")),
+        )
+        .clicked()
+    {
+        animations.extend(
+            missing_animations
+                .into_iter()
+                .map(|anim_type| Animation::new(anim_type, default_delay, false, vec![])),
+        );
+    }
+    if animations.is_empty() {
+        ui.label(text.get("55"));
+        return;
+    }
+    egui::ScrollArea::vertical().show(ui, |ui| {
+        for (i, animation) in animations.iter_mut().enumerate() {
+            let mut title = text.get(animation.anim_type.translate_key()).to_string();
+            if !animation.is_valid(frames_amount) {
+                title.push_str(" ⚠");
+            }
+            egui::CollapsingHeader::new(title)
+                .id_salt(i)
+                .show(ui, |ui| draw_animation(ui, text, frames_amount, animation));
+        }
+    });
+}
+
+fn draw_animation(ui: &mut egui::Ui, text: &UiText, frames_amount: u32, animation: &mut Animation) {
+    let error_color = ui.visuals().error_fg_color;
+    if animation.frames.is_empty() {
+        ui.colored_label(error_color, text.get("86"));
+    }
+    let frames_out_of_range = animation.frames_out_of_range(frames_amount);
+    if !frames_out_of_range.is_empty() {
+        ui.colored_label(
+            error_color,
+            format!(
+                "{}{}",
+                text.get("87"),
+                frames_out_of_range.iter().join(", ")
+            ),
+        );
+    }
+
+    ui.horizontal(|ui| {
+        ui.label(text.get("50"));
+        ui.add(
+            egui::DragValue::new(&mut animation.delay)
+                .speed(0.01)
+                .range(0.0..=f32::MAX),
+        );
+        ui.checkbox(&mut animation.looping, text.get("51"));
+    });
+
+    ui.horizontal(|ui| {
+        ui.add(
+            egui::DragValue::new(&mut animation.new_frames_delay)
+                .speed(0.01)
+                .range(0.0..=f32::MAX),
+        );
+        if ui.button(text.get("53")).clicked() {
+            animation.set_frames_delay(Some(animation.new_frames_delay));
+        }
+        if ui.button(text.get("54")).clicked() {
+            animation.set_frames_delay(None);
+        }
+    });
+
+    ui.label(text.get("52"));
+    egui::Grid::new("frames").striped(true).show(ui, |ui| {
+        for frame in &mut animation.frames {
+            ui.add(egui::DragValue::new(&mut frame.value));
+            let mut has_delay = frame.delay.is_some();
+            if ui.checkbox(&mut has_delay, text.get("50")).changed() {
+                frame.delay = has_delay.then_some(animation.delay);
+            }
+            if let Some(delay) = &mut frame.delay {
+                ui.add(egui::DragValue::new(delay).speed(0.01).range(0.0..=f32::MAX));
+            }
+            ui.end_row();
+        }
+    });
+}
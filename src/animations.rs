@@ -1,10 +1,14 @@
 use num_derive::FromPrimitive;
 use once_cell::sync::Lazy;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::{cell::Cell, fmt::Display, hash::Hash};
+use strum::IntoEnumIterator;
 
 use crate::{hats_data::HatType, ui_text::Translatable};
 
+pub const DEFAULT_ANIMATION_DELAY: f32 = 0.1;
+
 pub static PET_ANIMATIONS: Lazy<Vec<AnimType>> = Lazy::new(|| {
     use AnimType::*;
     vec![
@@ -51,7 +55,48 @@ pub fn avalible_animations<'a>(hat_type: HatType) -> Option<&'a [AnimType]> {
     }
 }
 
-#[derive(Copy, Clone, Debug, FromPrimitive, PartialEq, Eq, Serialize, Deserialize, Default)]
+/// Animations available for `hat_type` that `animations` doesn't define yet.
+pub fn missing_anim_types(hat_type: HatType, animations: &[Animation]) -> Vec<AnimType> {
+    avalible_animations(hat_type)
+        .unwrap_or_default()
+        .iter()
+        .filter(|anim_type| !animations.iter().any(|a| a.anim_type == **anim_type))
+        .copied()
+        .collect()
+}
+
+/// Adds those of `new` whose type `animations` doesn't have yet, e.g. tags added to an
+/// aseprite file since it was last imported. Returns the added types.
+pub fn add_missing_animations(
+    animations: &mut Vec<Animation>,
+    new: Vec<Animation>,
+) -> Vec<AnimType> {
+    let mut added = vec![];
+    for animation in new {
+        if !animations
+            .iter()
+            .any(|a| a.anim_type == animation.anim_type)
+        {
+            added.push(animation.anim_type);
+            animations.push(animation);
+        }
+    }
+    added
+}
+
+#[derive(
+    Copy,
+    Clone,
+    Debug,
+    FromPrimitive,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    JsonSchema,
+    Default,
+    strum::EnumIter,
+)]
 pub enum AnimType {
     #[default]
     OnDefault,
@@ -70,6 +115,38 @@ pub enum AnimType {
     OnHatPickedUp,
 }
 
+impl AnimType {
+    /// The aseprite tag name that imports as this animation.
+    pub fn tag_name(&self) -> &'static str {
+        match self {
+            AnimType::OnDefault => "OnDefault",
+            AnimType::OnPressQuack => "OnPressQuack",
+            AnimType::OnReleaseQuack => "OnReleaseQuack",
+            AnimType::OnPetStop => "OnPetStop",
+            AnimType::OnPetApproach => "OnPetApproach",
+            AnimType::OnDuckDeath => "OnDuckDeath",
+            AnimType::OnDuckJump => "OnDuckJump",
+            AnimType::OnDuckLand => "OnDuckLand",
+            AnimType::OnDuckGlide => "OnDuckGlide",
+            AnimType::OnDuckWalk => "OnDuckWalk",
+            AnimType::OnDuckSneak => "OnDuckSneak",
+            AnimType::OnDuckNetted => "OnDuckNetted",
+            AnimType::OnDuckSpawned => "OnDuckSpawned",
+            AnimType::OnHatPickedUp => "OnHatPickedUp",
+        }
+    }
+
+    /// Inverse of [`AnimType::tag_name`] that ignores case, underscores, hyphens and spaces,
+    /// so "on_press_quack" and "On Press Quack" both match.
+    pub fn from_tag_name(name: &str) -> Option<AnimType> {
+        let name: String = name
+            .chars()
+            .filter(|c| !matches!(c, '_' | '-' | ' '))
+            .collect();
+        AnimType::iter().find(|anim_type| anim_type.tag_name().eq_ignore_ascii_case(&name))
+    }
+}
+
 impl Translatable for AnimType {
     fn translate_key(&self) -> &str {
         match self {
@@ -94,7 +171,7 @@ impl Translatable for AnimType {
 #[derive(Debug, Clone, Copy, Hash, Default)]
 pub struct FrameId(pub u32);
 
-#[derive(Debug, Serialize, Deserialize, Default)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Default)]
 pub struct Frame {
     pub value: u32,
     pub delay: Option<f32>,
@@ -177,10 +254,10 @@ pub fn frame_id() -> FrameId {
     FrameId(id)
 }
 
-#[derive(Clone, Debug, Serialize, Default, Deserialize)]
+#[derive(Clone, Debug, Serialize, Default, Deserialize, JsonSchema)]
 pub struct Animation {
-    //TODO: add support for diff. delay per frame
     pub anim_type: AnimType,
+    /// Delay of frames that don't have their own.
     pub delay: f32,
     pub looping: bool,
     pub frames: Vec<Frame>,
@@ -190,6 +267,8 @@ pub struct Animation {
     pub new_range_start: i32,
     #[serde(skip)]
     pub new_range_end: i32,
+    #[serde(skip)]
+    pub new_frames_delay: f32,
 }
 
 impl Animation {
@@ -202,6 +281,212 @@ impl Animation {
             new_frame: 1,
             new_range_end: 1,
             new_range_start: 1,
+            new_frames_delay: delay.max(0.0),
         }
     }
+
+    /// How long `frame` stays on screen. Its own delay wins over the animation's.
+    pub fn frame_delay(&self, frame: &Frame) -> f32 {
+        frame.delay.unwrap_or(self.delay).max(0.0)
+    }
+
+    /// How long one pass through the frames takes.
+    pub fn total_duration(&self) -> f32 {
+        self.frames
+            .iter()
+            .map(|frame| self.frame_delay(frame))
+            .sum()
+    }
+
+    /// The frame shown `time` seconds after the animation started. Looping animations
+    /// start over, others hold their last frame.
+    pub fn frame_at_time(&self, time: f32) -> Option<&Frame> {
+        let duration = self.total_duration();
+        let mut time = time.max(0.0);
+        if self.looping && duration > 0.0 {
+            time %= duration;
+        }
+        let mut end = 0.0;
+        for frame in &self.frames {
+            end += self.frame_delay(frame);
+            if time < end {
+                return Some(frame);
+            }
+        }
+        self.frames.last()
+    }
+
+    /// Frame values that don't point at an existing frame of the atlas.
+    pub fn frames_out_of_range(&self, frames_amount: u32) -> Vec<u32> {
+        self.frames
+            .iter()
+            .map(|f| f.value)
+            .filter(|value| *value >= frames_amount)
+            .collect()
+    }
+
+    /// Empty animations and out of range frames can't be exported.
+    pub fn is_valid(&self, frames_amount: u32) -> bool {
+        !self.frames.is_empty() && self.frames_out_of_range(frames_amount).is_empty()
+    }
+
+    /// Overrides every frame's delay. `None` makes frames fall back to the animation's delay.
+    pub fn set_frames_delay(&mut self, delay: Option<f32>) {
+        for frame in &mut self.frames {
+            frame.delay = delay;
+        }
+    }
+
+    /// Replaces per-frame delays with their average as the animation's delay, for game
+    /// builds that don't read per-frame delays. Returns false if the frames' delays
+    /// differed, so the timing changed.
+    pub fn flatten_delays(&mut self) -> bool {
+        if self.frames.iter().all(|frame| frame.delay.is_none()) {
+            return true;
+        }
+        let delays: Vec<f32> = self
+            .frames
+            .iter()
+            .map(|frame| frame.delay.unwrap_or(self.delay))
+            .collect();
+        let uniform = delays.iter().all(|delay| *delay == delays[0]);
+        self.delay = delays.iter().sum::<f32>() / delays.len() as f32;
+        self.set_frames_delay(None);
+        uniform
+    }
+}
+
+/// Plays a single animation the way the game does: each frame stays for its own delay,
+/// or the animation's, and non-looping animations stop on their last frame.
+#[derive(Debug, Clone, Default)]
+pub struct AnimationPlayer {
+    frame_index: usize,
+    /// Time spent on the current frame.
+    elapsed: f32,
+    current_frame: Option<u32>,
+}
+
+impl AnimationPlayer {
+    /// Moves `dt` seconds forward through `animation`, an animation of an atlas with
+    /// `frames_amount` frames.
+    pub fn advance(&mut self, animation: &Animation, frames_amount: u32, dt: f32) {
+        if self.frame_index >= animation.frames.len() {
+            self.restart();
+        }
+        self.elapsed += dt;
+        let duration = animation.total_duration();
+        if animation.looping && duration > 0.0 {
+            // Whole loops don't change the shown frame, skipping them keeps long pauses cheap.
+            let remaining: f32 = animation.frames[self.frame_index..]
+                .iter()
+                .map(|frame| animation.frame_delay(frame))
+                .sum();
+            if self.elapsed >= remaining + duration {
+                self.elapsed = (self.elapsed - remaining) % duration + remaining;
+            }
+        }
+        while let Some(frame) = animation.frames.get(self.frame_index) {
+            let frame_duration = animation.frame_delay(frame);
+            if self.elapsed < frame_duration {
+                break;
+            }
+            if self.frame_index + 1 < animation.frames.len() {
+                self.frame_index += 1;
+            } else if animation.looping && duration > 0.0 {
+                self.frame_index = 0;
+            } else {
+                self.elapsed = frame_duration;
+                break;
+            }
+            self.elapsed -= frame_duration;
+        }
+        self.current_frame = animation
+            .frames
+            .get(self.frame_index)
+            .map(|frame| frame.value)
+            .filter(|value| *value < frames_amount);
+    }
+
+    /// The atlas frame to show, `None` for empty animations and out of range frames.
+    pub fn current_frame(&self) -> Option<u32> {
+        self.current_frame
+    }
+
+    pub fn restart(&mut self) {
+        *self = Self::default();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{AnimType, Animation, AnimationPlayer, Frame, add_missing_animations};
+
+    #[test]
+    fn tag_names_ignore_case_and_separators() {
+        for name in [
+            "OnPressQuack",
+            "onpressquack",
+            "on_press_quack",
+            "on-press-quack",
+            "On Press Quack",
+        ] {
+            assert_eq!(AnimType::from_tag_name(name), Some(AnimType::OnPressQuack));
+        }
+        assert_eq!(AnimType::from_tag_name("onduckjmp"), None);
+    }
+
+    #[test]
+    fn only_missing_animations_are_added() {
+        let mut animations = vec![Animation::new(
+            AnimType::OnDefault,
+            0.5,
+            true,
+            vec![Frame::new(3)],
+        )];
+        let new = vec![
+            Animation::new(AnimType::OnDefault, 0.1, false, vec![Frame::new(0)]),
+            Animation::new(AnimType::OnDuckJump, 0.1, false, vec![Frame::new(1)]),
+        ];
+
+        let added = add_missing_animations(&mut animations, new);
+
+        assert_eq!(added, [AnimType::OnDuckJump]);
+        assert_eq!(animations.len(), 2);
+        assert_eq!(animations[0].delay, 0.5);
+        assert_eq!(animations[0].frames[0].value, 3);
+    }
+
+    #[test]
+    fn frame_at_time_uses_frame_delays() {
+        let frames = vec![Frame::with_delay(0, 0.5), Frame::new(1)];
+        let mut animation = Animation::new(AnimType::OnDefault, 0.25, false, frames);
+        assert_eq!(animation.total_duration(), 0.75);
+        let frame_at = |animation: &Animation, time| animation.frame_at_time(time).unwrap().value;
+        assert_eq!(frame_at(&animation, 0.4), 0);
+        assert_eq!(frame_at(&animation, 0.6), 1);
+        assert_eq!(frame_at(&animation, 2.0), 1);
+
+        animation.looping = true;
+        assert_eq!(frame_at(&animation, 1.0), 0);
+    }
+
+    #[test]
+    fn player_uses_frame_delays() {
+        let frames = vec![Frame::with_delay(0, 0.5), Frame::new(1), Frame::new(2)];
+        let mut animation = Animation::new(AnimType::OnDefault, 0.25, true, frames);
+        let mut player = AnimationPlayer::default();
+        let mut shown = vec![];
+        for _ in 0..8 {
+            player.advance(&animation, 3, 0.25);
+            shown.push(player.current_frame().unwrap());
+        }
+        assert_eq!(shown, [0, 1, 2, 0, 0, 1, 2, 0]);
+
+        animation.looping = false;
+        player.restart();
+        player.advance(&animation, 3, 10.0);
+        assert_eq!(player.current_frame(), Some(2));
+        player.advance(&animation, 2, 0.1);
+        assert_eq!(player.current_frame(), None);
+    }
 }
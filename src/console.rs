@@ -1,73 +1,233 @@
-use std::sync::mpsc::{Receiver, Sender, channel};
+use std::{
+    collections::VecDeque,
+    fmt::Write as _,
+    sync::{
+        Mutex, OnceLock, PoisonError,
+        mpsc::{Receiver, Sender, channel},
+    },
+};
 
+use anyhow::Result;
 use eframe::egui::{self, ScrollArea};
-use flexi_logger::{Logger, writers::LogWriter};
-use log::Level;
+use flexi_logger::{Logger, LoggerHandle, writers::LogWriter};
+use log::{
+    Level,
+    kv::{self, VisitSource},
+};
 
+use crate::ui_text::UiText;
+
+/// How many logs a console keeps unless made with [`Console::with_capacity`].
 const MAX_LOGS: usize = 500;
 
-pub struct ConsoleLogWriter {
-    sender: Sender<(Level, String)>,
+/// The global logger can only be installed once, so it's kept alive here for the whole run.
+static LOGGER: OnceLock<LoggerHandle> = OnceLock::new();
+/// Sender of the most recently created [`Console`]; creating a new console swaps it out.
+static LOG_SENDER: Mutex<Option<Sender<LogEntry>>> = Mutex::new(None);
+
+/// A log message along with the key-values it was logged with, e.g. `hat = hat.name()`.
+#[derive(Debug, Clone)]
+pub struct LogEntry {
+    pub level: Level,
+    /// The key-values joined as `key=value`, empty when there were none.
+    pub context: String,
+    pub message: String,
 }
 
-impl ConsoleLogWriter {
-    pub fn new(sender: Sender<(Level, String)>) -> Self {
-        Self { sender }
+impl LogEntry {
+    fn new(record: &log::Record) -> Self {
+        let mut visitor = ContextVisitor(String::new());
+        let _ = record.key_values().visit(&mut visitor);
+        Self {
+            level: record.level(),
+            context: visitor.0,
+            message: record.args().to_string(),
+        }
+    }
+
+    /// The log as one line of text, the way the console shows it.
+    pub fn to_line(&self) -> String {
+        if self.context.is_empty() {
+            format!("{}: {}", self.level, self.message)
+        } else {
+            format!("{} [{}]: {}", self.level, self.context, self.message)
+        }
+    }
+}
+
+struct ContextVisitor(String);
+
+impl<'kvs> VisitSource<'kvs> for ContextVisitor {
+    fn visit_pair(&mut self, key: kv::Key<'kvs>, value: kv::Value<'kvs>) -> Result<(), kv::Error> {
+        if !self.0.is_empty() {
+            self.0.push(' ');
+        }
+        let _ = write!(self.0, "{}={}", key, value);
+        Ok(())
     }
 }
 
+pub struct ConsoleLogWriter;
+
 impl LogWriter for ConsoleLogWriter {
     fn write(
         &self,
         _now: &mut flexi_logger::DeferredNow,
         record: &log::Record,
     ) -> std::io::Result<()> {
-        let _ = self
-            .sender
-            .send((record.level(), record.args().to_string()));
+        if let Some(sender) = &*LOG_SENDER.lock().unwrap_or_else(PoisonError::into_inner) {
+            let _ = sender.send(LogEntry::new(record));
+        }
         Ok(())
     }
     fn flush(&self) -> std::io::Result<()> {
         Ok(())
     }
 }
+/// Which logs the console shows. Only changes what is displayed, never the logs themselves.
+#[derive(Debug, Clone)]
+pub struct ConsoleFilter {
+    pub show_error: bool,
+    pub show_warn: bool,
+    pub show_info: bool,
+    /// Debug and trace logs.
+    pub show_debug: bool,
+    /// Case-insensitive text the message has to contain.
+    pub search: String,
+}
+
+impl Default for ConsoleFilter {
+    fn default() -> Self {
+        Self {
+            show_error: true,
+            show_warn: true,
+            show_info: true,
+            show_debug: true,
+            search: String::new(),
+        }
+    }
+}
+
+impl ConsoleFilter {
+    pub fn matches(&self, log: &LogEntry) -> bool {
+        let level_shown = match log.level {
+            Level::Error => self.show_error,
+            Level::Warn => self.show_warn,
+            Level::Info => self.show_info,
+            Level::Debug | Level::Trace => self.show_debug,
+        };
+        level_shown
+            && log
+                .message
+                .to_lowercase()
+                .contains(&self.search.to_lowercase())
+    }
+
+    /// Meant for a horizontal layout, e.g. the console's toolbar.
+    fn ui(&mut self, ui: &mut egui::Ui, text: &UiText) {
+        for (show, level) in [
+            (&mut self.show_error, Level::Error),
+            (&mut self.show_warn, Level::Warn),
+            (&mut self.show_info, Level::Info),
+            (&mut self.show_debug, Level::Debug),
+        ] {
+            ui.checkbox(
+                show,
+                egui::RichText::new(level.to_string()).color(Console::level_color(level)),
+            );
+        }
+        ui.separator();
+        ui.add(egui::TextEdit::singleline(&mut self.search).hint_text(text.get("178")));
+    }
+}
+
 #[derive(Debug)]
 pub struct Console {
-    recv: Receiver<(Level, String)>,
-    logs: Vec<(Level, String)>,
+    recv: Receiver<LogEntry>,
+    logs: VecDeque<LogEntry>,
+    /// The oldest logs are dropped past this many.
+    capacity: usize,
+    filter: ConsoleFilter,
 }
 
 impl Console {
+    /// Panics if the logger can't be set up, see [`Console::try_new`].
     pub fn new() -> Self {
+        Self::try_new().expect("could not set up the console logger")
+    }
+
+    /// Installs the global logger on first use. Fails if it couldn't be installed, e.g.
+    /// because another logger already was.
+    pub fn try_new() -> Result<Self> {
+        Self::with_capacity(MAX_LOGS)
+    }
+
+    /// Like [`Console::try_new`], keeping only the last `capacity` logs.
+    pub fn with_capacity(capacity: usize) -> Result<Self> {
+        if LOGGER.get().is_none() {
+            let logger = Logger::try_with_env()?
+                .log_to_writer(Box::new(ConsoleLogWriter))
+                .start()?;
+            let _ = LOGGER.set(logger);
+        }
         let (sender, recv) = channel();
-        Logger::try_with_env()
-            .unwrap()
-            .log_to_writer(Box::new(ConsoleLogWriter::new(sender)))
-            .start()
-            .unwrap();
-        Self {
+        *LOG_SENDER.lock().unwrap_or_else(PoisonError::into_inner) = Some(sender);
+        Ok(Self {
             recv,
-            logs: Default::default(),
+            logs: VecDeque::with_capacity(capacity),
+            capacity,
+            filter: Default::default(),
+        })
+    }
+
+    /// Logs to stderr instead, for when the console couldn't be set up.
+    pub fn log_to_stderr() -> Result<()> {
+        if LOGGER.get().is_none() {
+            let logger = Logger::try_with_env_or_str("info")?
+                .log_to_stderr()
+                .start()?;
+            let _ = LOGGER.set(logger);
         }
+        Ok(())
     }
 
-    pub fn update(&mut self, ui: &mut egui::Ui) {
+    pub fn update(&mut self, ui: &mut egui::Ui, text: &UiText) {
         while let Ok(log) = self.recv.try_recv() {
-            self.logs.push(log);
-            if self.logs.len() > MAX_LOGS {
-                self.logs.remove(0);
+            self.logs.push_back(log);
+            if self.logs.len() > self.capacity {
+                self.logs.pop_front();
             }
         }
 
+        ui.horizontal(|ui| {
+            self.filter.ui(ui, text);
+            ui.separator();
+            if ui.button(text.get("179")).clicked() {
+                let lines: Vec<_> = self
+                    .logs
+                    .iter()
+                    .filter(|log| self.filter.matches(log))
+                    .map(LogEntry::to_line)
+                    .collect();
+                ui.ctx().copy_text(lines.join("\n"));
+            }
+            if ui.button(text.get("180")).clicked() {
+                self.logs.clear();
+            }
+        });
+        ui.separator();
         ScrollArea::new([true, true]).show(ui, |ui| {
             ui.allocate_space((ui.available_width(), 1.0).into());
             ui.style_mut().wrap_mode = Some(egui::TextWrapMode::Wrap);
-            for (level, log) in &self.logs {
+            for log in self.logs.iter().filter(|log| self.filter.matches(log)) {
                 ui.horizontal(|ui| {
                     ui.spacing_mut().item_spacing.x = 0.0;
-                    let level_color = Self::level_color(*level);
-                    ui.label(egui::RichText::new(level.to_string()).color(level_color));
-                    ui.label(format!(": {}", log));
+                    let level_color = Self::level_color(log.level);
+                    ui.label(egui::RichText::new(log.level.to_string()).color(level_color));
+                    if !log.context.is_empty() {
+                        ui.label(egui::RichText::new(format!(" [{}]", log.context)).weak());
+                    }
+                    ui.label(format!(": {}", log.message));
                 });
             }
             ui.allocate_space((ui.available_width(), ui.available_height()).into());
@@ -89,3 +249,42 @@ impl Default for Console {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use log::Level;
+
+    use super::{ConsoleFilter, LogEntry};
+
+    #[test]
+    fn log_lines_start_with_level() {
+        let mut log = LogEntry {
+            level: Level::Warn,
+            context: String::new(),
+            message: "frame size clamped".to_string(),
+        };
+        assert_eq!(log.to_line(), "WARN: frame size clamped");
+        log.context = "hat=Cool".to_string();
+        assert_eq!(log.to_line(), "WARN [hat=Cool]: frame size clamped");
+    }
+
+    #[test]
+    fn filter_checks_level_and_search() {
+        let log = |level, message: &str| LogEntry {
+            level,
+            context: String::new(),
+            message: message.to_string(),
+        };
+        let mut filter = ConsoleFilter {
+            show_warn: false,
+            search: "HAT".to_string(),
+            ..Default::default()
+        };
+        assert!(filter.matches(&log(Level::Error, "could not open hat")));
+        assert!(!filter.matches(&log(Level::Warn, "hat has no elements")));
+        assert!(!filter.matches(&log(Level::Info, "project opened")));
+
+        filter.search.clear();
+        assert!(filter.matches(&log(Level::Trace, "anything")));
+    }
+}
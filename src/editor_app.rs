@@ -1,29 +1,95 @@
-use std::{fs::File, io::Write, path::Path, rc::Rc, time::Duration};
+use std::{
+    fs::File,
+    io::Write,
+    path::{Path, PathBuf},
+    rc::Rc,
+    time::{Duration, Instant},
+};
 
 use anyhow::{Context, Result, bail};
 use eframe::{
     egui::{self},
     glow::{self},
 };
-use log::{error, info};
+use egui_dock::DockState;
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use strum::IntoEnumIterator;
 
 use crate::{
+    animations::AnimType,
     catppuccin_egui,
     console::Console,
-    hats::{Hat, LoadHatElement, WearableHat},
-    hats_data::{HatData, HatType},
-    name_getter::{NameGetter, NameGetterResult},
-    tabs::{FrameData, Tab, Tabs},
+    file_dialogs::{FILE_DIALOG_DIRS_KEY, FileDialogDirs, FileDialogKind},
+    hat_templates::HatTemplate,
+    hats::{AUTOSAVE_FILE, ExportReport, Hat, HatElementId, HatError, script_template},
+    hats_data::{DEFAULT_IMAGES_DIR, HPP_EXTENSION, HatData, HatElementData, HatMetadata, HatType},
+    image::{AsepriteLayout, Image},
+    json_diff::JsonChange,
+    keybinds::EditorAction,
+    name_getter::{NameGetter, NameGetterResult, validate_file_name, validate_hat_name},
+    project::Project,
+    recent_hats::{RECENT_HATS_KEY, RecentHats},
+    settings::{SETTINGS_KEY, Settings},
+    tabs::{FrameData, SavedTab, Tab, Tabs},
+    texture,
     ui_text::{Language, Translatable, UiText},
 };
 
 use borrow::partial as p;
 use borrow::traits::*;
 
+const TRANSLATIONS_FILE: &str = "translations.json";
+const WINDOW_GEOMETRY_KEY: &str = "window_geometry";
+const TABS_LAYOUT_KEY: &str = "tabs_layout";
+/// How often open hats are checked for images edited in other programs, while idle.
+const IMAGE_POLL_INTERVAL: Duration = Duration::from_secs(1);
+const REVERT_MODAL_ID: &str = "revert_modal";
+const CLEAN_IMAGES_MODAL_ID: &str = "clean_images_modal";
+const RECOVER_AUTOSAVE_MODAL_ID: &str = "recover_autosave_modal";
+const VALIDATION_ERRORS_MODAL_ID: &str = "validation_errors_modal";
+const HAT_PROPERTIES_MODAL_ID: &str = "hat_properties_modal";
+const HAT_CHANGES_MODAL_ID: &str = "hat_changes_modal";
+const EXPORT_REPORT_MODAL_ID: &str = "export_report_modal";
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+struct WindowGeometry {
+    position: egui::Pos2,
+    size: egui::Vec2,
+}
+
+/// Hat metadata as edited in the properties modal, written to the hat only when applied.
+#[derive(Debug, Default, Clone)]
+struct HatProperties {
+    author: String,
+    description: String,
+    tags: String,
+}
+
+impl HatProperties {
+    fn new(metadata: &HatMetadata) -> Self {
+        Self {
+            author: metadata.author.clone().unwrap_or_default(),
+            description: metadata.description.clone().unwrap_or_default(),
+            tags: metadata.tags.join(", "),
+        }
+    }
+
+    fn to_metadata(&self) -> HatMetadata {
+        let non_empty = |s: &str| Some(s.trim().to_string()).filter(|s| !s.is_empty());
+        HatMetadata {
+            author: non_empty(&self.author),
+            description: non_empty(&self.description),
+            tags: self.tags.split(',').filter_map(non_empty).collect(),
+        }
+    }
+}
+
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub enum NameGetterVariant {
     Hat,
-    Script,
+    /// A script for the element with this id.
+    Script(HatElementId),
 }
 
 #[derive(borrow::Partial)]
@@ -35,6 +101,26 @@ pub struct EditorApp {
     toasts: egui_notify::Toasts,
     toasts_storage: Vec<(ToastType, String)>,
     console: Option<Console>,
+    startup_hat_path: Option<PathBuf>,
+    /// Tabs open in the last session, restored once the GL context is available.
+    layout_to_restore: Option<DockState<SavedTab>>,
+    settings: Settings,
+    window_geometry: Option<WindowGeometry>,
+    window_restored: bool,
+    images_to_clean: Vec<PathBuf>,
+    last_autosave: Instant,
+    autosave_to_recover: Option<PathBuf>,
+    file_dialog_dirs: FileDialogDirs,
+    recent_hats: RecentHats,
+    /// Problems in the data.json of a just opened hat, waiting to be shown.
+    validation_errors: Vec<String>,
+    hat_properties: HatProperties,
+    /// Differences between the focused hat and its data.json, while they are shown.
+    hat_changes: Vec<JsonChange>,
+    /// A folder of hats listed in a side panel, if one was opened.
+    project: Option<Project>,
+    /// Problems found in the hat about to be exported, and whether to ask for the path.
+    export_report: Option<(ExportReport, bool)>,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -46,9 +132,24 @@ pub enum ToastType {
 }
 
 impl EditorApp {
-    pub fn new(cc: &eframe::CreationContext<'_>) -> Self {
-        let ui_text = UiText::new(Language::English, include_str!("../translations.json"));
+    /// `startup_hat_path` is opened once the GL context is available, e.g. a `.hatspp`
+    /// file passed on the command line.
+    pub fn new(cc: &eframe::CreationContext<'_>, startup_hat_path: Option<PathBuf>) -> Self {
+        let mut toasts_storage = vec![];
+        let settings = Settings::load(cc.storage);
+        let ui_text = Self::load_ui_text(settings.language, &mut toasts_storage);
 
+        let console = match Console::try_new() {
+            Ok(console) => Some(console),
+            Err(err) => {
+                eprintln!("could not set up the console, logging to stderr: {:#}", err);
+                if let Err(err) = Console::log_to_stderr() {
+                    eprintln!("could not log to stderr either: {:#}", err);
+                }
+                toasts_storage.push((ToastType::Warn, ui_text.get("181").to_string()));
+                None
+            }
+        };
         let tabs = Tabs::new(&ui_text);
         catppuccin_egui::set_theme(&cc.egui_ctx, catppuccin_egui::MOCHA);
         Self::set_font(&cc.egui_ctx);
@@ -57,8 +158,33 @@ impl EditorApp {
             tabs,
             hat_name_getter: NameGetter::default(),
             toasts: egui_notify::Toasts::default(),
-            toasts_storage: Default::default(),
-            console: Some(Console::new()),
+            toasts_storage,
+            console,
+            startup_hat_path,
+            layout_to_restore: cc
+                .storage
+                .and_then(|storage| eframe::get_value(storage, TABS_LAYOUT_KEY)),
+            settings,
+            window_geometry: cc
+                .storage
+                .and_then(|storage| eframe::get_value(storage, WINDOW_GEOMETRY_KEY)),
+            window_restored: false,
+            images_to_clean: vec![],
+            last_autosave: Instant::now(),
+            autosave_to_recover: None,
+            file_dialog_dirs: cc
+                .storage
+                .and_then(|storage| eframe::get_value(storage, FILE_DIALOG_DIRS_KEY))
+                .unwrap_or_default(),
+            recent_hats: cc
+                .storage
+                .and_then(|storage| eframe::get_value(storage, RECENT_HATS_KEY))
+                .unwrap_or_default(),
+            validation_errors: vec![],
+            hat_properties: HatProperties::default(),
+            hat_changes: vec![],
+            project: None,
+            export_report: None,
         }
     }
 
@@ -76,6 +202,64 @@ impl EditorApp {
         ctx.set_fonts(fonts);
     }
 
+    /// Prefers a `translations.json` placed next to the executable, falling back to the
+    /// embedded one if it's missing or broken.
+    fn load_ui_text(language: Language, toasts: &mut Vec<(ToastType, String)>) -> UiText {
+        let embedded = || {
+            UiText::new(language, include_str!("../translations.json"))
+                .expect("embedded translations should always be valid")
+        };
+        let Some(path) = std::env::current_exe()
+            .ok()
+            .and_then(|exe| exe.parent().map(|dir| dir.join(TRANSLATIONS_FILE)))
+            .filter(|path| path.exists())
+        else {
+            return embedded();
+        };
+        match UiText::from_path(language, &path) {
+            Ok(ui_text) => ui_text,
+            Err(err) => {
+                error!("while loading translations: {:#}", err);
+                let ui_text = embedded();
+                toasts.push((
+                    ToastType::Error,
+                    format!("{}: {:?}", ui_text.get("196"), &path),
+                ));
+                ui_text
+            }
+        }
+    }
+
+    fn print_missing_translations(text: &UiText) {
+        for language in Language::iter().filter(|l| *l != Language::English) {
+            let missing_keys = text.missing_keys(language);
+            if missing_keys.is_empty() {
+                info!("{:?} translation is complete", language);
+            } else {
+                warn!(
+                    "{:?} translation is missing {} keys: {}",
+                    language,
+                    missing_keys.len(),
+                    missing_keys.join(", ")
+                );
+            }
+        }
+    }
+
+    fn export_schema() -> Result<()> {
+        let Some(path) = rfd::FileDialog::new()
+            .add_filter("JSON Schema", &["json"])
+            .set_file_name("data.schema.json")
+            .save_file()
+        else {
+            return Ok(());
+        };
+        std::fs::write(&path, HatData::json_schema())
+            .context(format!("could not write schema to {:?}", &path))?;
+        info!("data.json schema exported to {:?}", &path);
+        Ok(())
+    }
+
     fn set_min_width(ui: &mut egui::Ui, text: &str) {
         let galley = ui.painter().layout_no_wrap(
             text.to_string(),
@@ -91,120 +275,181 @@ impl p!(<mut toasts_storage> EditorApp) {
         self.toasts_storage.push((toast_type, message));
     }
 }
-impl p!(<mut toasts_storage, mut toasts> EditorApp) {
+impl p!(<mut toasts_storage, mut toasts, settings> EditorApp) {
     fn display_toasts(&mut self, ctx: &egui::Context) {
+        *self.toasts = std::mem::take(self.toasts).with_anchor(self.settings.toast_anchor.anchor());
         for (toast_type, message) in self.toasts_storage.drain(..) {
-            match toast_type {
-                ToastType::Success => {
-                    self.toasts
-                        .success(message)
-                        .closable(false)
-                        .duration(Some(Duration::from_secs(3)));
-                }
-                ToastType::Info => {
-                    self.toasts
-                        .info(message)
-                        .closable(false)
-                        .duration(Some(Duration::from_secs(3)));
-                }
-                ToastType::Warn => {
-                    self.toasts
-                        .warning(message)
-                        .closable(false)
-                        .duration(Some(Duration::from_secs(3)));
-                }
-                ToastType::Error => {
-                    self.toasts
-                        .error(message)
-                        .closable(false)
-                        .duration(Some(Duration::from_secs(3)));
-                }
-            }
+            let toast = match toast_type {
+                ToastType::Success => self.toasts.success(message),
+                ToastType::Info => self.toasts.info(message),
+                ToastType::Warn => self.toasts.warning(message),
+                ToastType::Error => self.toasts.error(message),
+            };
+            let duration = self.settings.toast_duration(toast_type);
+            toast
+                .closable(duration.is_none() || toast_type == ToastType::Error)
+                .duration(duration);
         }
         self.toasts.show(ctx);
     }
 }
 
-impl p!(<mut tabs> EditorApp) {
-    fn open_hat(&mut self, gl: &glow::Context, path: impl AsRef<Path>) -> Result<()> {
-        if self
-            .tabs
-            .dock_state
-            .iter_all_tabs()
-            .any(|t| matches!(&t.1, Tab::HatElement { hat, .. } if hat.path() == path.as_ref()))
+impl p!(<mut tabs, mut file_dialog_dirs> EditorApp) {
+    /// Asks for a folder to save the focused hat to. Folders of hats open in other tabs are
+    /// refused, so two tabs never save over each other.
+    fn pick_hat_folder(&mut self) -> Result<Option<PathBuf>> {
+        let Some(path) = self
+            .file_dialog_dirs
+            .dialog(FileDialogKind::OpenHat)
+            .pick_folder()
+        else {
+            return Ok(None);
+        };
+        self.file_dialog_dirs
+            .remember(FileDialogKind::OpenHat, &path);
+        let current = self.tabs.last_interacted_tab_hat().map(Hat::id);
+        if let Some(id) = self.tabs.open_hat_id(&path)
+            && Some(id) != current
         {
-            bail!("hat with this path is already added: {:?}", path.as_ref());
+            bail!("the hat in {:?} is open in a tab already", &path);
         }
-
-        let hat = Hat::load(path, gl)?;
-        info!("hat {} loaded successfully", hat.name());
-        let selected_hat_id = hat.elements().next().map(|e| e.id());
-        //add textures to reloader
-        let tab = Tab::new_hat_tab(hat, selected_hat_id);
-        self.tabs.dock_state.push_to_focused_leaf(tab);
-        Ok(())
+        Ok(Some(path))
     }
 
-    fn open_hat_with_dialog(&mut self, gl: &glow::Context) -> Result<()> {
-        let Some(path) = rfd::FileDialog::new()
-            .set_directory("/home/palas/Documents/projects/rust-projects/hpp-editor-v2/")
-            .pick_folder()
-        else {
-            return Ok(());
+    /// Returns `false` if the user cancelled picking a folder for a new hat.
+    fn save_hat(&mut self) -> Result<bool> {
+        let is_unsaved = self
+            .tabs
+            .last_interacted_tab_hat()
+            .context("expected hat tab")?
+            .is_unsaved();
+        let new_folder = if is_unsaved {
+            let Some(path) = self.pick_hat_folder()? else {
+                return Ok(false);
+            };
+            Some(path)
+        } else {
+            None
         };
-        self.open_hat(gl, &path)
+        let hat = self
+            .tabs
+            .last_interacted_tab_hat_mut()
+            .context("expected hat tab")?;
+        match new_folder {
+            Some(path) => hat.save_as(&path)?,
+            None => {
+                let path = hat.path().to_path_buf();
+                hat.save(&path)?;
+            }
+        }
+        hat.mark_saved();
+        Ok(true)
     }
 
-    fn save_hat(&mut self) -> Result<()> {
-        let last_tab = self
-            .tabs
-            .last_interacted_tab()
-            .context("could not find last interacted tab")?;
-        let Tab::HatElement { hat, .. } = last_tab else {
+    /// Returns `false` if the user cancelled picking a folder.
+    fn save_hat_as(&mut self) -> Result<bool> {
+        if self.tabs.last_interacted_tab_hat().is_none() {
             bail!("expected hat tab");
+        }
+        let Some(path) = self.pick_hat_folder()? else {
+            return Ok(false);
         };
-        hat.save(hat.path())
+        let hat = self
+            .tabs
+            .last_interacted_tab_hat_mut()
+            .context("expected hat tab")?;
+        hat.save_as(&path)?;
+        hat.mark_saved();
+        Ok(true)
     }
 
-    fn export_hat_to_file_as(&mut self) -> Result<()> {
+    /// Returns where the hat was exported to, unless the dialog was cancelled.
+    fn export_hat_to_file_as(&mut self, flatten_frame_delays: bool) -> Result<Option<PathBuf>> {
         let last_tab = self
             .tabs
-            .last_interacted_tab()
+            .last_interacted_tab_mut()
             .context("could not find last interacted tab")?;
         let Tab::HatElement { hat, .. } = last_tab else {
             bail!("expected hat tab");
         };
-        let Some(path) = rfd::FileDialog::new()
+        let Some(path) = self
+            .file_dialog_dirs
+            .dialog(FileDialogKind::ExportFile)
             .add_filter(".hatspp", &["hatspp"])
             .save_file()
         else {
-            return Ok(());
+            return Ok(None);
         };
-        hat.export_to_file(path)
+        self.file_dialog_dirs
+            .remember(FileDialogKind::ExportFile, &path);
+        hat.export_to_file(&path, flatten_frame_delays)?;
+        Ok(Some(path))
     }
+}
 
-    fn export_hat_to_file(&mut self) -> Result<()> {
+impl p!(<mut tabs> EditorApp) {
+    /// Returns where the hat was exported to.
+    fn export_hat_to_file(&mut self, flatten_frame_delays: bool) -> Result<PathBuf> {
         let last_tab = self
             .tabs
-            .last_interacted_tab()
+            .last_interacted_tab_mut()
             .context("could not find last interacted tab")?;
         let Tab::HatElement { hat, .. } = last_tab else {
             bail!("expected hat tab");
         };
-        hat.export_to_file(hat.path().join("hat.hatspp"))
+        let path = hat.path().join("hat.hatspp");
+        hat.export_to_file(&path, flatten_frame_delays)?;
+        Ok(path)
     }
 
     fn can_export(&mut self) -> bool {
         matches!(
             self.tabs.last_interacted_tab(),
-            Some(Tab::HatElement { .. })
+            Some(Tab::HatElement { hat, .. }) if !hat.is_empty()
         )
     }
 
+    fn can_revert(&mut self) -> bool {
+        self.tabs
+            .last_interacted_tab_hat()
+            .is_some_and(|hat| !hat.is_file_backed() && !hat.is_unsaved())
+    }
+
+    /// Reloads the focused hat from disk, dropping all in-memory edits.
+    fn revert_hat(&mut self, gl: &glow::Context) -> Result<()> {
+        let Some(Tab::HatElement {
+            hat,
+            selected_hat_id,
+            ..
+        }) = self.tabs.last_interacted_tab_mut()
+        else {
+            bail!("expected hat tab");
+        };
+        if hat.is_file_backed() {
+            bail!("hats opened from a file can't be reverted");
+        }
+        let reverted_hat = Hat::load(hat.path(), gl)?;
+        //ids are regenerated on load, so find the selected element by what it points to
+        let selected = selected_hat_id
+            .and_then(|id| hat.element(id))
+            .map(|e| (e.base().hat_type, e.base().local_image_path.clone()));
+        *selected_hat_id = selected.and_then(|(hat_type, local_image_path)| {
+            reverted_hat
+                .elements()
+                .find(|e| {
+                    e.base().hat_type == hat_type && e.base().local_image_path == local_image_path
+                })
+                .map(|e| e.id())
+        });
+        *hat = reverted_hat;
+        info!(hat = hat.name(); "hat reverted");
+        Ok(())
+    }
+
     fn can_save(&mut self) -> bool {
         matches!(
             self.tabs.last_interacted_tab(),
-            Some(Tab::HatElement { .. })
+            Some(Tab::HatElement { hat, .. }) if !hat.is_empty()
         )
     }
 
@@ -241,37 +486,86 @@ impl p!(<mut tabs> EditorApp) {
             .find_active_focused()
             .map(|(_, tab)| &mut *tab)
     }
-    fn add_script_template_to_hat(&mut self) {
-        if let Some(Tab::HatElement {
-            hat,
-            selected_hat_id: Some(id),
-            ..
-        }) = self.tabs.last_interacted_tab_mut()
-            && let Some(element) = hat.element(*id)
-        {}
+
+    /// Makes every element texture drawable by egui. Textures are only registered once.
+    fn register_textures(&mut self, frame: &mut eframe::Frame) {
+        for (_, tab) in self.tabs.dock_state.iter_all_tabs_mut() {
+            let Tab::HatElement { hat, .. } = tab else {
+                continue;
+            };
+            for element in hat.elements() {
+                let secondary = element.secondary().map(|secondary| &secondary.texture);
+                for texture in std::iter::once(element.texture()).chain(secondary) {
+                    if texture.egui_id().is_none()
+                        && let Some(native) = texture.native()
+                    {
+                        texture.set_egui_id(frame.register_native_glow_texture(native));
+                    }
+                }
+            }
+        }
+    }
+
+    /// Picks up element images edited in other programs.
+    fn reload_changed_images(&mut self, gl: &glow::Context, ctx: &egui::Context) {
+        let mut any_hats = false;
+        for (_, tab) in self.tabs.dock_state.iter_all_tabs_mut() {
+            let Tab::HatElement { hat, .. } = tab else {
+                continue;
+            };
+            any_hats = true;
+            for path in hat.changed_images() {
+                match hat.reload_image(&path, gl) {
+                    Ok(0) => {}
+                    Ok(_) => info!(hat = hat.name(); "reloaded {:?}", path),
+                    Err(err) => error!(hat = hat.name(); "while reloading {:?}: {}", path, err),
+                }
+            }
+        }
+        //nothing else may wake the editor up while an image is edited elsewhere
+        if any_hats {
+            ctx.request_repaint_after(IMAGE_POLL_INTERVAL);
+        }
+    }
+
+    /// Rebuilds the textures of all open hats, e.g. after the GL context was lost and
+    /// elements started rendering black.
+    fn reload_textures(&mut self, gl: &glow::Context) -> Result<usize> {
+        //registrations of a lost context mean nothing to the new one
+        texture::forget_recycled_textures();
+        let mut reloaded = 0;
+        for (_, tab) in self.tabs.dock_state.iter_all_tabs_mut() {
+            if let Tab::HatElement { hat, .. } = tab {
+                reloaded += hat.reload_textures(gl)?;
+            }
+        }
+        Ok(reloaded)
     }
 
     fn rename_hat(&mut self, name: String) {
         if let Some(hat) = self.tabs.last_interacted_tab_hat_mut() {
             *hat.name_mut() = name;
+            *hat.name_set_by_user_mut() = true;
         }
     }
 }
 
-impl p!(<mut tabs, ui_text, mut hat_name_getter> EditorApp) {
+impl p!(<mut tabs, ui_text, mut hat_name_getter, mut toasts_storage> EditorApp) {
     fn update_hat_getter(&mut self, ctx: &egui::Context) {
         let text = self.ui_text;
         let result = self.hat_name_getter.update(ctx, text);
         if let Some(NameGetterResult::Confirmed(name, variant)) = result {
             match variant {
                 NameGetterVariant::Hat => self.partial_borrow().rename_hat(name),
-                NameGetterVariant::Script => self.partial_borrow().add_script_template_to_hat(),
+                NameGetterVariant::Script(id) => {
+                    self.partial_borrow().add_script_template_to_hat(id, name)
+                }
             }
         }
     }
 }
 
-impl p!(<mut tabs, ui_text, mut console, mut hat_name_getter> EditorApp) {
+impl p!(<mut tabs, ui_text, mut console, mut hat_name_getter, mut toasts_storage, mut file_dialog_dirs, settings, mut recent_hats> EditorApp) {
     fn draw_app(&mut self, gl: &glow::Context, ui: &mut egui::Ui) {
         let frame_data = FrameData {
             ui_text: self.ui_text,
@@ -281,15 +575,28 @@ impl p!(<mut tabs, ui_text, mut console, mut hat_name_getter> EditorApp) {
             clicked_help_tab: false,
             console: None,
             gl,
+            file_dialog_dirs: &mut *self.file_dialog_dirs,
+            animation_sources: vec![],
+            imported_animations: None,
+            open_hats: vec![],
+            copied_element: None,
+            default_animation_delay: self.settings.default_animation_delay,
+            aseprite_layout: self.settings.aseprite_layout,
         };
         let frame_result = self.tabs.ui(ui, frame_data);
+        for (toast_type, message) in frame_result.toasts {
+            self.partial_borrow().add_toast(toast_type, message);
+        }
 
         if frame_result.console.is_some() {
             *self.console = frame_result.console;
         }
         if frame_result.clicked_rename_hat {
-            self.hat_name_getter
-                .open(self.ui_text.get("14").to_string(), NameGetterVariant::Hat);
+            self.hat_name_getter.open(
+                self.ui_text.get("14").to_string(),
+                NameGetterVariant::Hat,
+                Some(Box::new(validate_hat_name)),
+            );
         }
         if frame_result.clicked_open_hat {
             if let Err(err) = self.partial_borrow().open_hat_with_dialog(gl) {
@@ -304,69 +611,286 @@ impl p!(<mut tabs, ui_text, mut console, mut hat_name_getter> EditorApp) {
     }
 }
 
-impl p!(<mut tabs, ui_text, mut toasts, mut toasts_storage, mut console> EditorApp) {
+impl p!(<mut tabs, mut toasts_storage, mut file_dialog_dirs, mut export_report, settings, mut recent_hats> EditorApp) {
+    /// Exports the focused hat right away if nothing is wrong with it, otherwise shows
+    /// what is first. `ask_path` lets the user pick where the file goes.
+    fn request_export(&mut self, ctx: &egui::Context, ask_path: bool) {
+        let Some(hat) = self.tabs.last_interacted_tab_hat() else {
+            return;
+        };
+        let report = hat.export_report();
+        if report.is_clean() {
+            self.partial_borrow().export_hat(ask_path);
+        } else {
+            *self.export_report = Some((report, ask_path));
+            egui_modal::Modal::new(ctx, EXPORT_REPORT_MODAL_ID).open();
+        }
+    }
+}
+
+impl p!(<mut tabs, mut toasts_storage, mut file_dialog_dirs, settings, mut recent_hats> EditorApp) {
+    fn export_hat(&mut self, ask_path: bool) {
+        let hat_name = self
+            .tabs
+            .last_interacted_hat_name()
+            .unwrap_or("")
+            .to_string();
+        let flatten_frame_delays = self.settings.flatten_frame_delays;
+        if ask_path {
+            match self
+                .partial_borrow()
+                .export_hat_to_file_as(flatten_frame_delays)
+            {
+                Ok(Some(path)) => self.recent_hats.push(path),
+                Ok(None) => {}
+                Err(err) => {
+                    error!(hat = hat_name.as_str(); "while exporting hat to file: {}", err);
+                    self.partial_borrow().add_toast(
+                        ToastType::Error,
+                        format!(r#"could not export hat "{}": {}"#, &hat_name, err),
+                    );
+                }
+            }
+            return;
+        }
+        match self
+            .partial_borrow()
+            .export_hat_to_file(flatten_frame_delays)
+        {
+            Ok(path) => {
+                self.recent_hats.push(path);
+                self.partial_borrow().add_toast(
+                    ToastType::Success,
+                    format!(r#"hat "{}" was exported successfully"#, &hat_name),
+                );
+            }
+            Err(err) => {
+                error!(hat = hat_name.as_str(); "while exporting hat to file: {}", err);
+                self.partial_borrow().add_toast(
+                    ToastType::Error,
+                    format!(r#"could not export hat "{}": {}"#, &hat_name, err),
+                );
+            }
+        }
+    }
+}
+
+impl p!(<mut tabs, ui_text, mut toasts_storage, mut file_dialog_dirs, mut export_report, settings, mut recent_hats> EditorApp) {
+    fn draw_export_report_modal(&mut self, ctx: &egui::Context) {
+        let text = self.ui_text;
+        let modal = egui_modal::Modal::new(ctx, EXPORT_REPORT_MODAL_ID);
+        modal.show(|ui| {
+            modal.title(ui, text.get("155"));
+            modal.frame(ui, |ui| {
+                let Some((report, _)) = self.export_report.as_ref() else {
+                    return;
+                };
+                egui::ScrollArea::vertical()
+                    .max_height(300.0)
+                    .show(ui, |ui| {
+                        for (key, problems, color) in [
+                            ("156", &report.errors, ui.visuals().error_fg_color),
+                            ("157", &report.warnings, ui.visuals().warn_fg_color),
+                        ] {
+                            if problems.is_empty() {
+                                continue;
+                            }
+                            ui.colored_label(color, text.get(key));
+                            for problem in problems {
+                                ui.label(format!("• {}", problem));
+                            }
+                        }
+                    });
+            });
+            modal.buttons(ui, |ui| {
+                let can_export = self
+                    .export_report
+                    .as_ref()
+                    .is_some_and(|(report, _)| report.errors.is_empty());
+                if can_export && modal.suggested_button(ui, text.get("158")).clicked() {
+                    modal.close();
+                    if let Some((_, ask_path)) = self.export_report.take() {
+                        self.partial_borrow().export_hat(ask_path);
+                    }
+                } else if modal.button(ui, text.get("43")).clicked() {
+                    modal.close();
+                    *self.export_report = None;
+                }
+            });
+        });
+    }
+}
+
+impl p!(<mut tabs, ui_text, mut toasts_storage, mut file_dialog_dirs, mut export_report, settings, mut recent_hats> EditorApp) {
+    /// Runs an action the same way whether it came from the menu or a keyboard shortcut.
+    fn run_editor_action(&mut self, action: EditorAction, gl: &glow::Context, ctx: &egui::Context) {
+        let hat_name = self
+            .tabs
+            .last_interacted_hat_name()
+            .unwrap_or("")
+            .to_string();
+        match action {
+            EditorAction::NewHat => {
+                if let Err(err) = self.partial_borrow().add_new_hat_template() {
+                    error!("while adding new hat: {}", err.to_string());
+                }
+            }
+            EditorAction::OpenHat => {
+                if let Err(err) = self.partial_borrow().open_hat_with_dialog(gl) {
+                    error!("while opening hat: {}", err.to_string());
+                }
+            }
+            EditorAction::SaveHat | EditorAction::SaveHatAs => {
+                if !self.partial_borrow().can_save() {
+                    return;
+                }
+                let saved = if action == EditorAction::SaveHatAs {
+                    self.partial_borrow().save_hat_as()
+                } else {
+                    self.partial_borrow().save_hat()
+                };
+                match saved {
+                    Ok(true) => self.partial_borrow().add_toast(
+                        ToastType::Success,
+                        format!(r#"hat "{}" saved successfully"#, &hat_name),
+                    ),
+                    Ok(false) => {}
+                    Err(err) => {
+                        error!(hat = hat_name.as_str(); "while saving hat: {}", err);
+                        self.partial_borrow().add_toast(
+                            ToastType::Error,
+                            format!(r#"could not save hat "{}": {}"#, &hat_name, err),
+                        );
+                    }
+                }
+            }
+            EditorAction::ExportHat => {
+                if self.partial_borrow().can_export() {
+                    self.partial_borrow().request_export(ctx, false);
+                }
+            }
+            EditorAction::Undo | EditorAction::Redo => {
+                let redo = action == EditorAction::Redo;
+                if let Err(err) = self.tabs.undo_last_interacted(gl, redo) {
+                    error!(hat = hat_name.as_str(); "while undoing: {}", err);
+                    let message = format!(r#"{} "{}""#, self.ui_text.get("185"), &hat_name);
+                    self.partial_borrow().add_toast(ToastType::Error, message);
+                }
+            }
+        }
+    }
+}
+
+impl p!(<mut tabs, ui_text, mut toasts_storage, mut file_dialog_dirs, mut export_report, settings, mut recent_hats> EditorApp) {
+    fn handle_keybinds(&mut self, gl: &glow::Context, ctx: &egui::Context) {
+        for action in self.settings.keybinds.triggered(ctx) {
+            self.partial_borrow().run_editor_action(action, gl, ctx);
+        }
+    }
+}
+
+impl p!(<mut tabs, ui_text, mut toasts, mut toasts_storage, mut console, mut settings, mut images_to_clean, mut file_dialog_dirs, mut hat_properties, mut hat_changes, mut project, mut export_report, mut recent_hats, mut hat_name_getter> EditorApp) {
     fn draw_menu(&mut self, gl: &glow::Context, ui: &mut egui::Ui) {
         let (text, self2) = self.extract_ui_text();
         egui::menu::bar(ui, |ui| {
             ui.menu_button(text.get("Hat"), |ui| {
-                let hat_name = self2
-                    .tabs
-                    .last_interacted_hat_name()
-                    .unwrap_or("")
-                    .to_string();
-                if ui.button(text.get("New")).clicked() {
-                    if let Err(err) = self2.partial_borrow().add_new_hat_template() {
-                        error!("while adding new hat: {}", err.to_string());
+                let [
+                    new_shortcut,
+                    open_shortcut,
+                    save_shortcut,
+                    save_as_shortcut,
+                    export_shortcut,
+                ] = [
+                    EditorAction::NewHat,
+                    EditorAction::OpenHat,
+                    EditorAction::SaveHat,
+                    EditorAction::SaveHatAs,
+                    EditorAction::ExportHat,
+                ]
+                .map(|action| self2.settings.keybinds.shortcut_text(ui.ctx(), action));
+                if ui
+                    .add(egui::Button::new(text.get("New")).shortcut_text(new_shortcut))
+                    .clicked()
+                {
+                    self2
+                        .partial_borrow()
+                        .run_editor_action(EditorAction::NewHat, gl, ui.ctx());
+                    ui.close_menu();
+                }
+                if ui.button(text.get("105")).clicked() {
+                    self2.partial_borrow().add_new_unsaved_hat();
+                    ui.close_menu();
+                }
+                ui.menu_button(text.get("129"), |ui| {
+                    let animation_delay = self2.settings.default_animation_delay;
+                    let layout = self2.settings.aseprite_layout;
+                    for template in HatTemplate::iter() {
+                        if ui.button(text.get(template.translate_key())).clicked() {
+                            if let Err(err) = self2.partial_borrow().add_new_hat_from_template(
+                                template,
+                                animation_delay,
+                                layout,
+                                gl,
+                            ) {
+                                error!("while creating hat from template: {}", err.to_string());
+                            }
+                            ui.close_menu();
+                        }
                     }
+                });
+                if ui
+                    .add(egui::Button::new(text.get("Open")).shortcut_text(open_shortcut))
+                    .clicked()
+                {
+                    self2
+                        .partial_borrow()
+                        .run_editor_action(EditorAction::OpenHat, gl, ui.ctx());
                     ui.close_menu();
                 }
-                if ui.button(text.get("Open")).clicked() {
-                    if let Err(err) = self2.partial_borrow().open_hat_with_dialog(gl) {
-                        error!("while opening hat: {}", err.to_string());
+                if ui.button(text.get("148")).clicked() {
+                    if let Err(err) = self2.partial_borrow().open_project_with_dialog() {
+                        error!("while opening project: {}", err.to_string());
                     }
                     ui.close_menu();
                 }
+                if self2.project.is_some() && ui.button(text.get("149")).clicked() {
+                    *self2.project = None;
+                    ui.close_menu();
+                }
                 if ui
                     .add_enabled(
                         self2.partial_borrow().can_save(),
-                        egui::Button::new(text.get("Save")),
+                        egui::Button::new(text.get("Save")).shortcut_text(save_shortcut),
                     )
                     .clicked()
                 {
-                    if let Err(err) = self2.partial_borrow().save_hat() {
-                        error!("while saving hat: {}", err.to_string());
-                        self2.partial_borrow().add_toast(
-                            ToastType::Error,
-                            format!(r#"could not save hat "{}""#, &hat_name),
-                        );
-                    } else {
-                        self2.partial_borrow().add_toast(
-                            ToastType::Success,
-                            format!(r#"hat "{}" saved successfully"#, &hat_name),
-                        );
-                    }
+                    self2
+                        .partial_borrow()
+                        .run_editor_action(EditorAction::SaveHat, gl, ui.ctx());
+                    ui.close_menu();
+                }
+                if ui
+                    .add_enabled(
+                        self2.partial_borrow().can_save(),
+                        egui::Button::new(text.get("167")).shortcut_text(save_as_shortcut),
+                    )
+                    .clicked()
+                {
+                    self2
+                        .partial_borrow()
+                        .run_editor_action(EditorAction::SaveHatAs, gl, ui.ctx());
                     ui.close_menu();
                 }
                 if ui
                     .add_enabled(
                         self2.partial_borrow().can_export(),
-                        egui::Button::new(text.get("Export")),
+                        egui::Button::new(text.get("Export")).shortcut_text(export_shortcut),
                     )
                     .clicked()
                 {
-                    if let Err(err) = self2.partial_borrow().export_hat_to_file() {
-                        error!("while exporting hat to file: {}", err.to_string());
-                        self2.partial_borrow().add_toast(
-                            ToastType::Error,
-                            format!(r#"could not export hat "{}""#, &hat_name),
-                        );
-                    } else {
-                        self2.partial_borrow().add_toast(
-                            ToastType::Success,
-                            format!(r#"hat "{}" was exported successfully"#, &hat_name),
-                        );
-                    }
-
+                    self2
+                        .partial_borrow()
+                        .run_editor_action(EditorAction::ExportHat, gl, ui.ctx());
                     ui.close_menu();
                 }
                 if ui
@@ -376,16 +900,106 @@ impl p!(<mut tabs, ui_text, mut toasts, mut toasts_storage, mut console> EditorA
                     )
                     .clicked()
                 {
-                    if let Err(err) = self2.partial_borrow().export_hat_to_file_as() {
-                        error!("while exporting hat to file: {}", err.to_string())
+                    self2.partial_borrow().request_export(ui.ctx(), true);
+                    ui.close_menu();
+                }
+                if ui
+                    .add_enabled(
+                        self2.partial_borrow().can_revert(),
+                        egui::Button::new(text.get("75")),
+                    )
+                    .clicked()
+                {
+                    egui_modal::Modal::new(ui.ctx(), REVERT_MODAL_ID).open();
+                    ui.close_menu();
+                }
+                if ui
+                    .add_enabled(
+                        self2.partial_borrow().can_revert(),
+                        egui::Button::new(text.get("90")),
+                    )
+                    .clicked()
+                {
+                    self2.partial_borrow().find_images_to_clean(ui.ctx());
+                    ui.close_menu();
+                }
+                if ui
+                    .add_enabled(
+                        self2.partial_borrow().can_revert(),
+                        egui::Button::new(text.get("137")),
+                    )
+                    .clicked()
+                {
+                    self2.partial_borrow().show_hat_changes(ui.ctx());
+                    ui.close_menu();
+                }
+                if ui
+                    .add_enabled(
+                        self2.tabs.last_interacted_tab_hat().is_some(),
+                        egui::Button::new(text.get("125")),
+                    )
+                    .clicked()
+                {
+                    if let Some(hat) = self2.tabs.last_interacted_tab_hat() {
+                        *self2.hat_properties = HatProperties::new(hat.metadata());
                     }
+                    egui_modal::Modal::new(ui.ctx(), HAT_PROPERTIES_MODAL_ID).open();
                     ui.close_menu();
                 }
-                ui.collapsing(text.get("Recent"), |ui| {});
+                ui.collapsing(text.get("Recent"), |ui| {
+                    if self2.recent_hats.paths().is_empty() {
+                        ui.label(text.get("174"));
+                    }
+                    let mut clicked = None;
+                    for path in self2.recent_hats.paths() {
+                        let label = path.to_string_lossy();
+                        let response = if path.exists() {
+                            ui.button(label)
+                        } else {
+                            let label =
+                                egui::RichText::new(label).color(ui.visuals().weak_text_color());
+                            ui.button(label).on_hover_text(text.get("175"))
+                        };
+                        if response.clicked() {
+                            clicked = Some(path.clone());
+                        }
+                    }
+                    if let Some(path) = clicked {
+                        self2.partial_borrow().open_recent_hat(gl, &path);
+                        ui.close_menu();
+                    }
+                });
             });
 
+            ui.menu_button(text.get("163"), |ui| {
+                let (can_undo, can_redo) = self2
+                    .tabs
+                    .last_interacted_undo_stack()
+                    .map(|undo_stack| (undo_stack.can_undo(), undo_stack.can_redo()))
+                    .unwrap_or_default();
+                for (action, enabled) in [
+                    (EditorAction::Undo, can_undo),
+                    (EditorAction::Redo, can_redo),
+                ] {
+                    let shortcut = self2.settings.keybinds.shortcut_text(ui.ctx(), action);
+                    if ui
+                        .add_enabled(
+                            enabled,
+                            egui::Button::new(text.get(action.translate_key()))
+                                .shortcut_text(shortcut),
+                        )
+                        .clicked()
+                    {
+                        self2
+                            .partial_borrow()
+                            .run_editor_action(action, gl, ui.ctx());
+                        ui.close_menu();
+                    }
+                }
+            });
             ui.menu_button(text.get("Elements"), |ui| {
-                if let Err(err) = self2.partial_borrow().draw_elements_menu(gl, ui) {
+                let layout = self2.settings.aseprite_layout;
+                if let Err(err) = self2.partial_borrow().draw_elements_menu(gl, ui, layout) {
                     error!("{}", err.to_string());
                 }
             });
@@ -393,9 +1007,15 @@ impl p!(<mut tabs, ui_text, mut toasts, mut toasts_storage, mut console> EditorA
                 if ui.button(text.get("Open help tab")).clicked() {
                     ui.close_menu();
                 }
+                ui.collapsing(text.get("84"), |ui| {
+                    for anim_type in AnimType::iter() {
+                        ui.label(anim_type.tag_name())
+                            .on_hover_text(text.get(anim_type.translate_key()));
+                    }
+                });
             });
             ui.menu_button(text.get("Settings"), |ui| {
-                self2.partial_borrow().draw_settings_menu(ui);
+                self2.partial_borrow().draw_settings_menu(text, ui);
             });
             ui.menu_button(text.get("Other"), |ui| {
                 if ui.button(text.get("Open home tab")).clicked() {
@@ -407,14 +1027,88 @@ impl p!(<mut tabs, ui_text, mut toasts, mut toasts_storage, mut console> EditorA
                     self2.tabs.open_console_tab(console, text);
                     ui.close_menu();
                 }
+                if ui.button(text.get("56")).clicked() {
+                    EditorApp::print_missing_translations(text);
+                    ui.close_menu();
+                }
+                if ui.button(text.get("103")).clicked() {
+                    if let Err(err) = EditorApp::export_schema() {
+                        error!("while exporting schema: {}", err.to_string());
+                    }
+                    ui.close_menu();
+                }
+                if ui.button(text.get("119")).clicked() {
+                    match self2.partial_borrow().reload_textures(gl) {
+                        Ok(reloaded) => info!("{} textures rebuilt", reloaded),
+                        Err(err) => error!("while reloading textures: {}", err.to_string()),
+                    }
+                    ui.close_menu();
+                }
             });
         });
     }
 }
 
-impl p!(<mut tabs, ui_text> EditorApp) {
-    fn draw_elements_menu(&mut self, gl: &glow::Context, ui: &mut egui::Ui) -> Result<()> {
+impl p!(<mut tabs, ui_text, mut file_dialog_dirs> EditorApp) {
+    /// A hat without a folder; one gets picked on the first save.
+    fn add_new_unsaved_hat(&mut self) {
+        let name = self.tabs.new_hat_tab_name(self.ui_text);
+        let hat = Hat::new(Path::new(""), &name);
+        info!("new unsaved hat {} created", &name);
+        self.tabs
+            .dock_state
+            .push_to_focused_leaf(Tab::new_hat_tab(hat, None));
+    }
+
+    /// Creates an unsaved hat with the template's elements, asking for an image for each.
+    /// Nothing is created if any of the dialogs is cancelled.
+    fn add_new_hat_from_template(
+        &mut self,
+        template: HatTemplate,
+        animation_delay: f32,
+        layout: AsepriteLayout,
+        gl: &glow::Context,
+    ) -> Result<()> {
+        let name = self.tabs.new_hat_tab_name(self.ui_text);
+        let mut hat = Hat::new(Path::new(""), &name);
+        for element in template.elements(animation_delay) {
+            let hat_type = element.base().hat_type;
+            let Some(path) = self
+                .file_dialog_dirs
+                .dialog(FileDialogKind::ImportImage)
+                .set_title(self.ui_text.get(hat_type.translate_key()))
+                .add_filter("Image", &["png", "aseprite"])
+                .pick_file()
+            else {
+                return Ok(());
+            };
+            self.file_dialog_dirs
+                .remember(FileDialogKind::ImportImage, &path);
+            let image = Image::new(&path)
+                .context(format!("could not load image at {:?}", &path))?
+                .with_aseprite_layout(layout)
+                .with_animation_delay(animation_delay);
+            hat.add_element_data(element, image, gl)?
+                .context(format!("{:?} element was rejected", hat_type))?;
+        }
+        info!("new unsaved hat {} created from {:?}", &name, template);
+        self.tabs
+            .dock_state
+            .push_to_focused_leaf(Tab::new_hat_tab(hat, None));
+        Ok(())
+    }
+}
+
+impl p!(<mut tabs, ui_text, mut file_dialog_dirs, mut hat_name_getter> EditorApp) {
+    fn draw_elements_menu(
+        &mut self,
+        gl: &glow::Context,
+        ui: &mut egui::Ui,
+        layout: AsepriteLayout,
+    ) -> Result<()> {
         let text = &self.ui_text;
+        let file_dialog_dirs = &mut self.file_dialog_dirs;
+        let hat_name_getter = &mut self.hat_name_getter;
         let Some(Tab::HatElement { hat, .. }) = self.tabs.last_interacted_tab_mut() else {
             return Ok(());
         };
@@ -422,15 +1116,19 @@ impl p!(<mut tabs, ui_text> EditorApp) {
             let wereable_key = text.get(HatType::Wearable.translate_key());
             EditorApp::set_min_width(ui, wereable_key);
             if hat.wereable().is_none() && ui.button(wereable_key).clicked() {
-                let Some(path) = rfd::FileDialog::new()
+                let Some(path) = file_dialog_dirs
+                    .dialog(FileDialogKind::ImportImage)
                     .add_filter("Image", &["png", "aseprite"])
                     .pick_file()
                 else {
                     return Ok(());
                 };
-                let wereable = WearableHat::load_from_path(&path, gl)
+                file_dialog_dirs.remember(FileDialogKind::ImportImage, &path);
+                let image = Image::new(&path)
+                    .context(format!("could not load image at {:?}", &path))?
+                    .with_aseprite_layout(layout);
+                hat.add_element_data(HatElementData::Wearable(Default::default()), image, gl)
                     .context("could not load wereable hat")?;
-                hat.add_element(wereable);
                 ui.close_menu();
             }
             Ok(())
@@ -451,48 +1149,21 @@ impl p!(<mut tabs, ui_text> EditorApp) {
                 //     ui.close_menu();
                 //     break;
                 // }
-                if response.clicked_by(egui::PointerButton::Secondary) {
-                    let pos = ui.input(|i| i.pointer.latest_pos().unwrap());
-                    egui::Window::new("hi")
-                        .current_pos(pos)
-                        .collapsible(false)
-                        .title_bar(false)
-                        .show(ui.ctx(), |ui| if ui.button("Add script").clicked() {});
-                }
-                // response.context_menu(|ui| {
-                //     if ui.input(|i| i.pointer.button_pressed(egui::PointerButton::Primary)) {
-                //         dbg!("hi3");
-                //     }
-                //     let script_attached = element.base().local_script_path.is_some();
-                //     let response =
-                //         ui.add_enabled(!script_attached, egui::Button::new(text.get("33")));
-                //     // if response.clicked()
-                //     //     || response.clicked_elsewhere() && response.contains_pointer()
-                //     // {
-                //     //     dbg!("hi");
-                //     // }
-                //     // if
-                //     //     .clicked()
-                //     // {
-                //     //     self.hat_name_getter
-                //     //         .open(text.get("36").to_string(), NameGetterVariant::Script);
-                //     //     should_exit = true;
-                //     //     ui.close_menu();
-                //     // }
-                //     if ui
-                //         .add_enabled(!script_attached, egui::Button::new(text.get("34")))
-                //         .clicked()
-                //     {
-                //         should_exit = true;
-                //         ui.close_menu();
-                //     }
-                //     if ui
-                //         .add_enabled(script_attached, egui::Button::new(text.get("35")))
-                //         .clicked()
-                //     {
-                //         ui.close_menu();
-                //     }
-                // });
+                response.context_menu(|ui| {
+                    let can_add_script = element.base().local_script_path.is_none()
+                        && script_template(element.base().hat_type).is_some();
+                    if ui
+                        .add_enabled(can_add_script, egui::Button::new(text.get("33")))
+                        .clicked()
+                    {
+                        hat_name_getter.open(
+                            text.get("36").to_string(),
+                            NameGetterVariant::Script(element.id()),
+                            Some(Box::new(validate_file_name)),
+                        );
+                        ui.close_menu();
+                    }
+                });
             }
             //     .context_menu(|ui| {
             //         ui.label("haha");
@@ -514,15 +1185,17 @@ impl p!(<mut tabs, ui_text> EditorApp) {
         Ok(())
     }
     fn add_new_hat_template(&mut self) -> Result<()> {
-        //TODO: remove the absolute path
-        let Some(path) = rfd::FileDialog::new()
-            .set_directory("/home/palas/Documents/projects/rust-projects/hpp-editor-v2/")
+        let Some(path) = self
+            .file_dialog_dirs
+            .dialog(FileDialogKind::OpenHat)
             .pick_folder()
         else {
             return Ok(());
         };
+        self.file_dialog_dirs
+            .remember(FileDialogKind::OpenHat, &path);
         let data_path = path.join("data.json");
-        std::fs::create_dir(path.join("images"))
+        std::fs::create_dir(path.join(DEFAULT_IMAGES_DIR))
             .context(format!("could not create images directory at {:?}", &path))?;
         std::fs::create_dir(path.join("src"))
             .context(format!("could not create src directory at {:?}", &path))?;
@@ -553,36 +1226,711 @@ impl p!(<mut tabs, ui_text> EditorApp) {
     }
 }
 
-impl p!(<> EditorApp) {
-    // fn save_hat_as(&mut self) -> Result<()> {
-    //     let last_tab = self
-    //         .last_interacted_tab_mut()
-    //         .context("could not find last interacted tab")?;
-    //     let Tab::HatElement { hat, .. } = last_tab else {
-    //         bail!("expected hat tab");
-    //     };
-    //     hat.save_as()
-    // }
+impl p!(<mut tabs, ui_text, mut toasts_storage> EditorApp) {
+    /// Creates the element's script and checks the hat's files again, since it now names
+    /// one more.
+    fn add_script_template_to_hat(&mut self, id: HatElementId, name: String) {
+        let Some(hat) = self.tabs.last_interacted_tab_hat_mut() else {
+            return;
+        };
+        let result = hat.add_script(id, &name).and_then(|path| {
+            info!(hat = hat.name(), element = id.0; "script {:?} added", path);
+            Ok(hat.check_files_integrity()?)
+        });
+        if let Err(err) = result {
+            error!(hat = hat.name(), element = id.0; "while adding script: {}", err);
+            let message = format!(r#"{} "{}""#, self.ui_text.get("183"), hat.name());
+            self.partial_borrow().add_toast(ToastType::Error, message);
+        }
+    }
+
+    fn draw_revert_modal(&mut self, gl: &glow::Context, ctx: &egui::Context) {
+        let text = self.ui_text;
+        let modal = egui_modal::Modal::new(ctx, REVERT_MODAL_ID);
+        modal.show(|ui| {
+            modal.frame(ui, |ui| {
+                ui.label(text.get("76"));
+            });
+            modal.buttons(ui, |ui| {
+                if modal.caution_button(ui, text.get("47")).clicked() {
+                    modal.close();
+                    if let Err(err) = self.partial_borrow().revert_hat(gl) {
+                        error!("while reverting hat: {}", err.to_string());
+                        self.partial_borrow()
+                            .add_toast(ToastType::Error, text.get("190").to_string());
+                    }
+                } else if modal.button(ui, text.get("48")).clicked() {
+                    modal.close();
+                }
+            });
+        });
+    }
+}
+
+impl p!(<mut tabs, ui_text, mut toasts_storage, mut images_to_clean> EditorApp) {
+    fn find_images_to_clean(&mut self, ctx: &egui::Context) {
+        let text = self.ui_text;
+        let Some(hat) = self.tabs.last_interacted_tab_hat() else {
+            return;
+        };
+        let hat_name = hat.name().to_string();
+        match hat.unreferenced_images() {
+            Ok(images) if images.is_empty() => {
+                self.partial_borrow().add_toast(
+                    ToastType::Info,
+                    format!(r#"{} "{}""#, text.get("186"), hat_name),
+                );
+            }
+            Ok(images) => {
+                *self.images_to_clean = images;
+                egui_modal::Modal::new(ctx, CLEAN_IMAGES_MODAL_ID).open();
+            }
+            Err(err) => {
+                error!("while looking for unused images: {}", err.to_string());
+                self.partial_borrow()
+                    .add_toast(ToastType::Error, text.get("187").to_string());
+            }
+        }
+    }
+
+    fn clean_images(&mut self) -> Result<()> {
+        let images = std::mem::take(self.images_to_clean);
+        let hat = self
+            .tabs
+            .last_interacted_tab_hat()
+            .context("expected hat tab")?;
+        let removed = hat.remove_unreferenced_images(&images)?;
+        info!(hat = hat.name(); "removed {} unused images from {:?}", removed, hat.path());
+        Ok(())
+    }
+}
+
+impl p!(<mut tabs, ui_text, mut toasts_storage, mut images_to_clean> EditorApp) {
+    fn draw_clean_images_modal(&mut self, ctx: &egui::Context) {
+        let text = self.ui_text;
+        let modal = egui_modal::Modal::new(ctx, CLEAN_IMAGES_MODAL_ID);
+        modal.show(|ui| {
+            modal.frame(ui, |ui| {
+                ui.label(text.get("91"));
+                egui::ScrollArea::vertical()
+                    .max_height(200.0)
+                    .show(ui, |ui| {
+                        for path in self.images_to_clean.iter() {
+                            let file_name = path.file_name().unwrap_or(path.as_os_str());
+                            ui.label(file_name.to_string_lossy());
+                        }
+                    });
+            });
+            modal.buttons(ui, |ui| {
+                if modal.caution_button(ui, text.get("47")).clicked() {
+                    modal.close();
+                    if let Err(err) = self.partial_borrow().clean_images() {
+                        error!("while cleaning images folder: {}", err.to_string());
+                        self.partial_borrow()
+                            .add_toast(ToastType::Error, text.get("188").to_string());
+                    }
+                } else if modal.button(ui, text.get("48")).clicked() {
+                    modal.close();
+                    self.images_to_clean.clear();
+                }
+            });
+        });
+    }
+}
+
+/// Hat errors say what exactly is wrong with the hat, so they are worth showing to the
+/// user. Anything else only goes to the log.
+fn open_hat_error_message(text: &UiText, path: &Path, err: &anyhow::Error) -> String {
+    match err.downcast_ref::<HatError>() {
+        Some(hat_err) => format!("{} {:?}: {}", text.get("191"), path, hat_err),
+        None => format!("{} {:?}", text.get("191"), path),
+    }
+}
+
+impl p!(<mut toasts_storage> EditorApp) {
+    /// Loads the hat at `path` into a new tab, letting the user know about anything that
+    /// was fixed up along the way.
+    fn load_hat_tab(&mut self, gl: &glow::Context, path: impl AsRef<Path>) -> Result<Tab> {
+        let hat = if path
+            .as_ref()
+            .extension()
+            .is_some_and(|extension| extension == HPP_EXTENSION)
+        {
+            Hat::load_from_file(path, gl)?
+        } else {
+            Hat::load(path, gl)?
+        };
+        info!(hat = hat.name(); "hat loaded successfully");
+        let selected_hat_id = hat.elements().next().map(|e| e.id());
+        //add textures to reloader
+        Ok(Tab::new_hat_tab(hat, selected_hat_id))
+    }
+}
+
+impl p!(<mut tabs, mut toasts_storage, mut recent_hats> EditorApp) {
+    /// Hats that are open already get their tab focused instead.
+    fn open_hat(&mut self, gl: &glow::Context, path: impl AsRef<Path>) -> Result<()> {
+        if self.tabs.focus_hat_tab(path.as_ref()) {
+            info!(
+                "hat {:?} is already open, switched to its tab",
+                path.as_ref()
+            );
+            self.recent_hats.push(path);
+            return Ok(());
+        }
+        let tab = self.partial_borrow().load_hat_tab(gl, &path)?;
+        self.tabs.dock_state.push_to_focused_leaf(tab);
+        self.recent_hats.push(path);
+        Ok(())
+    }
+}
+
+impl p!(<mut tabs, ui_text, mut toasts_storage, mut recent_hats> EditorApp) {
+    /// Recent hats that were moved or deleted are dropped from the list instead.
+    fn open_recent_hat(&mut self, gl: &glow::Context, path: &Path) {
+        let text = self.ui_text;
+        if !path.exists() {
+            self.recent_hats.remove(path);
+            self.partial_borrow()
+                .add_toast(ToastType::Warn, format!("{:?}: {}", path, text.get("193")));
+            return;
+        }
+        if let Err(err) = self.partial_borrow().open_hat(gl, path) {
+            error!("while opening recent hat {:?}: {}", path, err.to_string());
+            self.partial_borrow()
+                .add_toast(ToastType::Error, open_hat_error_message(text, path, &err));
+        }
+    }
+
+    fn handle_dropped_files(&mut self, gl: &glow::Context, ctx: &egui::Context) {
+        let text = self.ui_text;
+        let dropped_files = ctx.input(|i| i.raw.dropped_files.clone());
+        for path in dropped_files.into_iter().filter_map(|file| file.path) {
+            let is_hat_file = path
+                .extension()
+                .is_some_and(|extension| extension == HPP_EXTENSION);
+            if !is_hat_file && !path.is_dir() {
+                self.partial_borrow()
+                    .add_toast(ToastType::Warn, format!("{:?}: {}", &path, text.get("192")));
+                continue;
+            }
+            if let Err(err) = self.partial_borrow().open_hat(gl, &path) {
+                error!("while opening dropped hat {:?}: {}", &path, err.to_string());
+                self.partial_borrow()
+                    .add_toast(ToastType::Error, open_hat_error_message(text, &path, &err));
+            }
+        }
+    }
+}
+
+impl p!(<mut file_dialog_dirs, mut project> EditorApp) {
+    fn open_project_with_dialog(&mut self) -> Result<()> {
+        let Some(path) = self
+            .file_dialog_dirs
+            .dialog(FileDialogKind::OpenHat)
+            .pick_folder()
+        else {
+            return Ok(());
+        };
+        self.file_dialog_dirs
+            .remember(FileDialogKind::OpenHat, &path);
+        let project = Project::open(&path)?;
+        info!(
+            "project {:?} opened with {} hats",
+            &path,
+            project.hats().len()
+        );
+        *self.project = Some(project);
+        Ok(())
+    }
+}
+
+impl p!(<mut project> EditorApp) {
+    fn poll_project(&mut self) {
+        let Some(project) = self.project.as_mut() else {
+            return;
+        };
+        match project.poll() {
+            Ok(true) => info!("project {:?} changed on disk", project.root()),
+            Ok(false) => {}
+            Err(err) => {
+                error!("while rescanning project: {}", err.to_string());
+                *self.project = None;
+            }
+        }
+    }
+}
+
+impl p!(<mut tabs, ui_text, mut toasts_storage, mut project, mut recent_hats> EditorApp) {
+    fn draw_project_panel(&mut self, gl: &glow::Context, ui: &mut egui::Ui) {
+        let Some(project) = self.project.as_ref() else {
+            return;
+        };
+        let text = self.ui_text;
+        ui.heading(project.name())
+            .on_hover_text(project.root().to_string_lossy());
+        ui.separator();
+        if project.hats().is_empty() {
+            ui.label(text.get("150"));
+        }
+        let mut clicked = None;
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            for project_hat in project.hats() {
+                let is_open = self.tabs.is_hat_open(&project_hat.path);
+                if ui
+                    .selectable_label(is_open, &project_hat.name)
+                    .on_hover_text(project_hat.path.to_string_lossy())
+                    .clicked()
+                {
+                    clicked = Some(project_hat.path.clone());
+                }
+            }
+        });
+        if let Some(path) = clicked
+            && let Err(err) = self.partial_borrow().open_hat(gl, &path)
+        {
+            error!("while opening hat {:?}: {}", &path, err.to_string());
+            let message = open_hat_error_message(self.ui_text, &path, &err);
+            self.partial_borrow().add_toast(ToastType::Error, message);
+        }
+    }
+}
+
+impl p!(<mut tabs, mut toasts_storage, mut file_dialog_dirs, mut recent_hats> EditorApp) {
+    fn open_hat_with_dialog(&mut self, gl: &glow::Context) -> Result<()> {
+        let Some(path) = self
+            .file_dialog_dirs
+            .dialog(FileDialogKind::OpenHat)
+            .pick_folder()
+        else {
+            return Ok(());
+        };
+        self.file_dialog_dirs
+            .remember(FileDialogKind::OpenHat, &path);
+        self.partial_borrow().open_hat(gl, &path)
+    }
+}
 
-    fn draw_settings_menu(&mut self, ui: &mut egui::Ui) {}
+impl p!(<mut tabs, settings> EditorApp) {
+    fn apply_settings_to_hats(&mut self) {
+        for (_, tab) in self.tabs.dock_state.iter_all_tabs_mut() {
+            if let Tab::HatElement { hat, .. } = tab {
+                hat.set_max_pets(self.settings.max_pets);
+            }
+        }
+    }
+}
+
+impl p!(<mut tabs, mut toasts_storage, mut console, ui_text, mut layout_to_restore> EditorApp) {
+    /// Reopens the tabs of the last session. Hats that can't be loaded anymore are skipped.
+    fn restore_layout(&mut self, gl: &glow::Context) {
+        let text = self.ui_text;
+        let Some(layout) = self.layout_to_restore.take() else {
+            return;
+        };
+        let dock_state = layout.filter_map_tabs(|tab| match tab {
+            SavedTab::Home => Some(Tab::new_home_tab(self.ui_text.get("Home tab").to_string())),
+            SavedTab::Console => self
+                .console
+                .take()
+                .map(|console| Tab::new_console_tab(self.ui_text.get("38").to_string(), console)),
+            SavedTab::Hat(path) => {
+                if !path.exists() {
+                    self.partial_borrow()
+                        .add_toast(ToastType::Warn, format!("{}: {:?}", text.get("194"), path));
+                    return None;
+                }
+                match self.partial_borrow().load_hat_tab(gl, path) {
+                    Ok(tab) => Some(tab),
+                    Err(err) => {
+                        error!("while reopening hat at {:?}: {}", path, err.to_string());
+                        self.partial_borrow()
+                            .add_toast(ToastType::Error, open_hat_error_message(text, path, &err));
+                        None
+                    }
+                }
+            }
+        });
+        if dock_state.iter_all_tabs().next().is_some() {
+            self.tabs.set_layout(dock_state);
+        }
+    }
+}
 
-    // fn add_script_template()
+impl p!(<mut ui_text, mut tabs, settings> EditorApp) {
+    /// Switches to the language picked in the settings, if it changed.
+    fn apply_language(&mut self, ctx: &egui::Context) {
+        let language = self.settings.language;
+        if self.ui_text.language() == language {
+            return;
+        }
+        self.ui_text.set_language(language);
+        self.tabs
+            .dock_state
+            .translations
+            .tab_context_menu
+            .close_button = self.ui_text.get("15").to_string();
+        ctx.request_repaint();
+    }
+}
 
+impl p!(<mut tabs, settings, mut last_autosave> EditorApp) {
+    fn autosave_hats(&mut self, ctx: &egui::Context) {
+        let Some(interval) = self.settings.autosave_interval() else {
+            return;
+        };
+        let elapsed = self.last_autosave.elapsed();
+        if elapsed < interval {
+            //nothing else may wake the editor up while it's idle
+            ctx.request_repaint_after(interval - elapsed);
+            return;
+        }
+        *self.last_autosave = Instant::now();
+        for (_, tab) in self.tabs.dock_state.iter_all_tabs() {
+            if let Tab::HatElement { hat, .. } = tab
+                && !hat.is_file_backed()
+                && !hat.is_unsaved()
+                && hat.is_dirty()
+            {
+                match hat.autosave() {
+                    Ok(()) => info!(hat = hat.name(); "hat autosaved"),
+                    Err(err) => error!(hat = hat.name(); "while autosaving hat: {}", err),
+                }
+            }
+        }
+    }
+}
+
+impl p!(<mut tabs, mut autosave_to_recover> EditorApp) {
+    fn check_autosaves(&mut self, ctx: &egui::Context) {
+        if self.autosave_to_recover.is_some() {
+            return;
+        }
+        for (_, tab) in self.tabs.dock_state.iter_all_tabs_mut() {
+            if let Tab::HatElement { hat, .. } = tab
+                && hat.take_newer_autosave()
+            {
+                *self.autosave_to_recover = Some(hat.path().to_path_buf());
+                egui_modal::Modal::new(ctx, RECOVER_AUTOSAVE_MODAL_ID).open();
+                return;
+            }
+        }
+    }
+
+    fn recover_autosave(&mut self, gl: &glow::Context) -> Result<()> {
+        let path = self
+            .autosave_to_recover
+            .take()
+            .context("no autosave to recover")?;
+        let Some(hat) = self
+            .tabs
+            .dock_state
+            .iter_all_tabs_mut()
+            .find_map(|(_, tab)| match tab {
+                Tab::HatElement { hat, .. } if hat.path() == path => Some(hat),
+                _ => None,
+            })
+        else {
+            bail!("hat at {:?} is no longer open", &path);
+        };
+        let recovered_hat = Hat::load_autosave(&path, gl)?;
+        *hat = recovered_hat;
+        info!(hat = hat.name(); "hat recovered from autosave");
+        Ok(())
+    }
+
+    fn discard_autosave(&mut self) -> Result<()> {
+        let path = self
+            .autosave_to_recover
+            .take()
+            .context("no autosave to discard")?;
+        std::fs::remove_file(path.join(AUTOSAVE_FILE))
+            .context(format!("could not remove autosave of {:?}", &path))
+    }
+}
+
+impl p!(<mut tabs, ui_text, mut toasts_storage, mut autosave_to_recover> EditorApp) {
+    fn draw_recover_autosave_modal(&mut self, gl: &glow::Context, ctx: &egui::Context) {
+        let text = self.ui_text;
+        let modal = egui_modal::Modal::new(ctx, RECOVER_AUTOSAVE_MODAL_ID);
+        modal.show(|ui| {
+            modal.frame(ui, |ui| {
+                ui.label(text.get("94"));
+                if let Some(path) = self.autosave_to_recover.as_ref() {
+                    ui.label(path.to_string_lossy());
+                }
+            });
+            modal.buttons(ui, |ui| {
+                if modal.suggested_button(ui, text.get("47")).clicked() {
+                    modal.close();
+                    if let Err(err) = self.partial_borrow().recover_autosave(gl) {
+                        error!("while recovering autosave: {}", err.to_string());
+                        self.partial_borrow()
+                            .add_toast(ToastType::Error, text.get("189").to_string());
+                    }
+                } else if modal.caution_button(ui, text.get("48")).clicked() {
+                    modal.close();
+                    if let Err(err) = self.partial_borrow().discard_autosave() {
+                        error!("while discarding autosave: {}", err.to_string());
+                    }
+                }
+            });
+        });
+    }
+}
+
+impl p!(<mut tabs, mut validation_errors> EditorApp) {
+    fn check_validation_errors(&mut self, ctx: &egui::Context) {
+        if !self.validation_errors.is_empty() {
+            return;
+        }
+        for (_, tab) in self.tabs.dock_state.iter_all_tabs_mut() {
+            if let Tab::HatElement { hat, .. } = tab {
+                let errors = hat.take_validation_errors();
+                if !errors.is_empty() {
+                    *self.validation_errors = errors;
+                    egui_modal::Modal::new(ctx, VALIDATION_ERRORS_MODAL_ID).open();
+                    return;
+                }
+            }
+        }
+    }
+}
+
+impl p!(<ui_text, mut validation_errors> EditorApp) {
+    fn draw_validation_errors_modal(&mut self, ctx: &egui::Context) {
+        let text = self.ui_text;
+        let modal = egui_modal::Modal::new(ctx, VALIDATION_ERRORS_MODAL_ID);
+        modal.show(|ui| {
+            modal.frame(ui, |ui| {
+                ui.label(text.get("120"));
+                for error in self.validation_errors.iter() {
+                    ui.label(format!("• {}", error));
+                }
+            });
+            modal.buttons(ui, |ui| {
+                if modal.button(ui, text.get("15")).clicked() {
+                    modal.close();
+                    self.validation_errors.clear();
+                }
+            });
+        });
+    }
+}
+
+impl p!(<mut tabs, ui_text, mut toasts_storage, mut hat_changes> EditorApp) {
+    fn show_hat_changes(&mut self, ctx: &egui::Context) {
+        let text = self.ui_text;
+        let Some(hat) = self.tabs.last_interacted_tab_hat() else {
+            return;
+        };
+        match hat.changes_since_save() {
+            Ok(changes) => {
+                *self.hat_changes = changes;
+                egui_modal::Modal::new(ctx, HAT_CHANGES_MODAL_ID).open();
+            }
+            Err(err) => {
+                error!(
+                    "while comparing hat with its data.json: {}",
+                    err.to_string()
+                );
+                self.partial_borrow()
+                    .add_toast(ToastType::Error, text.get("195").to_string());
+            }
+        }
+    }
+}
+
+impl p!(<ui_text, mut hat_changes> EditorApp) {
+    fn draw_hat_changes_modal(&mut self, ctx: &egui::Context) {
+        let text = self.ui_text;
+        let modal = egui_modal::Modal::new(ctx, HAT_CHANGES_MODAL_ID);
+        modal.show(|ui| {
+            modal.title(ui, text.get("137"));
+            modal.frame(ui, |ui| {
+                if self.hat_changes.is_empty() {
+                    ui.label(text.get("138"));
+                }
+                egui::ScrollArea::vertical()
+                    .max_height(300.0)
+                    .show(ui, |ui| {
+                        for change in self.hat_changes.iter() {
+                            ui.monospace(change.to_string());
+                        }
+                    });
+            });
+            modal.buttons(ui, |ui| {
+                if modal.button(ui, text.get("15")).clicked() {
+                    modal.close();
+                    self.hat_changes.clear();
+                }
+            });
+        });
+    }
+}
+
+impl p!(<mut tabs, ui_text, mut hat_properties> EditorApp) {
+    fn draw_hat_properties_modal(&mut self, ctx: &egui::Context) {
+        let text = self.ui_text;
+        let modal = egui_modal::Modal::new(ctx, HAT_PROPERTIES_MODAL_ID);
+        modal.show(|ui| {
+            modal.title(ui, text.get("125"));
+            modal.frame(ui, |ui| {
+                egui::Grid::new("hat_properties_grid").show(ui, |ui| {
+                    ui.label(text.get("126"));
+                    ui.text_edit_singleline(&mut self.hat_properties.author);
+                    ui.end_row();
+                    ui.label(text.get("127"));
+                    ui.text_edit_multiline(&mut self.hat_properties.description);
+                    ui.end_row();
+                    ui.label(text.get("128"));
+                    ui.text_edit_singleline(&mut self.hat_properties.tags);
+                    ui.end_row();
+                });
+            });
+            modal.buttons(ui, |ui| {
+                if modal.suggested_button(ui, text.get("123")).clicked() {
+                    modal.close();
+                    if let Some(hat) = self.tabs.last_interacted_tab_hat_mut() {
+                        *hat.metadata_mut() = self.hat_properties.to_metadata();
+                    }
+                } else if modal.button(ui, text.get("43")).clicked() {
+                    modal.close();
+                }
+            });
+        });
+    }
+}
+
+impl p!(<mut window_geometry, mut window_restored> EditorApp) {
+    /// Restores the saved window placement once the monitor size is known, then keeps
+    /// track of where the window is so it can be saved on exit.
+    fn update_window_geometry(&mut self, ctx: &egui::Context) {
+        let (outer_rect, inner_rect, monitor_size) = ctx.input(|i| {
+            let viewport = i.viewport();
+            (
+                viewport.outer_rect,
+                viewport.inner_rect,
+                viewport.monitor_size,
+            )
+        });
+        if !*self.window_restored {
+            let Some(monitor_size) = monitor_size else {
+                return;
+            };
+            *self.window_restored = true;
+            if let Some(geometry) = *self.window_geometry {
+                //keep the window on screen in case the monitor setup changed
+                let size = geometry.size.min(monitor_size);
+                let position = geometry
+                    .position
+                    .clamp(egui::Pos2::ZERO, (monitor_size - size).to_pos2());
+                ctx.send_viewport_cmd(egui::ViewportCommand::InnerSize(size));
+                ctx.send_viewport_cmd(egui::ViewportCommand::OuterPosition(position));
+            }
+            return;
+        }
+        if let (Some(outer_rect), Some(inner_rect)) = (outer_rect, inner_rect) {
+            *self.window_geometry = Some(WindowGeometry {
+                position: outer_rect.min,
+                size: inner_rect.size(),
+            });
+        }
+    }
+}
+
+impl p!(<mut settings> EditorApp) {
+    fn draw_settings_menu(&mut self, text: &UiText, ui: &mut egui::Ui) {
+        self.settings.ui(ui, text);
+    }
+}
+
+impl p!(<> EditorApp) {
     fn draw_elements_add_menu(&mut self, gl: &glow::Context, ui: &mut egui::Ui) {}
 
     fn draw_elements_select_menu(&mut self, gl: &glow::Context, ui: &mut egui::Ui) {}
 }
 
 impl eframe::App for EditorApp {
+    fn save(&mut self, storage: &mut dyn eframe::Storage) {
+        eframe::set_value(storage, SETTINGS_KEY, &self.settings);
+        eframe::set_value(storage, RECENT_HATS_KEY, &self.recent_hats);
+        eframe::set_value(storage, FILE_DIALOG_DIRS_KEY, &self.file_dialog_dirs);
+        if let Some(window_geometry) = &self.window_geometry {
+            eframe::set_value(storage, WINDOW_GEOMETRY_KEY, window_geometry);
+        }
+        //keep the last session's tabs until they are restored
+        if self.layout_to_restore.is_none() {
+            eframe::set_value(storage, TABS_LAYOUT_KEY, &self.tabs.saved_layout());
+        }
+    }
+
     fn update(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
         let gl = &frame.gl().cloned().unwrap();
         ctx.set_pixels_per_point(1.5);
+        self.as_refs_mut()
+            .partial_borrow()
+            .update_window_geometry(ctx);
+        self.as_refs_mut().partial_borrow().restore_layout(gl);
 
+        if let Some(path) = self.startup_hat_path.take()
+            && let Err(err) = self.as_refs_mut().partial_borrow().open_hat(gl, &path)
+        {
+            error!("while opening hat at {:?}: {}", &path, err.to_string());
+            let message = open_hat_error_message(&self.ui_text, &path, &err);
+            self.as_refs_mut()
+                .partial_borrow()
+                .add_toast(ToastType::Error, message);
+        }
+
+        self.as_refs_mut()
+            .partial_borrow()
+            .handle_dropped_files(gl, ctx);
+        self.as_refs_mut().partial_borrow().handle_keybinds(gl, ctx);
+        self.as_refs_mut().partial_borrow().poll_project();
+        self.as_refs_mut()
+            .partial_borrow()
+            .reload_changed_images(gl, ctx);
+        texture::delete_pending_textures(gl);
+        self.as_refs_mut().partial_borrow().register_textures(frame);
+        self.as_refs_mut().partial_borrow().apply_settings_to_hats();
+        self.as_refs_mut().partial_borrow().apply_language(ctx);
+        self.as_refs_mut().partial_borrow().autosave_hats(ctx);
+        self.as_refs_mut().partial_borrow().check_autosaves(ctx);
+        self.as_refs_mut()
+            .partial_borrow()
+            .check_validation_errors(ctx);
+        if self.project.is_some() {
+            egui::SidePanel::left("project_panel").show(ctx, |ui| {
+                self.as_refs_mut()
+                    .partial_borrow()
+                    .draw_project_panel(gl, ui);
+            });
+        }
         egui::CentralPanel::default().show(ctx, |ui| {
             self.as_refs_mut().partial_borrow().draw_menu(gl, ui);
             self.as_refs_mut().partial_borrow().draw_app(gl, ui);
         });
         self.as_refs_mut().partial_borrow().update_hat_getter(ctx);
+        self.as_refs_mut().partial_borrow().draw_revert_modal(gl, ctx);
+        self.as_refs_mut()
+            .partial_borrow()
+            .draw_clean_images_modal(ctx);
+        self.as_refs_mut()
+            .partial_borrow()
+            .draw_recover_autosave_modal(gl, ctx);
+        self.as_refs_mut()
+            .partial_borrow()
+            .draw_validation_errors_modal(ctx);
+        self.as_refs_mut()
+            .partial_borrow()
+            .draw_hat_properties_modal(ctx);
+        self.as_refs_mut()
+            .partial_borrow()
+            .draw_hat_changes_modal(ctx);
+        self.as_refs_mut()
+            .partial_borrow()
+            .draw_export_report_modal(ctx);
         self.as_refs_mut().partial_borrow().display_toasts(ctx);
     }
 }
@@ -0,0 +1,86 @@
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+pub const FILE_DIALOG_DIRS_KEY: &str = "file_dialog_dirs";
+
+/// What a file dialog is opened for. Each kind remembers its own directory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileDialogKind {
+    OpenHat,
+    ImportImage,
+    ExportFile,
+}
+
+/// The directory each kind of file dialog was last used in.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FileDialogDirs {
+    open_hat: Option<PathBuf>,
+    import_image: Option<PathBuf>,
+    export_file: Option<PathBuf>,
+}
+
+impl FileDialogDirs {
+    pub fn dir(&self, kind: FileDialogKind) -> Option<&Path> {
+        match kind {
+            FileDialogKind::OpenHat => self.open_hat.as_deref(),
+            FileDialogKind::ImportImage => self.import_image.as_deref(),
+            FileDialogKind::ExportFile => self.export_file.as_deref(),
+        }
+    }
+
+    fn dir_mut(&mut self, kind: FileDialogKind) -> &mut Option<PathBuf> {
+        match kind {
+            FileDialogKind::OpenHat => &mut self.open_hat,
+            FileDialogKind::ImportImage => &mut self.import_image,
+            FileDialogKind::ExportFile => &mut self.export_file,
+        }
+    }
+
+    /// A dialog starting in the directory `kind` was last used in, or in the documents
+    /// folder the first time.
+    pub fn dialog(&self, kind: FileDialogKind) -> rfd::FileDialog {
+        let dialog = rfd::FileDialog::new();
+        match self
+            .dir(kind)
+            .map(Path::to_path_buf)
+            .or_else(dirs::document_dir)
+        {
+            Some(dir) => dialog.set_directory(dir),
+            None => dialog,
+        }
+    }
+
+    /// Remembers the directory `picked` is in. For picked folders that's their parent, so the
+    /// next dialog shows them alongside their siblings.
+    pub fn remember(&mut self, kind: FileDialogKind, picked: &Path) {
+        if let Some(parent) = picked.parent() {
+            *self.dir_mut(kind) = Some(parent.to_path_buf());
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+
+    use super::{FileDialogDirs, FileDialogKind};
+
+    #[test]
+    fn kinds_are_remembered_separately() {
+        let mut dirs = FileDialogDirs::default();
+        dirs.remember(FileDialogKind::OpenHat, Path::new("hats/cool_hat"));
+        dirs.remember(
+            FileDialogKind::ExportFile,
+            Path::new("exports/cool_hat.hatspp"),
+        );
+
+        assert_eq!(dirs.dir(FileDialogKind::OpenHat), Some(Path::new("hats")));
+        assert_eq!(dirs.dir(FileDialogKind::ImportImage), None);
+        assert_eq!(
+            dirs.dir(FileDialogKind::ExportFile),
+            Some(Path::new("exports"))
+        );
+    }
+}
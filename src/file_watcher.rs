@@ -1,11 +1,21 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
+use eframe::egui;
+use log::warn;
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
 use std::path::Path;
 use std::{
     collections::{HashMap, HashSet},
     path::PathBuf,
     sync::mpsc,
+    time::{Duration, Instant},
 };
 
+use crate::ui_text::UiText;
+
+/// How long a file has to stay untouched after changing before it's reported, so an editor
+/// writing it in several steps triggers a single update.
+const DEBOUNCE: Duration = Duration::from_millis(100);
+
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub struct Ms(pub u128);
 
@@ -23,6 +33,7 @@ pub struct UpdatedFiles<'a> {
     files: &'a HashMap<FileId, PathBuf>,
 }
 
+#[derive(Debug)]
 pub struct FileData {
     path: PathBuf,
     id: FileId,
@@ -33,12 +44,134 @@ impl UpdatedFiles<'_> {
     fn file_accessed(&self, file_id: FileId) -> bool {
         self.files.contains_key(&file_id)
     }
+
+    pub fn iter(&self) -> impl Iterator<Item = (FileId, &Path)> {
+        self.files.iter().map(|(id, path)| (*id, path.as_path()))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+}
+
+/// Learns about changes from file system events instead of checking every file.
+#[derive(Debug)]
+struct EventWatcher {
+    watcher: RecommendedWatcher,
+    events: mpsc::Receiver<notify::Result<notify::Event>>,
+    watched_dirs: HashSet<PathBuf>,
+    /// Watched paths and the directory they are watched through, by their canonical form,
+    /// which is what events refer to.
+    canonical_paths: HashMap<PathBuf, (PathBuf, PathBuf)>,
+    /// Watched paths that changed, with when they last did.
+    pending: HashMap<PathBuf, Instant>,
+}
+
+impl EventWatcher {
+    fn new() -> Result<Self> {
+        let (sender, events) = mpsc::channel();
+        let watcher = notify::recommended_watcher(sender).context("could not create watcher")?;
+        Ok(Self {
+            watcher,
+            events,
+            watched_dirs: Default::default(),
+            canonical_paths: Default::default(),
+            pending: Default::default(),
+        })
+    }
+
+    /// Files are watched through the directory they are in, which keeps working when an
+    /// editor saves by replacing the file.
+    fn watch(&mut self, path: &Path) -> Result<()> {
+        let canonical = path
+            .canonicalize()
+            .context(format!("could not resolve {:?}", path))?;
+        let dir = if canonical.is_dir() {
+            canonical.clone()
+        } else {
+            canonical
+                .parent()
+                .context(format!("{:?} has no parent directory", path))?
+                .to_path_buf()
+        };
+        if !self.watched_dirs.contains(&dir) {
+            self.watcher
+                .watch(&dir, RecursiveMode::NonRecursive)
+                .context(format!("could not watch {:?}", &dir))?;
+            self.watched_dirs.insert(dir.clone());
+        }
+        self.canonical_paths
+            .insert(canonical, (path.to_path_buf(), dir));
+        Ok(())
+    }
+
+    /// Stops watching the directory `path` was watched through once nothing else in it is.
+    fn unwatch(&mut self, path: &Path) {
+        let Some(canonical) = self
+            .canonical_paths
+            .iter()
+            .find(|(_, (watched, _))| watched == path)
+            .map(|(canonical, _)| canonical.clone())
+        else {
+            return;
+        };
+        let (_, dir) = self
+            .canonical_paths
+            .remove(&canonical)
+            .expect("path was just found");
+        self.pending.remove(path);
+        if self
+            .canonical_paths
+            .values()
+            .all(|(_, other_dir)| *other_dir != dir)
+        {
+            //the directory may be gone already, which is fine
+            let _ = self.watcher.unwatch(&dir);
+            self.watched_dirs.remove(&dir);
+        }
+    }
+
+    /// Watched paths whose changes have settled. Directories change along with their
+    /// entries.
+    fn settled_changes(&mut self) -> Vec<PathBuf> {
+        let now = Instant::now();
+        for event in self.events.try_iter().filter_map(Result::ok) {
+            if matches!(event.kind, EventKind::Access(_)) {
+                continue;
+            }
+            for path in &event.paths {
+                let watched = [Some(path.as_path()), path.parent()]
+                    .into_iter()
+                    .flatten()
+                    .find_map(|path| self.canonical_paths.get(path))
+                    .map(|(watched, _)| watched);
+                if let Some(watched) = watched {
+                    self.pending.insert(watched.clone(), now);
+                }
+            }
+        }
+        let settled: Vec<_> = self
+            .pending
+            .iter()
+            .filter(|(_, changed)| now.duration_since(**changed) >= DEBOUNCE)
+            .map(|(path, _)| path.clone())
+            .collect();
+        for path in &settled {
+            self.pending.remove(path);
+        }
+        settled
+    }
 }
 
+#[derive(Debug)]
 pub struct FileWatcher {
     updated_files: HashMap<FileId, PathBuf>,
     files_by_paths: HashMap<PathBuf, FileData>,
     file_id_counter: usize,
+    /// Only created once the first file is watched, so idle watchers don't hold on to
+    /// system resources. `None` until then, and when modification times are polled instead.
+    events: Option<EventWatcher>,
+    polling: bool,
 }
 
 impl FileWatcher {
@@ -47,10 +180,31 @@ impl FileWatcher {
             files_by_paths: Default::default(),
             updated_files: Default::default(),
             file_id_counter: 0,
+            events: None,
+            polling: false,
         }
     }
 
+    #[cfg(not(feature = "polling-watcher"))]
+    fn event_watcher() -> Option<EventWatcher> {
+        EventWatcher::new()
+            .inspect_err(|err| warn!("falling back to polling for file changes: {:#}", err))
+            .ok()
+    }
+
+    #[cfg(feature = "polling-watcher")]
+    fn event_watcher() -> Option<EventWatcher> {
+        None
+    }
+
     pub fn watch_file(&mut self, path: &Path) -> Result<FileId> {
+        if self.events.is_none() && !self.polling {
+            self.events = Self::event_watcher();
+            self.polling = self.events.is_none();
+        }
+        if let Some(events) = &mut self.events {
+            events.watch(path)?;
+        }
         let new_id = self.new_file_id();
         self.files_by_paths.insert(path.to_path_buf(), FileData {
             path: path.to_path_buf(),
@@ -60,9 +214,79 @@ impl FileWatcher {
         Ok(new_id)
     }
 
+    pub fn unwatch_file(&mut self, id: FileId) {
+        let path = self
+            .files_by_paths
+            .values()
+            .find(|file_data| file_data.id == id)
+            .map(|file_data| file_data.path.clone());
+        if let Some(path) = path {
+            self.unwatch_path(&path);
+        }
+    }
+
+    pub fn unwatch_path(&mut self, path: &Path) {
+        if self.files_by_paths.remove(path).is_some()
+            && let Some(events) = &mut self.events
+        {
+            events.unwatch(path);
+        }
+    }
+
+    /// Stops watching everything.
+    pub fn unwatch_all(&mut self) {
+        let paths: Vec<_> = self.files_by_paths.keys().cloned().collect();
+        for path in paths {
+            self.unwatch_path(&path);
+        }
+    }
+
+    /// Every watched file with its path. Watching a path again replaces its entry, so each
+    /// path shows up once.
+    pub fn watched(&self) -> impl Iterator<Item = (FileId, &Path)> {
+        self.files_by_paths
+            .values()
+            .map(|file_data| (file_data.id, file_data.path.as_path()))
+    }
+
+    pub fn watched_count(&self) -> usize {
+        self.files_by_paths.len()
+    }
+
+    /// Lists the watched files and the modification times last seen for them.
+    pub fn draw_debug(&self, ui: &mut egui::Ui, text: &UiText) {
+        let mut files: Vec<_> = self.files_by_paths.values().collect();
+        files.sort_by_key(|file_data| file_data.id.0);
+        ui.label(format!("{}: {}", text.get("182"), files.len()));
+        egui::Grid::new("watched_files")
+            .striped(true)
+            .show(ui, |ui| {
+                for file_data in files {
+                    ui.label(file_data.id.0.to_string());
+                    ui.label(file_data.path.to_string_lossy());
+                    ui.label(format!("{} ms", file_data.last_modification_time.0));
+                    ui.end_row();
+                }
+            });
+    }
+
     pub fn update(&mut self) -> UpdatedFiles {
         self.updated_files.clear();
 
+        if let Some(events) = &mut self.events {
+            for path in events.settled_changes() {
+                if let Some(file_data) = self.files_by_paths.get_mut(&path) {
+                    if let Ok(modify_time) = file_modified_time(&path) {
+                        file_data.last_modification_time = modify_time;
+                    }
+                    self.updated_files.insert(file_data.id, path);
+                }
+            }
+            return UpdatedFiles {
+                files: &self.updated_files,
+            };
+        }
+
         for (path, file_data) in &mut self.files_by_paths {
             if let Ok(new_modify_time) = file_modified_time(path) {
                 if new_modify_time == file_data.last_modification_time {
@@ -94,9 +318,49 @@ impl Default for FileWatcher {
 
 #[cfg(test)]
 mod test {
-    use std::path::Path;
+    use std::{path::Path, time::Duration};
+
+    use crate::test_utils::TempDir;
+
+    use super::{DEBOUNCE, FileWatcher};
+
+    #[test]
+    fn watched_paths_are_unique() {
+        let dir = TempDir::new().unwrap();
+        let first = dir.path().join("first.png");
+        let second = dir.path().join("second.png");
+        std::fs::write(&first, []).unwrap();
+        std::fs::write(&second, []).unwrap();
+
+        let mut watcher = FileWatcher::new();
+        watcher.watch_file(&first).unwrap();
+        watcher.watch_file(&second).unwrap();
+        let id = watcher.watch_file(&first).unwrap();
 
-    use super::FileWatcher;
+        assert_eq!(watcher.watched_count(), 2);
+        assert!(
+            watcher
+                .watched()
+                .any(|(watched_id, path)| watched_id == id && path == first)
+        );
+    }
+
+    #[test]
+    fn unwatched_files_are_not_reported() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("image.png");
+        std::fs::write(&path, [0]).unwrap();
+
+        let mut watcher = FileWatcher::new();
+        let id = watcher.watch_file(&path).unwrap();
+        watcher.unwatch_file(id);
+        assert_eq!(watcher.watched_count(), 0);
+
+        std::thread::sleep(Duration::from_millis(50));
+        std::fs::write(&path, [0, 1]).unwrap();
+        std::thread::sleep(DEBOUNCE * 2);
+        assert!(!watcher.update().file_accessed(id));
+    }
 
     #[test]
     fn file_watcher() {
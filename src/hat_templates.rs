@@ -0,0 +1,70 @@
+use strum::EnumIter;
+
+use crate::{
+    animations::{AnimType, Animation, Frame},
+    hats_data::{HatElementData, WalkingPetData, WearableData, WingsData},
+    ui_text::Translatable,
+};
+
+/// A preset set of elements a new hat can start with. Only the images are left to pick.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumIter)]
+pub enum HatTemplate {
+    BasicWearable,
+    WingsSet,
+    WalkingPet,
+}
+
+impl HatTemplate {
+    /// The template's elements, with placeholder animations using `animation_delay`.
+    pub fn elements(&self, animation_delay: f32) -> Vec<HatElementData> {
+        let wearable = HatElementData::Wearable(WearableData {
+            animations: vec![placeholder_animation(animation_delay)],
+            ..Default::default()
+        });
+        match self {
+            HatTemplate::BasicWearable => vec![wearable],
+            HatTemplate::WingsSet => vec![wearable, HatElementData::Wings(WingsData::default())],
+            HatTemplate::WalkingPet => vec![
+                wearable,
+                HatElementData::WalkingPet(WalkingPetData {
+                    animations: vec![placeholder_animation(animation_delay)],
+                    ..Default::default()
+                }),
+            ],
+        }
+    }
+}
+
+impl Translatable for HatTemplate {
+    fn translate_key(&self) -> &str {
+        match self {
+            HatTemplate::BasicWearable => "130",
+            HatTemplate::WingsSet => "131",
+            HatTemplate::WalkingPet => "132",
+        }
+    }
+}
+
+/// A looping animation showing just the first frame, replaced by the image's tags when
+/// an aseprite file is picked.
+fn placeholder_animation(delay: f32) -> Animation {
+    Animation::new(AnimType::OnDefault, delay, true, vec![Frame::new(0)])
+}
+
+#[cfg(test)]
+mod test {
+    use strum::IntoEnumIterator;
+
+    use crate::{animations::DEFAULT_ANIMATION_DELAY, hats_data::HatData};
+
+    use super::HatTemplate;
+
+    #[test]
+    fn templates_are_valid_hats() {
+        for template in HatTemplate::iter() {
+            let mut data = HatData::new(String::new());
+            data.elements = template.elements(DEFAULT_ANIMATION_DELAY);
+            assert!(data.duplicate_unique_elements().is_empty());
+        }
+    }
+}
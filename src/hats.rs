@@ -1,29 +1,42 @@
 use std::{
     cell::Cell,
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     fs::File,
+    hash::{DefaultHasher, Hash, Hasher},
     io::{Read, Write},
     path::{Path, PathBuf},
+    rc::Rc,
+    time::Duration,
 };
 
 use anyhow::{Context as _, Result, bail};
+use bevy_math::IVec2;
 use downcast_rs::{Downcast, impl_downcast};
-use eframe::{glow, icon_data::from_png_bytes};
+use eframe::{egui, glow, icon_data::from_png_bytes};
+use log::{error, info, warn};
 use pixas::bitmap::Bitmap;
 use serde::{Deserialize, Serialize};
 use strum::IntoEnumIterator;
+use thiserror::Error;
 use uuid::Uuid;
-use zip::{ZipArchive, ZipWriter, write::SimpleFileOptions};
+use zip::{ZipArchive, ZipWriter, result::ZipError, write::SimpleFileOptions};
 
 use crate::{
-    animations::Animation,
+    animations::{AnimType, Animation, add_missing_animations, avalible_animations},
+    file_watcher::FileWatcher,
     hats_data::{
-        ExtraHatData, FlyingPetData, HatBaseData, HatData, HatElementData, HatElementDataRef,
-        HatType, MAX_PETS, WalkingPetData, WearableData, WingsData,
+        DEFAULT_IMAGES_DIR, ExtraHatData, FlyingPetData, HPP_EXTENSION, HatBaseData, HatData,
+        HatElementData, HatElementDataRef, HatMetadata, HatType, MAX_EXTRA_HAT_SIZE, MAX_PETS,
+        SCRIPTS_DIR, WalkingPetData, WearableData, WingsData,
     },
-    image::Image,
-    path_utils::{LocalPath, LocalPathError},
+    image::{
+        AsepriteData, AsepriteLayout, ColorSwap, Image, PixelRegion, TrimBounds, changed_region,
+        ignored_tags_warning, region_bytes, swap_colors, trim_bitmap, write_gif,
+    },
+    json_diff::{JsonChange, json_diff},
+    path_utils::LocalPath,
     texture::Texture,
+    ui_text::UiText,
 };
 
 thread_local! {
@@ -46,6 +59,14 @@ pub struct HatViewMut<'a> {
     animations: Option<&'a mut Vec<Animation>>,
 }
 
+/// A second image drawn over the same frames as the element's own, e.g. a normal or
+/// emissive map.
+#[derive(Debug)]
+pub struct SecondaryImage {
+    pub texture: Texture,
+    pub bitmap: Bitmap,
+}
+
 pub trait HatElement: Downcast + std::fmt::Debug {
     fn base(&self) -> &HatBaseData;
     fn hat_element_data_ref(&self) -> HatElementDataRef;
@@ -54,12 +75,91 @@ pub trait HatElement: Downcast + std::fmt::Debug {
     fn texture_mut(&mut self) -> &mut Texture;
     fn bitmap(&self) -> &Bitmap;
     fn bitmap_mut(&mut self) -> &mut Bitmap;
+    fn secondary(&self) -> Option<&SecondaryImage>;
+    fn secondary_mut(&mut self) -> &mut Option<SecondaryImage>;
     fn animations(&self) -> Option<&Vec<Animation>>;
     fn animations_mut(&mut self) -> Option<&mut Vec<Animation>>;
     fn id(&self) -> HatElementId;
     fn view_mut(&mut self) -> HatViewMut<'_>;
     fn frames_amount(&self) -> u32;
     fn is_unique(&self) -> bool;
+    fn into_bitmap(self: Box<Self>) -> Bitmap;
+
+    fn defined_anim_types(&self) -> Vec<AnimType> {
+        self.animations()
+            .map(|animations| animations.iter().map(|a| a.anim_type).collect())
+            .unwrap_or_default()
+    }
+
+    /// Empty for elements that can't have animations at all.
+    fn missing_anim_types(&self) -> Vec<AnimType> {
+        if self.animations().is_none() {
+            return vec![];
+        }
+        let defined = self.defined_anim_types();
+        avalible_animations(self.base().hat_type)
+            .unwrap_or_default()
+            .iter()
+            .filter(|anim_type| !defined.contains(anim_type))
+            .copied()
+            .collect()
+    }
+
+    /// Writes `animation` to `path` as an animated GIF with the frames' own delays. Frames
+    /// that aren't in the atlas are left out, returns warnings about them.
+    fn export_gif(&self, animation: &Animation, path: &Path) -> Result<Vec<String>> {
+        let frame_size = self.base().frame_size;
+        let mut warnings = vec![];
+        let mut frames = vec![];
+        for frame in &animation.frames {
+            let Some(rect) = self.frame_rect(frame.value) else {
+                warnings.push(format!(
+                    "skipped frame {} of {:?}, the image has {} frames",
+                    frame.value,
+                    animation.anim_type,
+                    self.frames_amount()
+                ));
+                continue;
+            };
+            let region = PixelRegion {
+                min: IVec2::new(rect.min.x as i32, rect.min.y as i32),
+                size: frame_size,
+            };
+            frames.push((
+                region_bytes(self.bitmap(), region),
+                animation.frame_delay(frame),
+            ));
+        }
+        if frames.is_empty() {
+            bail!("{:?} has no frames to export", animation.anim_type);
+        }
+        let file = File::create(path).context(format!("could not create {:?}", path))?;
+        write_gif(file, frame_size, &mut frames, animation.looping)
+            .context(format!("could not write gif to {:?}", path))?;
+        Ok(warnings)
+    }
+
+    /// Where frame `index` is in the texture, in pixels. Frames fill the atlas row by row.
+    fn frame_rect(&self, index: u32) -> Option<egui::Rect> {
+        let frame_size = self.base().frame_size;
+        debug_assert!(
+            frame_size.cmpgt(IVec2::ZERO).all(),
+            "frame size {} should have been clamped when the element was added",
+            frame_size
+        );
+        if index >= self.frames_amount() {
+            return None;
+        }
+        let columns = (self.texture().width() / frame_size.x) as u32;
+        let min = egui::pos2(
+            (index % columns) as f32 * frame_size.x as f32,
+            (index / columns) as f32 * frame_size.y as f32,
+        );
+        Some(egui::Rect::from_min_size(
+            min,
+            egui::vec2(frame_size.x as f32, frame_size.y as f32),
+        ))
+    }
 }
 
 pub trait IsPet {
@@ -77,6 +177,15 @@ impl<T: HatElement + ?Sized> IsPet for T {
 
 impl_downcast!(HatElement);
 
+/// How many frames of `frame_size` fit in a `width`x`height` atlas. None do for frame sizes
+/// that aren't positive, which only malformed data.json files have before they're clamped.
+fn frames_in(width: i32, height: i32, frame_size: IVec2) -> u32 {
+    if frame_size.cmple(IVec2::ZERO).any() {
+        return 0;
+    }
+    ((width / frame_size.x) * (height / frame_size.y)) as u32
+}
+
 macro_rules! impl_hat_element {
     ($t:ident, $($anims_name:ident).+, unique:$e:expr) => {
         paste::paste!{
@@ -101,6 +210,15 @@ macro_rules! impl_hat_element {
                 fn bitmap_mut(&mut self) -> &mut Bitmap {
                     &mut self.bitmap
                 }
+                fn secondary(&self) -> Option<&SecondaryImage> {
+                    self.secondary.as_ref()
+                }
+                fn secondary_mut(&mut self) -> &mut Option<SecondaryImage> {
+                    &mut self.secondary
+                }
+                fn into_bitmap(self: Box<Self>) -> Bitmap {
+                    self.bitmap
+                }
                 fn base(&self) -> &HatBaseData {
                     &self.data.base
                 }
@@ -120,9 +238,8 @@ macro_rules! impl_hat_element {
                     Some(&mut self.$($anims_name).+)
                 }
                 fn frames_amount(&self) -> u32 {
-                    let frames_x = self.texture().width() / self.base().frame_size.x;
-                    let frames_y = self.texture().height() / self.base().frame_size.y;
-                    (frames_x * frames_y) as u32
+                    let texture = self.texture();
+                    frames_in(texture.width(), texture.height(), self.base().frame_size)
                 }
                 fn id(&self) -> HatElementId {
                     self.id
@@ -153,6 +270,15 @@ macro_rules! impl_hat_element {
                 fn bitmap_mut(&mut self) -> &mut Bitmap {
                     &mut self.bitmap
                 }
+                fn secondary(&self) -> Option<&SecondaryImage> {
+                    self.secondary.as_ref()
+                }
+                fn secondary_mut(&mut self) -> &mut Option<SecondaryImage> {
+                    &mut self.secondary
+                }
+                fn into_bitmap(self: Box<Self>) -> Bitmap {
+                    self.bitmap
+                }
                 fn base(&self) -> &HatBaseData {
                     &self.data.base
                 }
@@ -172,9 +298,8 @@ macro_rules! impl_hat_element {
                     None
                 }
                 fn frames_amount(&self) -> u32 {
-                    let frames_x = self.texture().width() / self.base().frame_size.x;
-                    let frames_y = self.texture().height() / self.base().frame_size.y;
-                    (frames_x * frames_y) as u32
+                    let texture = self.texture();
+                    frames_in(texture.width(), texture.height(), self.base().frame_size)
                 }
                 fn id(&self) -> HatElementId {
                     self.id
@@ -197,8 +322,19 @@ macro_rules! hat_element_def {
             data: $data_type,
             texture: Texture,
             bitmap: Bitmap,
+            secondary: Option<SecondaryImage>,
             id: HatElementId,
         }
+
+        impl $type_name {
+            pub fn data(&self) -> &$data_type {
+                &self.data
+            }
+
+            pub fn data_mut(&mut self) -> &mut $data_type {
+                &mut self.data
+            }
+        }
     };
 }
 
@@ -208,11 +344,33 @@ hat_element_def!(FlyingPetHat, FlyingPetData);
 hat_element_def!(WalkingPetHat, WalkingPetData);
 hat_element_def!(ExtraHat, ExtraHatData);
 
+impl ExtraHat {
+    /// Extra hats larger than `MAX_EXTRA_HAT_SIZE` are drawn wrong in game.
+    pub fn size_warning(&self) -> Option<String> {
+        let (width, height) = (self.bitmap.width(), self.bitmap.height());
+        (width > MAX_EXTRA_HAT_SIZE.x || height > MAX_EXTRA_HAT_SIZE.y).then(|| {
+            format!(
+                "extra hat is {}x{}, but at most {}x{} is shown correctly",
+                width, height, MAX_EXTRA_HAT_SIZE.x, MAX_EXTRA_HAT_SIZE.y
+            )
+        })
+    }
+}
+
 pub trait LoadHatElement: Sized + HatElement {
     type Data: serde::Serialize + serde::de::DeserializeOwned + Clone + Default;
     fn load(data: Self::Data, image: Image, gl: &glow::Context) -> Result<Self>;
     fn load_from_path(path: &Path, gl: &glow::Context) -> Result<Self> {
-        let image = Image::new(path).context(format!("could not load image at {:?}", &path))?;
+        Self::load_from_path_with_layout(path, AsepriteLayout::default(), gl)
+    }
+    fn load_from_path_with_layout(
+        path: &Path,
+        layout: AsepriteLayout,
+        gl: &glow::Context,
+    ) -> Result<Self> {
+        let image = Image::new(path)
+            .context(format!("could not load image at {:?}", &path))?
+            .with_aseprite_layout(layout);
         Self::load(Self::Data::default(), image, gl)
     }
 }
@@ -232,6 +390,7 @@ macro_rules! impl_load_hat_element {
                     data,
                     texture,
                     bitmap,
+                    secondary: None,
                     id: hat_element_id(),
                 })
             }
@@ -253,6 +412,7 @@ macro_rules! impl_load_hat_element {
                         data,
                         texture,
                         bitmap,
+                        secondary: None,
                         id: hat_element_id(),
                     })
                 }
@@ -273,7 +433,356 @@ impl_load_hat_element!(@anims FlyingPet);
 impl_load_hat_element!(@anims WalkingPet);
 impl_load_hat_element!(@manual ExtraHat, ExtraHatData);
 
-#[derive(Debug, Clone, Copy, Hash, Default)]
+/// Why a hat could not be loaded, saved or exported.
+#[derive(Debug, Error)]
+pub enum HatError {
+    #[error("expected {0:?} to exist")]
+    MissingPath(PathBuf),
+    #[error("could not access {path:?}")]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("could not parse {path:?}")]
+    InvalidData {
+        path: PathBuf,
+        #[source]
+        source: serde_json::Error,
+    },
+    #[error("could not read archive at {path:?}")]
+    Archive {
+        path: PathBuf,
+        #[source]
+        source: ZipError,
+    },
+    #[error("could not decode image at {path:?}")]
+    Image {
+        path: PathBuf,
+        #[source]
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+    #[error("element has no image path")]
+    MissingImagePath,
+    #[error("could not load element from {path:?}")]
+    Element {
+        path: PathBuf,
+        #[source]
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+    #[error("failed files integrity check, missing: {}", list_paths(.0))]
+    Integrity(Vec<PathBuf>),
+    #[error("could not copy image to {path:?}")]
+    CopyImage {
+        path: PathBuf,
+        #[source]
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+    #[error("hat has no elements, the game won't load it")]
+    Empty,
+    #[error("images folder {0:?} must be a single folder inside the hat")]
+    InvalidImagesDir(String),
+    #[error("could not serialize hat data")]
+    Serialize(#[source] serde_json::Error),
+}
+
+/// A problem to tell the user about. With a `key`, its translated text is shown before
+/// `details`.
+#[derive(Debug, Clone)]
+pub struct HatWarning {
+    pub key: Option<&'static str>,
+    pub details: String,
+}
+
+impl HatWarning {
+    pub fn to_text(&self, text: &UiText) -> String {
+        match self.key {
+            Some(key) => format!("{}: {}", text.get(key), self.details),
+            None => self.details.clone(),
+        }
+    }
+}
+
+impl From<String> for HatWarning {
+    fn from(details: String) -> Self {
+        Self { key: None, details }
+    }
+}
+
+const WEARABLE_SCRIPT_TEMPLATE: &str = include_str!("../script-templates/wearable.lua");
+const WINGS_SCRIPT_TEMPLATE: &str = include_str!("../script-templates/wings.lua");
+
+/// What a new script of the element starts as, from `script-templates`. Pets get the
+/// wearable one typed with their own state class from `defs.lua`.
+pub fn script_template(hat_type: HatType) -> Option<String> {
+    match hat_type {
+        HatType::Wearable => Some(WEARABLE_SCRIPT_TEMPLATE.to_string()),
+        HatType::Wings => Some(WINGS_SCRIPT_TEMPLATE.to_string()),
+        HatType::FlyingPet | HatType::WalkingPet => {
+            Some(WEARABLE_SCRIPT_TEMPLATE.replace("WearableState", &format!("{:?}State", hat_type)))
+        }
+        HatType::Extra | HatType::Room => None,
+    }
+}
+
+/// Whether `name` names a folder directly inside another, without any `..`, root or
+/// separators that could point elsewhere.
+fn is_folder_name(name: &str) -> bool {
+    let mut components = Path::new(name).components();
+    matches!(
+        (components.next(), components.next()),
+        (Some(std::path::Component::Normal(_)), None)
+    )
+}
+
+fn list_paths(paths: &[PathBuf]) -> String {
+    paths
+        .iter()
+        .map(|path| format!("{:?}", path))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+impl HatError {
+    fn io(path: &Path) -> impl FnOnce(std::io::Error) -> Self {
+        move |source| Self::Io {
+            path: path.to_path_buf(),
+            source,
+        }
+    }
+
+    fn archive(path: &Path) -> impl FnOnce(ZipError) -> Self {
+        move |source| Self::Archive {
+            path: path.to_path_buf(),
+            source,
+        }
+    }
+}
+
+/// Saves the element's image and secondary image into the hat at `folder` and points the
+/// element at the copies. Images inside `old_folder`, the hat's previous folder, keep their
+/// place relative to it; images from anywhere else go into `images_path`, named after the
+/// element. Images already inside `folder` are left alone.
+fn copy_element_images(
+    element: &mut dyn HatElement,
+    old_folder: Option<&Path>,
+    folder: &Path,
+    images_path: &Path,
+) -> Result<()> {
+    let id = element.id().0;
+    if let Some(bitmap) = copy_image(
+        element.bitmap(),
+        old_folder,
+        folder,
+        images_path,
+        &id.to_string(),
+    )? {
+        info!(element = id; "copied image to {:?}", bitmap.path());
+        *element.bitmap_mut() = bitmap;
+        element.base_mut().local_image_path = None;
+    }
+    let secondary = match element.secondary() {
+        Some(secondary) => copy_image(
+            &secondary.bitmap,
+            old_folder,
+            folder,
+            images_path,
+            &format!("{}_secondary", id),
+        )?,
+        None => None,
+    };
+    if let Some(bitmap) = secondary {
+        info!(element = id; "copied secondary image to {:?}", bitmap.path());
+        if let Some(secondary) = element.secondary_mut() {
+            secondary.bitmap = bitmap;
+        }
+        element.base_mut().local_secondary_image_path = None;
+    }
+    Ok(())
+}
+
+/// Drops the element's secondary image if it's no longer as large as the element's image,
+/// e.g. after the image was replaced. Returns a warning if it did.
+fn detach_mismatched_secondary(element: &mut dyn HatElement) -> Option<String> {
+    let (width, height) = (element.bitmap().width(), element.bitmap().height());
+    let secondary = element.secondary()?;
+    let (secondary_width, secondary_height) = (secondary.bitmap.width(), secondary.bitmap.height());
+    if (secondary_width, secondary_height) == (width, height) {
+        return None;
+    }
+    *element.secondary_mut() = None;
+    element.base_mut().local_secondary_image_path = None;
+    Some(format!(
+        "secondary image was detached, it's {}x{} but the new image is {}x{}",
+        secondary_width, secondary_height, width, height
+    ))
+}
+
+/// `bitmap` saved into the hat at `folder` as described in [`copy_element_images`], or
+/// `None` if it's already there.
+fn copy_image(
+    bitmap: &Bitmap,
+    old_folder: Option<&Path>,
+    folder: &Path,
+    images_path: &Path,
+    name: &str,
+) -> Result<Option<Bitmap>> {
+    let path = bitmap.path();
+    if path.is_some_and(|path| path.local_path(folder).is_ok()) {
+        return Ok(None);
+    }
+    let image_path = path
+        .zip(old_folder)
+        .and_then(|(path, old_folder)| path.local_path(old_folder).ok())
+        .map(|local_path| folder.join(local_path))
+        .unwrap_or_else(|| free_image_path(images_path, name));
+    save_bitmap_copy(bitmap, &image_path).map(Some)
+}
+
+/// `<name>.png` in `images_path`, with a number appended if that file already exists.
+fn free_image_path(images_path: &Path, name: &str) -> PathBuf {
+    let image_path = images_path.join(format!("{}.png", name));
+    if !image_path.exists() {
+        return image_path;
+    }
+    (2..)
+        .map(|number| images_path.join(format!("{}_{}.png", name, number)))
+        .find(|image_path| !image_path.exists())
+        .expect("should always find a free name")
+}
+
+/// Saves `bitmap` to `path` and returns the copy, which knows where it lives.
+fn save_bitmap_copy(bitmap: &Bitmap, path: &Path) -> Result<Bitmap> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).context(format!("could not create {:?}", parent))?;
+    }
+    bitmap
+        .save(path)
+        .context(format!("could not save image to {:?}", path))?;
+    Bitmap::from_path(path).context(format!("could not read image at {:?}", path))
+}
+
+/// Loads an element under a known id, e.g. one brought back by undo.
+fn load_element(
+    data: HatElementData,
+    image: Image,
+    id: HatElementId,
+    gl: &glow::Context,
+) -> Result<Box<dyn HatElement>> {
+    macro_rules! load {
+        ($hat:ty, $data:expr) => {{
+            let mut element = <$hat>::load($data, image, gl)?;
+            element.id = id;
+            Box::new(element) as Box<dyn HatElement>
+        }};
+    }
+    Ok(match data {
+        HatElementData::Wearable(data) => load!(WearableHat, data),
+        HatElementData::Wings(data) => load!(WingsHat, data),
+        HatElementData::Extra(data) => load!(ExtraHat, data),
+        HatElementData::FlyingPet(data) => load!(FlyingPetHat, data),
+        HatElementData::WalkingPet(data) => load!(WalkingPetHat, data),
+    })
+}
+
+/// Replaces the element's settings. `data` has to be for the element's own type.
+fn set_element_data(element: &mut dyn HatElement, data: HatElementData) {
+    macro_rules! set {
+        ($hat:ty, $data:expr) => {
+            element
+                .downcast_mut::<$hat>()
+                .expect("data should match the element type")
+                .data = $data
+        };
+    }
+    match data {
+        HatElementData::Wearable(data) => set!(WearableHat, data),
+        HatElementData::Wings(data) => set!(WingsHat, data),
+        HatElementData::Extra(data) => set!(ExtraHat, data),
+        HatElementData::FlyingPet(data) => set!(FlyingPetHat, data),
+        HatElementData::WalkingPet(data) => set!(WalkingPetHat, data),
+    }
+}
+
+fn copy_bitmap(bitmap: &Bitmap) -> Bitmap {
+    Bitmap::from_bytes(
+        bitmap.width() as _,
+        bitmap.height() as _,
+        &bitmap.bytes()[..],
+    )
+}
+
+/// Reads a PNG stored in the `.hatspp` archive at `path`.
+fn read_archive_image(
+    zip_archive: &mut ZipArchive<File>,
+    path: &Path,
+    image_path: &Path,
+) -> Result<Bitmap, HatError> {
+    let index = zip_archive
+        .index_for_path(image_path)
+        .ok_or_else(|| HatError::MissingPath(path.join(image_path)))?;
+    let mut entry = zip_archive
+        .by_index(index)
+        .map_err(HatError::archive(path))?;
+    let mut data: Vec<u8> = vec![];
+    entry.read_to_end(&mut data).map_err(HatError::io(path))?;
+    Bitmap::from_png_bytes(&data[..], None).map_err(|err| HatError::Image {
+        path: path.join(image_path),
+        source: err.into(),
+    })
+}
+
+/// Written next to data.json by autosave, so the real save is never overwritten.
+pub const AUTOSAVE_FILE: &str = "data.json.autosave";
+
+const RENAME_RETRIES: u32 = 3;
+/// Saving runs on the UI thread, so all retries together wait 70ms at most.
+const RENAME_BACKOFF: Duration = Duration::from_millis(10);
+
+/// Errors that tend to go away on their own, e.g. while a virus scanner or indexer holds
+/// the file open for a moment.
+fn is_transient(err: &std::io::Error) -> bool {
+    //sharing and lock violations
+    #[cfg(windows)]
+    if matches!(err.raw_os_error(), Some(32 | 33)) {
+        return true;
+    }
+    matches!(
+        err.kind(),
+        std::io::ErrorKind::PermissionDenied
+            | std::io::ErrorKind::ResourceBusy
+            | std::io::ErrorKind::Interrupted
+    )
+}
+
+/// Moves a freshly written temporary file into place, retrying transient failures
+/// (e.g. on network drives) with exponential backoff. If every attempt fails, the
+/// temporary file is removed so it doesn't linger next to the hat.
+fn rename_with_retry(temp_path: &Path, path: &Path) -> Result<(), HatError> {
+    let mut attempt = 0;
+    loop {
+        match std::fs::rename(temp_path, path) {
+            Ok(()) => return Ok(()),
+            Err(err) if attempt < RENAME_RETRIES && is_transient(&err) => {
+                let backoff = RENAME_BACKOFF * 2u32.pow(attempt);
+                attempt += 1;
+                warn!(
+                    "could not rename {:?} ({}), retrying in {:?} ({}/{})",
+                    temp_path, err, backoff, attempt, RENAME_RETRIES
+                );
+                std::thread::sleep(backoff);
+            }
+            Err(err) => {
+                if let Err(remove_err) = std::fs::remove_file(temp_path) {
+                    error!("could not remove {:?}: {}", temp_path, remove_err);
+                }
+                return Err(HatError::io(temp_path)(err));
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Hash, Default, PartialEq, Eq)]
 pub struct HatId(pub u32);
 
 thread_local! {
@@ -292,7 +801,22 @@ pub struct Hat {
     path: PathBuf,
     name: String,
     name_set_by_user: bool,
+    /// Set for hats whose data.json points to a non-standard images folder.
+    images_dir: Option<String>,
+    metadata: HatMetadata,
+    max_pets: usize,
     id: HatId,
+    /// Snapshot of the data as of the last load or save, used to tell if there are unsaved changes.
+    saved_snapshot: Option<String>,
+    has_newer_autosave: bool,
+    /// Problems fixed up while loading or adding elements, e.g. frame sizes out of range,
+    /// waiting to be shown to the user.
+    load_warnings: Vec<HatWarning>,
+    /// Rule violations in the loaded data.json. Only the first instance of a duplicated
+    /// unique element is loaded.
+    validation_errors: Vec<String>,
+    /// Watches the PNGs of the elements, to pick up edits made in other programs.
+    image_watcher: FileWatcher,
 }
 
 macro_rules! hat_by_type_def {
@@ -311,6 +835,145 @@ macro_rules! hat_by_type_def {
         }
     };
 }
+/// How many elements of each kind a hat has, e.g. for tooltips.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HatSummary {
+    pub element_counts: HashMap<HatType, usize>,
+    pub pets: usize,
+    /// Frames of all elements added together.
+    pub total_frames: u32,
+}
+
+impl HatSummary {
+    pub fn add(&mut self, hat_type: HatType, frames_amount: u32) {
+        *self.element_counts.entry(hat_type).or_default() += 1;
+        if hat_type.is_pet() {
+            self.pets += 1;
+        }
+        self.total_frames += frames_amount;
+    }
+
+    pub fn count(&self, hat_type: HatType) -> usize {
+        self.element_counts
+            .get(&hat_type)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    pub fn has_wearable(&self) -> bool {
+        self.count(HatType::Wearable) > 0
+    }
+
+    pub fn has_wings(&self) -> bool {
+        self.count(HatType::Wings) > 0
+    }
+}
+
+impl FromIterator<(HatType, u32)> for HatSummary {
+    fn from_iter<T: IntoIterator<Item = (HatType, u32)>>(iter: T) -> Self {
+        let mut summary = HatSummary::default();
+        for (hat_type, frames_amount) in iter {
+            summary.add(hat_type, frames_amount);
+        }
+        summary
+    }
+}
+
+/// Everything worth knowing before exporting a hat. Errors keep the game from loading it,
+/// warnings only affect how it looks.
+#[derive(Debug, Clone, Default)]
+pub struct ExportReport {
+    pub errors: Vec<String>,
+    pub warnings: Vec<String>,
+}
+
+impl ExportReport {
+    pub fn is_clean(&self) -> bool {
+        self.errors.is_empty() && self.warnings.is_empty()
+    }
+}
+
+/// An element's image as kept in a [`HatSnapshot`].
+#[derive(Debug, Clone)]
+enum SnapshotImage {
+    File(PathBuf),
+    /// Images that only live in memory, e.g. ones read from a `.hatspp` archive.
+    Memory(Rc<Bitmap>),
+}
+
+impl SnapshotImage {
+    fn new(bitmap: &Bitmap) -> Self {
+        match bitmap.path() {
+            Some(path) => Self::File(path.to_path_buf()),
+            None => Self::Memory(Rc::new(copy_bitmap(bitmap))),
+        }
+    }
+
+    /// What the image is compared by: its file, or a hash of the pixels of in-memory images.
+    fn key(bitmap: &Bitmap) -> String {
+        match bitmap.path() {
+            Some(path) => path.to_string_lossy().to_string(),
+            None => {
+                let mut hasher = DefaultHasher::new();
+                bitmap.bytes()[..].hash(&mut hasher);
+                format!(
+                    "{}x{} {:x}",
+                    bitmap.width(),
+                    bitmap.height(),
+                    hasher.finish()
+                )
+            }
+        }
+    }
+
+    /// Whether `bitmap` already is this image, so it doesn't have to be loaded again.
+    fn matches(&self, bitmap: &Bitmap) -> bool {
+        match self {
+            Self::File(path) => bitmap.path() == Some(path.as_path()),
+            Self::Memory(image) => {
+                bitmap.path().is_none()
+                    && bitmap.width() == image.width()
+                    && bitmap.height() == image.height()
+                    && bitmap.bytes()[..] == image.bytes()[..]
+            }
+        }
+    }
+
+    fn load(&self) -> Result<Image> {
+        match self {
+            Self::File(path) => {
+                Image::new(path).context(format!("could not load image at {:?}", path))
+            }
+            Self::Memory(bitmap) => Ok(Image::Bitmap(copy_bitmap(bitmap))),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct ElementSnapshot {
+    id: HatElementId,
+    data: HatElementData,
+    image: SnapshotImage,
+    secondary: Option<SnapshotImage>,
+}
+
+/// A hat's data at one point in time, to go back to with [`Hat::restore_snapshot`].
+#[derive(Debug, Clone)]
+pub struct HatSnapshot {
+    name: String,
+    name_set_by_user: bool,
+    metadata: HatMetadata,
+    elements: Vec<ElementSnapshot>,
+    key: String,
+}
+
+impl HatSnapshot {
+    /// Equal for snapshots of the same data, see [`Hat::snapshot_key`].
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+}
+
 #[derive(Clone, Copy, Debug)]
 pub enum HatSaveType {
     Folder,
@@ -324,7 +987,15 @@ impl Hat {
             path: path.to_path_buf(),
             name: name.to_string(),
             name_set_by_user: false,
+            images_dir: None,
+            metadata: HatMetadata::default(),
+            max_pets: MAX_PETS,
             id: hat_id(),
+            saved_snapshot: None,
+            has_newer_autosave: false,
+            load_warnings: vec![],
+            validation_errors: vec![],
+            image_watcher: FileWatcher::new(),
         }
     }
 
@@ -332,6 +1003,12 @@ impl Hat {
         self.elements.is_empty()
     }
 
+    pub fn summary(&self) -> HatSummary {
+        self.elements()
+            .map(|element| (element.base().hat_type, element.frames_amount()))
+            .collect()
+    }
+
     pub fn element(&self, id: HatElementId) -> Option<&dyn HatElement> {
         self.elements.get(&id).map(|e| &**e)
     }
@@ -357,21 +1034,290 @@ impl Hat {
     }
 
     pub fn can_add_pets(&self) -> bool {
-        self.pets_amount() < MAX_PETS
+        self.pets_amount() < self.max_pets
     }
 
     pub fn can_add_elements(&self) -> bool {
         self.can_add_pets() && !HatType::iter().all(|hat_type| self.has_element(hat_type))
     }
 
-    pub fn add_element(&mut self, element: impl HatElement) {
+    /// Returns the element's id, or `None` if the pet cap or uniqueness rules rejected it.
+    /// Frame sizes out of range are clamped, with a warning for [`Hat::take_load_warnings`].
+    pub fn add_element(&mut self, mut element: impl HatElement) -> Option<HatElementId> {
         if element.is_pet() && !self.can_add_pets() {
-            return;
+            return None;
         }
         if element.is_unique() && self.has_element(element.base().hat_type) {
-            return;
+            return None;
+        }
+        if let Some(warning) = element.base_mut().clamp_frame_size() {
+            warn!(hat = self.name(), element = element.id().0; "{}", warning);
+            self.load_warnings.push(warning.into());
+        }
+        let id = element.id();
+        self.elements.insert(id, Box::new(element));
+        Some(id)
+    }
+
+    pub fn can_convert_element(&self, id: HatElementId, target: HatType) -> bool {
+        let Some(element) = self.element(id) else {
+            return false;
+        };
+        if element.base().hat_type == target || HatElementData::default_for(target).is_none() {
+            return false;
+        }
+        if target.is_unique() && self.has_element(target) {
+            return false;
+        }
+        !target.is_pet() || element.is_pet() || self.can_add_pets()
+    }
+
+    /// Replaces the element with one of the `target` type built from the same image and
+    /// returns the new element's id. Settings that couldn't be carried over are reported
+    /// through [`Hat::take_load_warnings`].
+    pub fn convert_element(
+        &mut self,
+        id: HatElementId,
+        target: HatType,
+        gl: &glow::Context,
+    ) -> Result<HatElementId> {
+        if !self.can_convert_element(id, target) {
+            bail!("can't convert element to {:?}", target);
+        }
+        let mut element = self
+            .elements
+            .remove(&id)
+            .context("element does not exist")?;
+        let secondary = element.secondary_mut().take();
+        let (data, lost) = element
+            .hat_element_data_ref()
+            .to_hat_element_data()
+            .convert(target)
+            .context(format!("{:?} has no element data", target))?;
+        let new_id = self
+            .add_element_data(data, Image::Bitmap(element.into_bitmap()), gl)?
+            .context("converted element was rejected")?;
+        if let Some(new_element) = self.element_mut(new_id) {
+            *new_element.secondary_mut() = secondary;
+        }
+        if !lost.is_empty() {
+            let warning = format!("converting to {:?} discarded: {}", target, lost.join(", "));
+            warn!(hat = self.name(), element = new_id.0; "{}", warning);
+            self.load_warnings.push(warning.into());
+        }
+        Ok(new_id)
+    }
+
+    /// Replaces the element's animations with copies of `animations`, taken from an element
+    /// with `source_frames_amount` frames. Everything that didn't fit is reported through
+    /// [`Hat::take_load_warnings`].
+    pub fn import_animations(
+        &mut self,
+        id: HatElementId,
+        animations: &[Animation],
+        source_frames_amount: u32,
+    ) -> Result<()> {
+        let element = self.element_mut(id).context("element does not exist")?;
+        let hat_type = element.base().hat_type;
+        let frames_amount = element.frames_amount();
+        let allowed = avalible_animations(hat_type).unwrap_or_default();
+        let target = element
+            .animations_mut()
+            .context(format!("{:?} has no animations", hat_type))?;
+
+        let mut warnings = vec![];
+        if frames_amount != source_frames_amount {
+            warnings.push(format!(
+                "source has {} frames, but the target has {}",
+                source_frames_amount, frames_amount
+            ));
+        }
+        let mut imported = vec![];
+        for animation in animations {
+            if !allowed.contains(&animation.anim_type) {
+                warnings.push(format!(
+                    "{:?} is not available for {:?}",
+                    animation.anim_type, hat_type
+                ));
+                continue;
+            }
+            //cloning gives the frames fresh ids
+            let mut animation = animation.clone();
+            let out_of_range = animation.frames_out_of_range(frames_amount);
+            if !out_of_range.is_empty() {
+                warnings.push(format!(
+                    "{:?}: dropped frames past the last one: {:?}",
+                    animation.anim_type, out_of_range
+                ));
+                animation.frames.retain(|frame| frame.value < frames_amount);
+            }
+            imported.push(animation);
+        }
+        *target = imported;
+        for warning in warnings {
+            warn!(hat = self.name(), element = id.0; "{}", warning);
+            self.load_warnings.push(warning.into());
+        }
+        Ok(())
+    }
+
+    /// Cuts `bounds` off every frame of the element. For hats saved to a folder, the result
+    /// is written next to the original image instead of replacing it.
+    pub fn trim_element(
+        &mut self,
+        id: HatElementId,
+        bounds: TrimBounds,
+        gl: &glow::Context,
+    ) -> Result<()> {
+        let element = self.element_mut(id).context("element does not exist")?;
+        let frame_size = element.base().frame_size;
+        let new_size = bounds.trimmed_size(frame_size);
+        if new_size.x <= 0 || new_size.y <= 0 {
+            bail!("trimming would leave no pixels");
+        }
+        let bitmap = trim_bitmap(element.bitmap(), frame_size, bounds);
+        *element.texture_mut() = Texture::from_bitmap(gl, &bitmap)?;
+        *element.bitmap_mut() = bitmap;
+        //the secondary image lines up with the frames, so it loses the same pixels
+        if let Some(secondary) = element.secondary_mut() {
+            let bitmap = trim_bitmap(&secondary.bitmap, frame_size, bounds);
+            secondary.texture = Texture::from_bitmap(gl, &bitmap)?;
+            secondary.bitmap = bitmap;
+        }
+        let base = element.base_mut();
+        base.frame_size = new_size;
+        base.local_image_path = None;
+        base.local_secondary_image_path = None;
+        self.store_element_images(id)
+    }
+
+    /// Replaces colors of the element's image as listed in `swaps`. Like trimming, this
+    /// writes a new image next to the original for hats saved to a folder.
+    pub fn recolor_element(
+        &mut self,
+        id: HatElementId,
+        swaps: &[ColorSwap],
+        gl: &glow::Context,
+    ) -> Result<()> {
+        let element = self.element_mut(id).context("element does not exist")?;
+        let bitmap = swap_colors(element.bitmap(), swaps);
+        let changed = changed_region(element.bitmap(), &bitmap);
+        //only the swapped pixels are uploaded, the size stays the same
+        if let Some(region) = changed {
+            element.texture().update_region(
+                gl,
+                region.min.x,
+                region.min.y,
+                region.size.x,
+                region.size.y,
+                &region_bytes(&bitmap, region),
+            )?;
+        }
+        *element.bitmap_mut() = bitmap;
+        element.base_mut().local_image_path = None;
+        self.store_element_images(id)
+    }
+
+    /// Recreates the texture of every element from its bitmap. Returns how many were rebuilt.
+    pub fn reload_textures(&self, gl: &glow::Context) -> Result<usize> {
+        for element in self.elements.values() {
+            element
+                .texture()
+                .recreate(gl, element.bitmap())
+                .context(format!(
+                    "could not recreate texture of {:?}",
+                    element.base().hat_type
+                ))?;
+            if let Some(secondary) = element.secondary() {
+                secondary
+                    .texture
+                    .recreate(gl, &secondary.bitmap)
+                    .context(format!(
+                        "could not recreate secondary texture of {:?}",
+                        element.base().hat_type
+                    ))?;
+            }
+        }
+        Ok(self.elements.len())
+    }
+
+    /// PNG files the elements and their secondary images were read from.
+    fn image_paths(&self) -> HashSet<PathBuf> {
+        self.elements()
+            .flat_map(|element| {
+                let secondary = element.secondary().map(|secondary| &secondary.bitmap);
+                std::iter::once(element.bitmap()).chain(secondary)
+            })
+            .filter_map(|bitmap| bitmap.path())
+            .filter(|path| path.extension().is_some_and(|extension| extension == "png"))
+            .map(Path::to_path_buf)
+            .collect()
+    }
+
+    /// Starts watching the images of the elements, replacing whatever was watched before.
+    fn watch_images(&mut self) {
+        self.image_watcher.unwatch_all();
+        for path in self.image_paths() {
+            if let Err(err) = self.image_watcher.watch_file(&path) {
+                warn!(hat = self.name(); "could not watch {:?}: {}", path, err);
+            }
+        }
+    }
+
+    pub fn image_watcher(&self) -> &FileWatcher {
+        &self.image_watcher
+    }
+
+    /// Stops watching the images, e.g. once the hat is closed.
+    pub fn unwatch_images(&mut self) {
+        self.image_watcher.unwatch_all();
+    }
+
+    /// Element images changed on disk since the last call. Images added to the hat since
+    /// are watched from then on, and removed ones aren't anymore.
+    pub fn changed_images(&mut self) -> Vec<PathBuf> {
+        let image_paths = self.image_paths();
+        let watched: HashSet<_> = self
+            .image_watcher
+            .watched()
+            .map(|(_, path)| path.to_path_buf())
+            .collect();
+        for path in watched.difference(&image_paths) {
+            self.image_watcher.unwatch_path(path);
+        }
+        for path in image_paths.difference(&watched) {
+            if let Err(err) = self.image_watcher.watch_file(path) {
+                warn!(hat = self.name(); "could not watch {:?}: {}", path, err);
+            }
+        }
+        self.image_watcher
+            .update()
+            .iter()
+            .map(|(_, path)| path.to_path_buf())
+            .collect()
+    }
+
+    /// Reads the image at `path` again for every element using it, e.g. after it was edited
+    /// in another program. Returns how many images were updated.
+    pub fn reload_image(&mut self, path: &Path, gl: &glow::Context) -> Result<usize> {
+        let mut reloaded = 0;
+        for element in self.elements.values_mut() {
+            if element.bitmap().path() == Some(path) {
+                *element.bitmap_mut() =
+                    Bitmap::from_path(path).context(format!("could not read {:?}", path))?;
+                element.texture_mut().reload(gl, path)?;
+                reloaded += 1;
+            }
+            if let Some(secondary) = element.secondary_mut()
+                && secondary.bitmap.path() == Some(path)
+            {
+                secondary.bitmap =
+                    Bitmap::from_path(path).context(format!("could not read {:?}", path))?;
+                secondary.texture.reload(gl, path)?;
+                reloaded += 1;
+            }
         }
-        self.elements.insert(element.id(), Box::new(element));
+        Ok(reloaded)
     }
 
     pub fn remove_element(&mut self, element_id: HatElementId) {
@@ -386,215 +1332,1058 @@ impl Hat {
         self.elements().any(|e| e.id() == hat_id)
     }
 
-    pub fn load(path: impl AsRef<Path>, gl: &glow::Context) -> Result<Self> {
+    pub fn load(path: impl AsRef<Path>, gl: &glow::Context) -> Result<Self, HatError> {
         let path = path.as_ref();
-        let data_path = path.join("data.json");
-        let images_path = path.join("images");
-        for path in &[path, &images_path] {
-            if !path.exists() {
-                bail!("expected {:?} to exist", path);
-            }
+        let has_newer_autosave = Self::autosave_is_newer(path);
+        let mut hat = Self::load_with_data_file(path, &path.join("data.json"), gl)?;
+        hat.has_newer_autosave = has_newer_autosave;
+        hat.mark_saved();
+        Ok(hat)
+    }
+
+    /// Loads the hat from its autosave rather than data.json. The result counts as unsaved.
+    pub fn load_autosave(path: impl AsRef<Path>, gl: &glow::Context) -> Result<Self, HatError> {
+        let path = path.as_ref();
+        let autosave_path = path.join(AUTOSAVE_FILE);
+        if !autosave_path.exists() {
+            return Err(HatError::MissingPath(autosave_path));
+        }
+        Self::load_with_data_file(path, &autosave_path, gl)
+    }
+
+    /// A name the user picked always wins, otherwise the hat is named after its folder
+    /// (or file), so renaming it on disk renames the hat.
+    fn loaded_name(path: &Path, data: &HatData) -> String {
+        if data.name_set_by_user {
+            return data.name.clone();
+        }
+        path.file_stem()
+            .and_then(|stem| stem.to_str())
+            .map(|stem| stem.to_string())
+            .unwrap_or_else(|| data.name.clone())
+    }
+
+    fn autosave_is_newer(path: &Path) -> bool {
+        let modified = |path: PathBuf| std::fs::metadata(path).and_then(|m| m.modified()).ok();
+        match (
+            modified(path.join(AUTOSAVE_FILE)),
+            modified(path.join("data.json")),
+        ) {
+            (Some(autosave), Some(saved)) => autosave > saved,
+            (Some(_), None) => true,
+            (None, _) => false,
+        }
+    }
+
+    fn load_with_data_file(
+        path: &Path,
+        data_path: &Path,
+        gl: &glow::Context,
+    ) -> Result<Self, HatError> {
+        if !path.exists() {
+            return Err(HatError::MissingPath(path.to_path_buf()));
         }
 
         let data: HatData = if data_path.exists() {
-            File::open(&data_path)
-                .context(format!("could not open {:?}", &data_path))
-                .and_then(|mut file| {
-                    let mut data = String::new();
-                    file.read_to_string(&mut data)
-                        .context(format!("could not read {:?}", &data_path))
-                        .map(|_| data)
-                })
-                .and_then(|data_string| {
-                    serde_json::from_str(&data_string)
-                        .context(format!("could not parse {:?}", &data_path))
-                })?
+            let data_string =
+                std::fs::read_to_string(data_path).map_err(HatError::io(data_path))?;
+            serde_json::from_str(&data_string).map_err(|source| HatError::InvalidData {
+                path: data_path.to_path_buf(),
+                source,
+            })?
         } else {
-            let mut file =
-                File::create(&data_path).context(format!("could not create {:?}", data_path))?;
             let hat_data = HatData::new("Default".to_string());
-            write!(
-                file,
-                "{}",
-                serde_json::to_string_pretty(&hat_data).expect("should always succeed")
-            )
-            .context(format!("could not write into {:?}", &data_path))?;
+            let data_string =
+                serde_json::to_string_pretty(&hat_data).map_err(HatError::Serialize)?;
+            let mut file = File::create(data_path).map_err(HatError::io(data_path))?;
+            write!(file, "{}", data_string).map_err(HatError::io(data_path))?;
             hat_data
         };
+        if let Some(images_dir) = &data.images_dir
+            && !is_folder_name(images_dir)
+        {
+            return Err(HatError::InvalidImagesDir(images_dir.clone()));
+        }
 
-        let mut hat = Hat::new(path, &data.name);
+        let mut hat = Hat::new(path, &Self::loaded_name(path, &data));
+        hat.name_set_by_user = data.name_set_by_user;
+        hat.validation_errors = data.duplicate_unique_elements();
+        hat.images_dir = data.images_dir;
+        let images_path = path.join(hat.images_dir_name());
+        if !images_path.exists() {
+            return Err(HatError::MissingPath(images_path));
+        }
+        hat.metadata = data.metadata;
         for element in data.elements {
-            let local_image_path = element.base().local_image_path.as_ref().unwrap();
+            let local_image_path = element
+                .base()
+                .local_image_path
+                .as_ref()
+                .ok_or(HatError::MissingImagePath)?;
             let image_path = path.join(local_image_path);
-            let bitmap = Bitmap::from_path(&image_path)
-                .context(format!("could not read image at {:?}", &image_path))?;
-
-            match element {
-                HatElementData::Wearable(wearable_data) => {
-                    hat.add_element(WearableHat::load(wearable_data, Image::Bitmap(bitmap), gl)?)
-                }
-                HatElementData::Wings(wings_data) => {
-                    hat.add_element(WingsHat::load(wings_data, Image::Bitmap(bitmap), gl)?)
-                }
-                HatElementData::Extra(extra_hat_data) => {
-                    hat.add_element(ExtraHat::load(extra_hat_data, Image::Bitmap(bitmap), gl)?)
-                }
-                HatElementData::FlyingPet(flying_pet_data) => hat.add_element(FlyingPetHat::load(
-                    flying_pet_data,
-                    Image::Bitmap(bitmap),
-                    gl,
-                )?),
-                HatElementData::WalkingPet(walking_pet_data) => hat.add_element(
-                    WalkingPetHat::load(walking_pet_data, Image::Bitmap(bitmap), gl)?,
-                ),
-            };
+            let secondary_path = element
+                .base()
+                .local_secondary_image_path
+                .as_ref()
+                .map(|local_path| path.join(local_path));
+            let bitmap = Bitmap::from_path(&image_path).map_err(|err| HatError::Image {
+                path: image_path.clone(),
+                source: err.into(),
+            })?;
+            let id = hat
+                .add_element_data(element, Image::Bitmap(bitmap), gl)
+                .map_err(|err| HatError::Element {
+                    path: image_path,
+                    source: err.into(),
+                })?;
+            if let Some(id) = id
+                && let Some(secondary_path) = secondary_path
+            {
+                let bitmap = Bitmap::from_path(&secondary_path).map_err(|err| HatError::Image {
+                    path: secondary_path.clone(),
+                    source: err.into(),
+                })?;
+                hat.attach_secondary_image(id, bitmap, gl)
+                    .map_err(|err| HatError::Element {
+                        path: secondary_path,
+                        source: err.into(),
+                    })?;
+            }
         }
+        hat.report_frame_size_mismatches();
+        hat.watch_images();
         Ok(hat)
     }
 
-    // pub fn load_from_file(path: impl AsRef<Path>, gl: &eframe::glow::Context) -> Result<Self> {
-    //     let path = path.as_ref();
-    //     if !path.exists() {
-    //         bail!("expected path to exist: {:?}", path);
-    //     }
-    //
-    //     let file = File::open(path)?;
-    //     let mut zip_archive = ZipArchive::new(file)?;
-    //     let hat_data: HatData = {
-    //         let mut data_json = zip_archive.by_name("data.json")?;
-    //         let mut data_json_string = String::new();
-    //         data_json.read_to_string(&mut data_json_string)?;
-    //         serde_json::from_str(&data_json_string)?
-    //     };
-    //     let mut hat = Hat::with_path(path, &hat_data.name);
-    //
-    //     for element in hat_data.elements {
-    //         let image_path = element.base().local_image_path.as_ref().unwrap();
-    //         let index = zip_archive.index_for_path(image_path).unwrap();
-    //         let mut entry = zip_archive.by_index(index)?;
-    //         let mut data: Vec<u8> = vec![];
-    //         entry.read_to_end(&mut data)?;
-    //         let bitmap = Bitmap::from_png_bytes(&data[..], None)?;
-    //
-    //         match element {
-    //             HatElementData::Wearable(wearable_data) => {
-    //                 hat.add_element(WearableHat::load(wearable_data, Image::Bitmap(bitmap), gl)?)
-    //             }
-    //             HatElementData::Wings(wings_data) => {
-    //                 hat.add_element(WingsHat::load(wings_data, Image::Bitmap(bitmap), gl)?)
-    //             }
-    //             HatElementData::Extra(extra_hat_data) => {
-    //                 hat.add_element(ExtraHat::load(extra_hat_data, Image::Bitmap(bitmap), gl)?)
-    //             }
-    //             HatElementData::FlyingPet(flying_pet_data) => hat.add_element(FlyingPetHat::load(
-    //                 flying_pet_data,
-    //                 Image::Bitmap(bitmap),
-    //                 gl,
-    //             )?),
-    //             HatElementData::WalkingPet(walking_pet_data) => hat.add_element(
-    //                 WalkingPetHat::load(walking_pet_data, Image::Bitmap(bitmap), gl)?,
-    //             ),
-    //         };
-    //     }
-    //
-    //     Ok(hat)
-    // }
-
-    // pub fn save_as(&mut self) -> Result<()> {
-    //     let path = rfd::FileDialog::new()
-    //         .pick_folder()
-    //         .context("could not pick folder")?;
-    //     self.save(&path)?;
-    //     *self.path_mut() = path;
-    //     Ok(())
-    // }
-    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
-        if let Err(err) = self.check_files_integrity() {
-            bail!("failed files integrity check: {}", err.to_string());
+    pub fn load_from_file(path: impl AsRef<Path>, gl: &glow::Context) -> Result<Self, HatError> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Err(HatError::MissingPath(path.to_path_buf()));
         }
-        let path = path.as_ref().join("data.json");
-        let uuid_path: PathBuf = {
-            let mut path = path.to_path_buf().into_os_string();
-            path.push("_");
-            path.push(Uuid::new_v4().to_string());
-            path.into()
-        };
-
-        let mut file =
-            File::create(&uuid_path).context(format!("could not create {:?}", uuid_path))?;
-
-        let data_string = serde_json::to_string_pretty(&self.gen_hat_data(HatSaveType::Folder))
-            .context("could not generate data.json")?;
-
-        write!(file, "{}", data_string).context(format!(
-            "could not write hat data to file at {:?}",
-            &uuid_path
-        ))?;
 
-        if std::fs::exists(&path).unwrap_or(false) {
-            if let Err(err) = std::fs::remove_file(&path) {
-                std::fs::remove_file(&uuid_path)
-                    .context(format!("could not remove {:?}", &uuid_path))?;
-                return Err(err.into());
+        let file = File::open(path).map_err(HatError::io(path))?;
+        let mut zip_archive = ZipArchive::new(file).map_err(HatError::archive(path))?;
+        let hat_data: HatData = {
+            let mut data_json = zip_archive
+                .by_name("data.json")
+                .map_err(HatError::archive(path))?;
+            let mut data_json_string = String::new();
+            data_json
+                .read_to_string(&mut data_json_string)
+                .map_err(HatError::io(path))?;
+            serde_json::from_str(&data_json_string).map_err(|source| HatError::InvalidData {
+                path: path.join("data.json"),
+                source,
+            })?
+        };
+        let mut hat = Hat::new(path, &Self::loaded_name(path, &hat_data));
+        hat.name_set_by_user = hat_data.name_set_by_user;
+        hat.validation_errors = hat_data.duplicate_unique_elements();
+        hat.metadata = hat_data.metadata;
+
+        for element in hat_data.elements {
+            let image_path = element
+                .base()
+                .local_image_path
+                .clone()
+                .ok_or(HatError::MissingImagePath)?;
+            let secondary_path = element.base().local_secondary_image_path.clone();
+            let bitmap = read_archive_image(&mut zip_archive, path, &image_path)?;
+            let id = hat
+                .add_element_data(element, Image::Bitmap(bitmap), gl)
+                .map_err(|err| HatError::Element {
+                    path: path.join(&image_path),
+                    source: err.into(),
+                })?;
+            if let Some(id) = id
+                && let Some(secondary_path) = secondary_path
+            {
+                let bitmap = read_archive_image(&mut zip_archive, path, &secondary_path)?;
+                hat.attach_secondary_image(id, bitmap, gl)
+                    .map_err(|err| HatError::Element {
+                        path: path.join(&secondary_path),
+                        source: err.into(),
+                    })?;
             }
         }
+        hat.report_frame_size_mismatches();
 
-        std::fs::rename(&uuid_path, path)
-            .context(format!("could not rename file: {:?}", uuid_path))?;
+        hat.mark_saved();
+        Ok(hat)
+    }
+
+    /// Builds an element of the data's type from `image` and adds it to the hat.
+    pub fn add_element_data(
+        &mut self,
+        mut element: HatElementData,
+        image: Image,
+        gl: &glow::Context,
+    ) -> Result<Option<HatElementId>> {
+        // Before loading, since the frame count is worked out from the frame size.
+        if let Some(warning) = element.base_mut().clamp_frame_size() {
+            warn!(hat = self.name(); "{}", warning);
+            self.load_warnings.push(warning.into());
+        }
+        if let Some(warning) = ignored_tags_warning(&image.ignored_tags()) {
+            warn!(hat = self.name(); "{}", warning);
+            self.load_warnings.push(warning.into());
+        }
+        let id = match element {
+            HatElementData::Wearable(wearable_data) => {
+                self.add_element(WearableHat::load(wearable_data, image, gl)?)
+            }
+            HatElementData::Wings(wings_data) => {
+                let mut wings = WingsHat::load(wings_data, image, gl)?;
+                let frames_amount = wings.frames_amount();
+                for warning in wings.data_mut().clamp_frames(frames_amount) {
+                    warn!(hat = self.name(); "{}", warning);
+                    self.load_warnings.push(warning.into());
+                }
+                self.add_element(wings)
+            }
+            HatElementData::Extra(extra_hat_data) => {
+                let extra_hat = ExtraHat::load(extra_hat_data, image, gl)?;
+                if let Some(warning) = extra_hat.size_warning() {
+                    warn!(hat = self.name(); "{}", warning);
+                    self.load_warnings.push(warning.into());
+                }
+                self.add_element(extra_hat)
+            }
+            HatElementData::FlyingPet(flying_pet_data) => self.add_element(FlyingPetHat::load(
+                flying_pet_data,
+                image,
+                gl,
+            )?),
+            HatElementData::WalkingPet(walking_pet_data) => self.add_element(
+                WalkingPetHat::load(walking_pet_data, image, gl)?,
+            ),
+        };
+        Ok(id)
+    }
+
+    /// Exports the element's animation with [`HatElement::export_gif`]. Skipped frames are
+    /// reported through [`Hat::take_load_warnings`].
+    pub fn export_element_gif(
+        &mut self,
+        id: HatElementId,
+        animation: &Animation,
+        path: &Path,
+    ) -> Result<()> {
+        let element = self.element(id).context("element does not exist")?;
+        for warning in element.export_gif(animation, path)? {
+            warn!(hat = self.name(), element = id.0; "{}", warning);
+            self.load_warnings.push(warning.into());
+        }
+        Ok(())
+    }
+
+    /// Returns the problems fixed up since the last call, once.
+    pub fn take_load_warnings(&mut self) -> Vec<HatWarning> {
+        std::mem::take(&mut self.load_warnings)
+    }
+
+    /// Returns the problems found in the loaded data.json, once.
+    pub fn take_validation_errors(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.validation_errors)
+    }
+
+    /// Elements whose image isn't evenly divided by their `frame_size`, which usually means
+    /// the image was replaced without updating data.json.
+    pub fn frame_size_mismatches(&self) -> Vec<&dyn HatElement> {
+        self.elements()
+            .filter(|e| {
+                let frame_size = e.base().frame_size;
+                frame_size.x <= 0
+                    || frame_size.y <= 0
+                    || e.bitmap().width() % frame_size.x != 0
+                    || e.bitmap().height() % frame_size.y != 0
+            })
+            .collect()
+    }
+
+    /// Lists [`Hat::frame_size_mismatches`] in a warning for [`Hat::take_load_warnings`].
+    fn report_frame_size_mismatches(&mut self) {
+        let mismatches = self
+            .frame_size_mismatches()
+            .iter()
+            .map(|e| {
+                format!(
+                    "{:?} ({}x{}, {}x{})",
+                    e.base().hat_type,
+                    e.base().frame_size.x,
+                    e.base().frame_size.y,
+                    e.bitmap().width(),
+                    e.bitmap().height()
+                )
+            })
+            .collect::<Vec<_>>();
+        if mismatches.is_empty() {
+            return;
+        }
+        let details = mismatches.join(", ");
+        warn!(hat = self.name(); "frame size doesn't fit the image of: {}", details);
+        self.load_warnings.push(HatWarning {
+            key: Some("184"),
+            details,
+        });
+    }
+
+    /// Whether the hat was created without a folder and hasn't been saved yet.
+    pub fn is_unsaved(&self) -> bool {
+        self.path.as_os_str().is_empty()
+    }
+
+    /// Why an element of `hat_type` can't be added, if anything stops it.
+    pub fn rejection_reason(&self, hat_type: HatType) -> Option<String> {
+        if hat_type.is_pet() && !self.can_add_pets() {
+            return Some(format!(
+                "{} already has {} pets",
+                self.name(),
+                self.max_pets
+            ));
+        }
+        if hat_type.is_unique() && self.has_element(hat_type) {
+            return Some(format!("{} already has {:?}", self.name(), hat_type));
+        }
+        None
+    }
+
+    /// The element's data and a copy of its image, e.g. to add it to another hat.
+    pub fn element_copy(&self, id: HatElementId) -> Option<(HatElementData, Bitmap)> {
+        let element = self.element(id)?;
+        Some((
+            element.hat_element_data_ref().to_hat_element_data(),
+            copy_bitmap(element.bitmap()),
+        ))
+    }
+
+    /// Adds a copy of the element under a fresh id, with an image of its own. `None` if the
+    /// element doesn't exist or the pet cap or uniqueness rules reject the copy.
+    pub fn duplicate_element(
+        &mut self,
+        id: HatElementId,
+        gl: &glow::Context,
+    ) -> Option<HatElementId> {
+        let (data, bitmap) = self.element_copy(id)?;
+        if self.rejection_reason(data.base().hat_type).is_some() {
+            return None;
+        }
+        match self.add_element_copy(data, bitmap, gl) {
+            Ok(new_id) => Some(new_id),
+            Err(err) => {
+                error!(hat = self.name(), element = id.0; "while duplicating element: {}", err);
+                None
+            }
+        }
+    }
+
+    /// Adds an element copied from another hat under a fresh id. Folder hats get the image
+    /// saved into their images folder.
+    pub fn add_element_copy(
+        &mut self,
+        mut data: HatElementData,
+        bitmap: Bitmap,
+        gl: &glow::Context,
+    ) -> Result<HatElementId> {
+        if let Some(reason) = self.rejection_reason(data.base().hat_type) {
+            bail!(reason);
+        }
+        data.base_mut().local_image_path = None;
+        let id = self
+            .add_element_data(data, Image::Bitmap(bitmap), gl)?
+            .context("element was rejected")?;
+        self.store_element_images(id)?;
+        Ok(id)
+    }
+
+    /// Swaps the element's image for `image`, keeping its settings. Aseprite images bring
+    /// their frame size and tagged animations along, like when adding an element.
+    pub fn replace_element_image(
+        &mut self,
+        id: HatElementId,
+        image: Image,
+        gl: &glow::Context,
+    ) -> Result<()> {
+        let mut warnings = vec![];
+        let (element, aseprite_data) = self.set_element_image(id, image, gl)?;
+        warnings.extend(detach_mismatched_secondary(element));
+        if let Some(aseprite_data) = aseprite_data {
+            element.base_mut().frame_size = aseprite_data.frame_size;
+            warnings.extend(element.base_mut().clamp_frame_size());
+            warnings.extend(ignored_tags_warning(&aseprite_data.ignored_tags));
+            if let Some(animations) = element.animations_mut() {
+                *animations = aseprite_data.animations;
+            }
+        }
+        self.store_element_images(id)?;
+        for warning in warnings {
+            warn!(hat = self.name(), element = id.0; "{}", warning);
+            self.load_warnings.push(warning.into());
+        }
+        Ok(())
+    }
+
+    /// What re-importing `image` with [`Hat::reimport_element_image`] couldn't keep as is.
+    /// Empty if the new image has the same frames as the current one.
+    pub fn reimport_conflicts(&self, id: HatElementId, image: &Image) -> Result<Vec<String>> {
+        let element = self.element(id).context("element does not exist")?;
+        let frame_size = element.base().frame_size;
+        let mut conflicts = vec![];
+        if let Image::Aseprite(..) = image
+            && (image.width(), image.height()) != (frame_size.x, frame_size.y)
+        {
+            conflicts.push(format!(
+                "frame size changed from {}x{} to {}x{}",
+                frame_size.x,
+                frame_size.y,
+                image.width(),
+                image.height()
+            ));
+        }
+        let (old_frames, new_frames) = (element.frames_amount(), image.frames_amount(frame_size));
+        if old_frames != new_frames {
+            conflicts.push(format!(
+                "the image has {} frames instead of {}",
+                new_frames, old_frames
+            ));
+        }
+        Ok(conflicts)
+    }
+
+    /// Swaps in a new version of the element's image while keeping its animations and
+    /// offsets. Tags of an aseprite file only add animations the element doesn't have yet,
+    /// but a changed frame size is always taken from it, since the old one would cut its
+    /// frames apart. What had to change, e.g. frames dropped past the new end, is reported
+    /// through [`Hat::take_load_warnings`].
+    pub fn reimport_element_image(
+        &mut self,
+        id: HatElementId,
+        image: Image,
+        gl: &glow::Context,
+    ) -> Result<()> {
+        let (element, aseprite_data) = self.set_element_image(id, image, gl)?;
+        let hat_type = element.base().hat_type;
+        let mut changes: Vec<_> = detach_mismatched_secondary(element).into_iter().collect();
+        if let Some(aseprite_data) = &aseprite_data
+            && aseprite_data.frame_size != element.base().frame_size
+        {
+            let old_size = element.base().frame_size;
+            element.base_mut().frame_size = aseprite_data.frame_size;
+            changes.push(format!(
+                "frame size changed from {}x{} to {}x{} as in the file",
+                old_size.x, old_size.y, aseprite_data.frame_size.x, aseprite_data.frame_size.y
+            ));
+            changes.extend(element.base_mut().clamp_frame_size());
+        }
+        let frames_amount = element.frames_amount();
+        if let Some(animations) = element.animations_mut() {
+            for animation in animations.iter_mut() {
+                let out_of_range = animation.frames_out_of_range(frames_amount);
+                if !out_of_range.is_empty() {
+                    changes.push(format!(
+                        "{:?}: dropped frames past the last one: {:?}",
+                        animation.anim_type, out_of_range
+                    ));
+                    animation.frames.retain(|frame| frame.value < frames_amount);
+                }
+            }
+            if let Some(aseprite_data) = aseprite_data {
+                changes.extend(ignored_tags_warning(&aseprite_data.ignored_tags));
+                let allowed = avalible_animations(hat_type).unwrap_or_default();
+                let new = aseprite_data
+                    .animations
+                    .into_iter()
+                    .filter(|animation| allowed.contains(&animation.anim_type))
+                    .collect();
+                for anim_type in add_missing_animations(animations, new) {
+                    changes.push(format!("{:?} added from the file", anim_type));
+                }
+            }
+        }
+        if let Some(wings) = element.downcast_mut::<WingsHat>() {
+            changes.extend(wings.data_mut().clamp_frames(frames_amount));
+        }
+        self.store_element_images(id)?;
+        for change in changes {
+            warn!(hat = self.name(), element = id.0; "{}", change);
+            self.load_warnings.push(change.into());
+        }
+        Ok(())
+    }
+
+    /// Puts the pixels of `image` in place of the element's image. Returns the element along
+    /// with what an aseprite file brought besides its pixels. Oversized extra hats are
+    /// reported through [`Hat::take_load_warnings`].
+    fn set_element_image(
+        &mut self,
+        id: HatElementId,
+        image: Image,
+        gl: &glow::Context,
+    ) -> Result<(&mut dyn HatElement, Option<AsepriteData>)> {
+        let element = self.element_mut(id).context("element does not exist")?;
+        let (bitmap, aseprite_data) = image.to_bitmap_with_data();
+        *element.texture_mut() = Texture::from_bitmap(gl, &bitmap)?;
+        *element.bitmap_mut() = bitmap;
+        element.base_mut().local_image_path = None;
+        if let Some(warning) = element
+            .downcast_ref::<ExtraHat>()
+            .and_then(|extra_hat| extra_hat.size_warning())
+        {
+            warn!(hat = self.name(), element = id.0; "{}", warning);
+            self.load_warnings.push(warning.into());
+        }
+        let element = self.element_mut(id).context("element does not exist")?;
+        Ok((element, aseprite_data))
+    }
+
+    /// Writes the element's image into the hat's images folder as a PNG, e.g. when it came
+    /// from an aseprite file. Returns the new path, or `None` if the image already was a
+    /// file in the hat's folder.
+    pub fn bake_element_image(&mut self, id: HatElementId) -> Result<Option<PathBuf>> {
+        if self.is_unsaved() || self.is_file_backed() {
+            bail!("only hats saved to a folder keep their images as files");
+        }
+        let element = self.element(id).context("element does not exist")?;
+        let old_path = element.bitmap().path().map(Path::to_path_buf);
+        self.store_element_images(id)?;
+        let element = self.element(id).context("element does not exist")?;
+        let new_path = element.bitmap().path().map(Path::to_path_buf);
+        Ok(new_path.filter(|path| old_path.as_ref() != Some(path)))
+    }
+
+    /// Saves the element's images that aren't in the hat's folder yet into its images
+    /// folder. Hats without a folder keep them where they are.
+    fn store_element_images(&mut self, id: HatElementId) -> Result<()> {
+        if self.is_unsaved() || self.is_file_backed() {
+            return Ok(());
+        }
+        let folder = self.path().to_path_buf();
+        let images_path = folder.join(self.images_dir_name());
+        let element = self.element_mut(id).context("element does not exist")?;
+        copy_element_images(element, None, &folder, &images_path)
+    }
+
+    /// Attaches a second image to the element, e.g. a normal map. It has to be as large as
+    /// the element's own image. Folder hats get it copied into their images folder.
+    pub fn attach_secondary_image(
+        &mut self,
+        id: HatElementId,
+        bitmap: Bitmap,
+        gl: &glow::Context,
+    ) -> Result<()> {
+        let element = self.element_mut(id).context("element does not exist")?;
+        let (width, height) = (element.bitmap().width(), element.bitmap().height());
+        if bitmap.width() != width || bitmap.height() != height {
+            bail!(
+                "secondary image is {}x{}, but the element's image is {}x{}",
+                bitmap.width(),
+                bitmap.height(),
+                width,
+                height
+            );
+        }
+        let texture = Texture::from_bitmap(gl, &bitmap)?;
+        *element.secondary_mut() = Some(SecondaryImage { texture, bitmap });
+        self.store_element_images(id)
+    }
+
+    /// Creates `src/<name>.lua` from a template and attaches it to the element. Returns the
+    /// script's path relative to the hat.
+    pub fn add_script(&mut self, id: HatElementId, name: &str) -> Result<PathBuf> {
+        if self.is_unsaved() || self.is_file_backed() {
+            bail!("only hats saved to a folder can have scripts");
+        }
+        let name = name.trim();
+        if name.is_empty() || name.contains(['/', '\\']) || name.starts_with('.') {
+            bail!("{:?} is not a valid script name", name);
+        }
+        let folder = self.path().to_path_buf();
+        let element = self.element_mut(id).context("element does not exist")?;
+        if element.base().local_script_path.is_some() {
+            bail!("the element already has a script");
+        }
+        let hat_type = element.base().hat_type;
+        let template =
+            script_template(hat_type).context(format!("{:?} can't have a script", hat_type))?;
+        let local_path = Path::new(SCRIPTS_DIR).join(format!("{}.lua", name));
+        let path = folder.join(&local_path);
+        if path.exists() {
+            bail!("{:?} already exists", &path);
+        }
+        std::fs::create_dir_all(folder.join(SCRIPTS_DIR))
+            .context(format!("could not create {:?}", folder.join(SCRIPTS_DIR)))?;
+        std::fs::write(&path, template).context(format!("could not write {:?}", &path))?;
+        element.base_mut().local_script_path = Some(local_path.clone());
+        Ok(local_path)
+    }
+
+    /// The image file itself is left alone, so it can be cleaned up with the other unused
+    /// images.
+    pub fn detach_secondary_image(&mut self, id: HatElementId) {
+        if let Some(element) = self.element_mut(id) {
+            *element.secondary_mut() = None;
+            element.base_mut().local_secondary_image_path = None;
+        }
+    }
+
+    /// Whether the hat was opened from a `.hatspp` archive rather than a folder.
+    pub fn is_file_backed(&self) -> bool {
+        self.path()
+            .extension()
+            .is_some_and(|extension| extension == HPP_EXTENSION)
+    }
+
+    /// Name of the subfolder the hat keeps its images in.
+    pub fn images_dir_name(&self) -> &str {
+        self.images_dir.as_deref().unwrap_or(DEFAULT_IMAGES_DIR)
+    }
+
+    /// The hat's images folder, refusing to work with it if it's a symlink that could
+    /// lead outside the hat.
+    fn images_dir(&self) -> Result<PathBuf> {
+        let images_path = self.path().join(self.images_dir_name());
+        let metadata = images_path
+            .symlink_metadata()
+            .context(format!("could not read {:?}", &images_path))?;
+        if !metadata.is_dir() {
+            bail!("{:?} is not a directory", &images_path);
+        }
+        let hat_path = self
+            .path()
+            .canonicalize()
+            .context(format!("could not resolve {:?}", self.path()))?;
+        Ok(hat_path.join(self.images_dir_name()))
+    }
+
+    /// Files in the hat's `images` folder that no element uses. Symlinks and subfolders
+    /// are never included.
+    pub fn unreferenced_images(&self) -> Result<Vec<PathBuf>> {
+        if self.is_file_backed() {
+            bail!("hats opened from a file have no images folder");
+        }
+        let images_path = self.images_dir()?;
+        let referenced: HashSet<PathBuf> = self
+            .elements()
+            .flat_map(|e| {
+                let local_path = e
+                    .base()
+                    .local_image_path
+                    .as_ref()
+                    .map(|path| self.path().join(path));
+                let secondary_path = e
+                    .secondary()
+                    .and_then(|secondary| secondary.bitmap.path())
+                    .map(Path::to_path_buf);
+                e.bitmap()
+                    .path()
+                    .map(Path::to_path_buf)
+                    .into_iter()
+                    .chain(local_path)
+                    .chain(secondary_path)
+            })
+            .filter_map(|path| path.canonicalize().ok())
+            .collect();
+
+        let mut unreferenced = vec![];
+        for entry in
+            std::fs::read_dir(&images_path).context(format!("could not read {:?}", &images_path))?
+        {
+            let entry = entry.context(format!("could not read {:?}", &images_path))?;
+            //file_type doesn't follow symlinks
+            if !entry.file_type().is_ok_and(|file_type| file_type.is_file()) {
+                continue;
+            }
+            let path = entry.path();
+            if !referenced.contains(&path) {
+                unreferenced.push(path);
+            }
+        }
+        unreferenced.sort();
+        Ok(unreferenced)
+    }
+
+    /// Deletes the given images, skipping any that are no longer unreferenced.
+    /// Returns how many files were removed.
+    pub fn remove_unreferenced_images(&self, paths: &[PathBuf]) -> Result<usize> {
+        let unreferenced = self.unreferenced_images()?;
+        let mut removed = 0;
+        for path in paths.iter().filter(|path| unreferenced.contains(path)) {
+            std::fs::remove_file(path).context(format!("could not remove {:?}", path))?;
+            removed += 1;
+        }
+        Ok(removed)
+    }
+
+    /// Saves a copy of the hat into `folder` and makes it the hat's folder from then on.
+    /// Images and scripts keep their place relative to the hat, images from outside of it
+    /// go into the images folder.
+    pub fn save_as(&mut self, folder: &Path) -> Result<()> {
+        if folder == self.path() {
+            return Ok(self.save(folder)?);
+        }
+        let old_folder =
+            (!self.is_unsaved() && !self.is_file_backed()).then(|| self.path().to_path_buf());
+        let images_path = folder.join(self.images_dir_name());
+        std::fs::create_dir_all(&images_path)
+            .context(format!("could not create {:?}", &images_path))?;
+        for element in self.elements.values_mut() {
+            copy_element_images(&mut **element, old_folder.as_deref(), folder, &images_path)?;
+            if let Some(old_folder) = &old_folder
+                && let Some(script_path) = &element.base().local_script_path
+                && old_folder.join(script_path).exists()
+            {
+                let new_script_path = folder.join(script_path);
+                if let Some(parent) = new_script_path.parent() {
+                    std::fs::create_dir_all(parent)
+                        .context(format!("could not create {:?}", parent))?;
+                }
+                std::fs::copy(old_folder.join(script_path), &new_script_path)
+                    .context(format!("could not copy script to {:?}", &new_script_path))?;
+            }
+        }
+        self.path = folder.to_path_buf();
+        self.save(folder)?;
+        Ok(())
+    }
+
+    /// Element images from outside `path` are copied into its images folder first, so the
+    /// saved hat doesn't depend on them.
+    pub fn save(&mut self, path: impl AsRef<Path>) -> Result<(), HatError> {
+        if self.is_empty() {
+            return Err(HatError::Empty);
+        }
+        self.check_files_integrity()?;
+        let images_path = path.as_ref().join(self.images_dir_name());
+        for element in self.elements.values_mut() {
+            copy_element_images(&mut **element, None, path.as_ref(), &images_path).map_err(
+                |err| HatError::CopyImage {
+                    path: images_path.clone(),
+                    source: err.into(),
+                },
+            )?;
+        }
+        self.write_data_json(&path.as_ref().join("data.json"))?;
+        //the real save is now the newest, so the autosave is no longer needed
+        let autosave_path = path.as_ref().join(AUTOSAVE_FILE);
+        if autosave_path.exists()
+            && let Err(err) = std::fs::remove_file(&autosave_path)
+        {
+            error!(hat = self.name(); "could not remove {:?}: {}", &autosave_path, err);
+        }
         Ok(())
     }
 
+    /// Writes the hat's data next to data.json without touching the real save.
+    pub fn autosave(&self) -> Result<()> {
+        if self.is_file_backed() || self.is_unsaved() {
+            bail!("only hats saved to a folder can be autosaved");
+        }
+        Ok(self.write_data_json(&self.path().join(AUTOSAVE_FILE))?)
+    }
+
+    fn write_data_json(&self, path: &Path) -> Result<(), HatError> {
+        let uuid_path: PathBuf = {
+            let mut path = path.to_path_buf().into_os_string();
+            path.push("_");
+            path.push(Uuid::new_v4().to_string());
+            path.into()
+        };
+
+        let data_string = serde_json::to_string_pretty(&self.gen_hat_data(HatSaveType::Folder))
+            .map_err(HatError::Serialize)?;
+
+        let mut file = File::create(&uuid_path).map_err(HatError::io(&uuid_path))?;
+
+        write!(file, "{}", data_string).map_err(HatError::io(&uuid_path))?;
+
+        if std::fs::exists(path).unwrap_or(false) {
+            if let Err(err) = std::fs::remove_file(path) {
+                std::fs::remove_file(&uuid_path).map_err(HatError::io(&uuid_path))?;
+                return Err(HatError::io(path)(err));
+            }
+        }
+
+        rename_with_retry(&uuid_path, path)
+    }
+
+    fn data_snapshot(&self) -> String {
+        let mut elements: Vec<_> = self.elements().collect();
+        elements.sort_by_key(|e| e.id().0);
+        let elements: Vec<_> = elements
+            .into_iter()
+            .map(|e| e.hat_element_data_ref().to_hat_element_data())
+            .collect();
+        serde_json::to_string(&(self.name(), elements)).expect("should always succeed")
+    }
+
+    /// Cheap to compare stand-in for [`Hat::snapshot`], which is only worth taking when
+    /// this changes.
+    pub fn snapshot_key(&self) -> String {
+        let mut elements: Vec<_> = self.elements().collect();
+        elements.sort_by_key(|e| e.id().0);
+        let elements: Vec<_> = elements
+            .into_iter()
+            .map(|e| {
+                (
+                    e.id().0,
+                    e.hat_element_data_ref().to_hat_element_data(),
+                    SnapshotImage::key(e.bitmap()),
+                    e.secondary()
+                        .map(|secondary| SnapshotImage::key(&secondary.bitmap)),
+                )
+            })
+            .collect();
+        serde_json::to_string(&(self.name(), self.name_set_by_user, &self.metadata, elements))
+            .expect("should always succeed")
+    }
+
+    pub fn snapshot(&self) -> HatSnapshot {
+        let mut elements: Vec<_> = self
+            .elements()
+            .map(|e| ElementSnapshot {
+                id: e.id(),
+                data: e.hat_element_data_ref().to_hat_element_data(),
+                image: SnapshotImage::new(e.bitmap()),
+                secondary: e
+                    .secondary()
+                    .map(|secondary| SnapshotImage::new(&secondary.bitmap)),
+            })
+            .collect();
+        elements.sort_by_key(|e| e.id.0);
+        HatSnapshot {
+            name: self.name.clone(),
+            name_set_by_user: self.name_set_by_user,
+            metadata: self.metadata.clone(),
+            elements,
+            key: self.snapshot_key(),
+        }
+    }
+
+    /// Brings the hat back to `snapshot`. Elements keep their ids, and only images that
+    /// differ from the current ones are loaded again. Nothing changes if one can't be.
+    pub fn restore_snapshot(&mut self, snapshot: &HatSnapshot, gl: &glow::Context) -> Result<()> {
+        let mut rebuilt = HashMap::new();
+        let mut secondaries = HashMap::new();
+        for element in &snapshot.elements {
+            let current = self.element(element.id).filter(|current| {
+                current.base().hat_type == element.data.base().hat_type
+                    && element.image.matches(current.bitmap())
+            });
+            if current.is_none() {
+                let image = element.image.load()?;
+                rebuilt.insert(
+                    element.id,
+                    load_element(element.data.clone(), image, element.id, gl)?,
+                );
+            }
+            let secondary_kept =
+                current.is_some_and(|current| match (current.secondary(), &element.secondary) {
+                    (Some(current), Some(image)) => image.matches(&current.bitmap),
+                    (None, None) => true,
+                    _ => false,
+                });
+            if !secondary_kept {
+                let secondary = match &element.secondary {
+                    Some(image) => {
+                        let (bitmap, _) = image.load()?.to_bitmap_with_data();
+                        let texture = Texture::from_bitmap(gl, &bitmap)?;
+                        Some(SecondaryImage { texture, bitmap })
+                    }
+                    None => None,
+                };
+                secondaries.insert(element.id, secondary);
+            }
+        }
+
+        let mut old_elements = std::mem::take(&mut self.elements);
+        for element in &snapshot.elements {
+            let mut restored = rebuilt
+                .remove(&element.id)
+                .or_else(|| old_elements.remove(&element.id))
+                .expect("element should be either kept or rebuilt");
+            set_element_data(&mut *restored, element.data.clone());
+            if let Some(secondary) = secondaries.remove(&element.id) {
+                *restored.secondary_mut() = secondary;
+            }
+            self.elements.insert(element.id, restored);
+        }
+        self.name = snapshot.name.clone();
+        self.name_set_by_user = snapshot.name_set_by_user;
+        self.metadata = snapshot.metadata.clone();
+        Ok(())
+    }
+
+    /// Whether the hat changed since it was last loaded or saved.
+    pub fn is_dirty(&self) -> bool {
+        self.saved_snapshot
+            .as_ref()
+            .is_none_or(|snapshot| *snapshot != self.data_snapshot())
+    }
+
+    /// What differs between the hat in memory and its data.json on disk. The saved data
+    /// goes through [`HatData`] first, so fields missing from older files don't show up.
+    pub fn changes_since_save(&self) -> Result<Vec<JsonChange>> {
+        if self.is_file_backed() || self.is_unsaved() {
+            bail!("only hats saved to a folder have a data.json to compare with");
+        }
+        let data_path = self.path().join("data.json");
+        let data_string = std::fs::read_to_string(&data_path)
+            .context(format!("could not read {:?}", &data_path))?;
+        let saved: HatData = serde_json::from_str(&data_string)
+            .context(format!("could not parse {:?}", &data_path))?;
+        let saved = serde_json::to_value(saved).expect("should always succeed");
+        let current = serde_json::to_value(self.gen_hat_data(HatSaveType::Folder))
+            .expect("should always succeed");
+        Ok(json_diff(&saved, &current))
+    }
+
+    pub fn mark_saved(&mut self) {
+        self.saved_snapshot = Some(self.data_snapshot());
+    }
+
+    /// Returns `true` once if the hat was loaded while an autosave newer than its
+    /// data.json was lying next to it.
+    pub fn take_newer_autosave(&mut self) -> bool {
+        std::mem::take(&mut self.has_newer_autosave)
+    }
+
     pub fn gen_hat_data(&self, save_type: HatSaveType) -> HatData {
         let mut hat_data = HatData::new(self.name().to_string());
+        hat_data.name_set_by_user = self.name_set_by_user;
+        hat_data.metadata = self.metadata.clone();
+        //archives always use the standard layout
+        if let HatSaveType::Folder = save_type {
+            hat_data.images_dir = self.images_dir.clone();
+        }
         for element in self.elements() {
             let local_image_path = match save_type {
-                HatSaveType::Folder => {
-                    //TODO: account for the situation where image is NOT in images - copy pngs
-                    let image_path = element.bitmap().path().unwrap();
-                    match image_path.local_path(self.path()) {
-                        Ok(path) => path,
-                        Err(LocalPathError::PathNotInDir) => todo!(),
-                    }
+                //images from elsewhere are copied there on save
+                HatSaveType::Folder => element
+                    .bitmap()
+                    .path()
+                    .and_then(|path| path.local_path(self.path()).ok())
+                    .unwrap_or_else(|| {
+                        Path::new(self.images_dir_name()).join(format!("{}.png", element.id().0))
+                    }),
+                HatSaveType::File => {
+                    Path::new(DEFAULT_IMAGES_DIR).join(format!("{}.png", element.id().0))
                 }
-                HatSaveType::File => Path::new("images").join(format!("{}.png", element.id().0)),
             };
+            let local_secondary_image_path = element.secondary().map(|secondary| {
+                let file_name = format!("{}_secondary.png", element.id().0);
+                match save_type {
+                    HatSaveType::Folder => secondary
+                        .bitmap
+                        .path()
+                        .and_then(|path| path.local_path(self.path()).ok())
+                        .unwrap_or_else(|| Path::new(self.images_dir_name()).join(file_name)),
+                    HatSaveType::File => Path::new(DEFAULT_IMAGES_DIR).join(file_name),
+                }
+            });
             let mut element_data = element.hat_element_data_ref().to_hat_element_data();
             let base = element_data.base_mut();
             base.local_image_path = Some(local_image_path);
+            base.local_secondary_image_path = local_secondary_image_path;
             assert!(base.local_image_path.is_some());
             hat_data.elements.push(element_data);
         }
         hat_data
     }
 
-    pub fn check_files_integrity(&self) -> Result<()> {
+    /// Fails with every missing image and script, not only the first one.
+    pub fn check_files_integrity(&self) -> Result<(), HatError> {
         if !self.path().exists() {
-            bail!("{:?} does not exist", self.path());
+            return Err(HatError::Integrity(vec![self.path().to_path_buf()]));
+        }
+        let mut missing: Vec<_> = self
+            .elements()
+            .flat_map(|element| {
+                let base = element.base();
+                [
+                    &base.local_image_path,
+                    &base.local_secondary_image_path,
+                    &base.local_script_path,
+                ]
+            })
+            .flatten()
+            .map(|path| self.path().join(path))
+            .filter(|path| !path.exists())
+            .collect();
+        if missing.is_empty() {
+            return Ok(());
         }
+        missing.sort();
+        missing.dedup();
+        Err(HatError::Integrity(missing))
+    }
+
+    /// Runs every check an exported hat should pass, collecting all problems instead of
+    /// stopping at the first one.
+    pub fn export_report(&self) -> ExportReport {
+        let mut report = ExportReport::default();
+        if self.is_empty() {
+            report.errors.push(HatError::Empty.to_string());
+        }
+        if let Err(err) = self.check_files_integrity() {
+            report.errors.push(err.to_string());
+        }
+        report.errors.extend(
+            self.gen_hat_data(HatSaveType::File)
+                .duplicate_unique_elements(),
+        );
         for element in self.elements() {
-            if let Some(path) = &element.base().local_image_path {
-                let path = self.path().join(path);
-                if !path.exists() {
-                    bail!("{:?} does not exist", path);
+            let hat_type = element.base().hat_type;
+            let frames_amount = element.frames_amount();
+            for animation in element.animations().into_iter().flatten() {
+                if animation.frames.is_empty() {
+                    report.errors.push(format!(
+                        "{:?} animation of {:?} has no frames",
+                        animation.anim_type, hat_type
+                    ));
+                }
+                let out_of_range = animation.frames_out_of_range(frames_amount);
+                if !out_of_range.is_empty() {
+                    report.errors.push(format!(
+                        "{:?} animation of {:?} uses frames past the last one: {:?}",
+                        animation.anim_type, hat_type, out_of_range
+                    ));
                 }
             }
-            if let Some(path) = &element.base().local_script_path
-                && !self.path().join(path).exists()
+            if let Some(warning) = element
+                .downcast_ref::<ExtraHat>()
+                .and_then(|extra_hat| extra_hat.size_warning())
             {
-                let path = self.path().join(path);
-                if !path.exists() {
-                    bail!("{:?} does not exist", path);
-                }
+                report.warnings.push(warning);
             }
         }
-
-        Ok(())
+        for element in self.frame_size_mismatches() {
+            report.warnings.push(format!(
+                "frame size of {:?} doesn't fit its image",
+                element.base().hat_type
+            ));
+        }
+        if self.pets_amount() > self.max_pets {
+            report.warnings.push(format!(
+                "hat has {} pets, more than the limit of {}",
+                self.pets_amount(),
+                self.max_pets
+            ));
+        }
+        report
     }
 
-    pub fn export_to_file(&self, path: impl AsRef<Path>) -> Result<()> {
-        if let Err(err) = self.check_files_integrity() {
-            bail!("failed files integrity check: {}", err.to_string());
+    /// `flatten_frame_delays` is for game builds that don't support per-frame delays, see
+    /// [`HatData::flatten_frame_delays`]. Timing it changes is reported through
+    /// [`Hat::take_load_warnings`].
+    pub fn export_to_file(
+        &mut self,
+        path: impl AsRef<Path>,
+        flatten_frame_delays: bool,
+    ) -> Result<(), HatError> {
+        if self.is_empty() {
+            return Err(HatError::Empty);
         }
+        self.check_files_integrity()?;
         let path = path.as_ref();
 
         let uuid_path: PathBuf = {
@@ -604,60 +2393,66 @@ impl Hat {
             path.into()
         };
 
-        let file =
-            File::create(&uuid_path).context(format!("could not create {:?}", &uuid_path))?;
-        let hat_data = self.gen_hat_data(HatSaveType::File);
+        let mut hat_data = self.gen_hat_data(HatSaveType::File);
+        if flatten_frame_delays {
+            for warning in hat_data.flatten_frame_delays() {
+                warn!(hat = self.name(); "{}", warning);
+                self.load_warnings.push(warning.into());
+            }
+        }
+        let data_string = serde_json::to_string_pretty(&hat_data).map_err(HatError::Serialize)?;
+        let file = File::create(&uuid_path).map_err(HatError::io(&uuid_path))?;
         let mut zip_writer = ZipWriter::new(file);
         let options = SimpleFileOptions::default();
 
         zip_writer
-            .add_directory("images", options)
-            .context("could not add images directory")?;
+            .add_directory(DEFAULT_IMAGES_DIR, options)
+            .map_err(HatError::archive(&uuid_path))?;
 
         for (element_data, element) in hat_data.elements.iter().zip(self.elements()) {
-            let mut bitmap_png_data = vec![];
-            element
-                .bitmap()
-                .to_png_bytes(&mut bitmap_png_data)
-                .context(format!(
-                    "could not convert image at {:?} to png data",
-                    element.bitmap().path().unwrap_or(Path::new("[no path]"))
-                ))?;
-            zip_writer
-                .start_file_from_path(
-                    element_data.base().local_image_path.as_ref().unwrap(),
-                    options,
-                )
-                .context("could not start adding image file")?;
-            zip_writer
-                .write_all(&bitmap_png_data)
-                .context("could not add image file")?;
+            let base = element_data.base();
+            let images = [(element.bitmap(), &base.local_image_path)]
+                .into_iter()
+                .chain(
+                    element
+                        .secondary()
+                        .map(|secondary| (&secondary.bitmap, &base.local_secondary_image_path)),
+                );
+            for (bitmap, local_path) in images {
+                let mut bitmap_png_data = vec![];
+                bitmap
+                    .to_png_bytes(&mut bitmap_png_data)
+                    .map_err(|err| HatError::Image {
+                        path: bitmap
+                            .path()
+                            .unwrap_or(Path::new("[no path]"))
+                            .to_path_buf(),
+                        source: err.into(),
+                    })?;
+                zip_writer
+                    .start_file_from_path(local_path.as_ref().unwrap(), options)
+                    .map_err(HatError::archive(&uuid_path))?;
+                zip_writer
+                    .write_all(&bitmap_png_data)
+                    .map_err(HatError::io(&uuid_path))?;
+            }
         }
 
         zip_writer
             .start_file("data.json", options)
-            .context("could not start adding data.json file")?;
-        zip_writer
-            .write_all(
-                serde_json::to_string_pretty(&hat_data)
-                    .context("could not generate data.json")?
-                    .as_bytes(),
-            )
-            .context("could not write data.json")?;
+            .map_err(HatError::archive(&uuid_path))?;
         zip_writer
-            .finish()
-            .context("could not finish writing files")?;
+            .write_all(data_string.as_bytes())
+            .map_err(HatError::io(&uuid_path))?;
+        zip_writer.finish().map_err(HatError::archive(&uuid_path))?;
 
         if std::fs::exists(path).unwrap_or(false) {
             if let Err(err) = std::fs::remove_file(path) {
-                std::fs::remove_file(&uuid_path)
-                    .context(format!("could not remove file at {:?}", &uuid_path))?;
-                return Err(err.into());
+                std::fs::remove_file(&uuid_path).map_err(HatError::io(&uuid_path))?;
+                return Err(HatError::io(path)(err));
             }
         }
-        std::fs::rename(&uuid_path, path)
-            .context(format!("could not rename file at {:?}", &uuid_path))?;
-        Ok(())
+        rename_with_retry(&uuid_path, path)
     }
 
     hat_by_type_def!(wereable, WearableHat, HatType::Wearable);
@@ -690,7 +2485,655 @@ impl Hat {
         &mut self.name_set_by_user
     }
 
+    pub fn metadata(&self) -> &HatMetadata {
+        &self.metadata
+    }
+
+    pub fn metadata_mut(&mut self) -> &mut HatMetadata {
+        &mut self.metadata
+    }
+
     pub fn id(&self) -> HatId {
         self.id
     }
+
+    pub fn max_pets(&self) -> usize {
+        self.max_pets
+    }
+
+    /// Game builds differ in how many pets they allow, so the cap is adjustable.
+    pub fn set_max_pets(&mut self, max_pets: usize) {
+        self.max_pets = max_pets;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+
+    use bevy_math::IVec2;
+    use eframe::{egui, glow};
+    use pixas::bitmap::Bitmap;
+
+    use super::{
+        FlyingPetHat, Hat, HatElement, HatElementId, HatError, HatSaveType, HatSummary, HatWarning,
+        LoadHatElement, WearableHat,
+    };
+    use crate::{
+        animations::{AnimType, Animation, Frame},
+        hats_data::{
+            ExtraHatData, HatData, HatElementData, HatType, MAX_EXTRA_HAT_SIZE, WearableData,
+            WingsData,
+        },
+        image::{Image, TrimBounds},
+        test_utils::{HeadlessGl, TempDir},
+    };
+
+    /// An unsaved hat with a two frame wearable and a secondary image as large as it.
+    fn hat_with_secondary(gl: &glow::Context) -> (Hat, HatElementId) {
+        let mut hat = Hat::new(Path::new(""), "Secondary");
+        let id = hat
+            .add_element_data(
+                HatElementData::Wearable(WearableData::default()),
+                Image::Bitmap(Bitmap::empty(64, 32)),
+                gl,
+            )
+            .unwrap()
+            .unwrap();
+        hat.attach_secondary_image(id, Bitmap::empty(64, 32), gl)
+            .unwrap();
+        (hat, id)
+    }
+
+    fn secondary_size(hat: &Hat, id: HatElementId) -> Option<(i32, i32)> {
+        let secondary = hat.element(id).unwrap().secondary()?;
+        Some((secondary.bitmap.width(), secondary.bitmap.height()))
+    }
+
+    #[test]
+    fn empty_hat_not_exported() {
+        let dir = TempDir::new().unwrap();
+        let mut hat = Hat::new(dir.path(), "Empty");
+        let archive_path = dir.path().join("empty.hatspp");
+
+        let err = hat.export_to_file(&archive_path, false).unwrap_err();
+        assert!(matches!(err, HatError::Empty));
+        assert_eq!(
+            err.to_string(),
+            "hat has no elements, the game won't load it"
+        );
+        assert!(!archive_path.exists());
+        assert!(matches!(hat.save(dir.path()), Err(HatError::Empty)));
+    }
+
+    #[test]
+    fn export_report_collects_animation_errors() {
+        let headless = HeadlessGl::new().unwrap();
+        let gl = &headless.gl;
+        let dir = TempDir::new().unwrap();
+        let mut hat = Hat::new(dir.path(), "Broken");
+        let mut data = WearableData::default();
+        data.base.frame_size = IVec2::new(32, 32);
+        data.animations = vec![
+            Animation::new(AnimType::OnDefault, 0.1, true, vec![]),
+            Animation::new(AnimType::OnDuckJump, 0.1, false, vec![Frame::new(5)]),
+        ];
+        let wearable = WearableHat::load(data, Image::Bitmap(Bitmap::empty(64, 32)), gl).unwrap();
+        hat.add_element(wearable).unwrap();
+
+        let report = hat.export_report();
+        assert_eq!(report.errors.len(), 2);
+        assert!(report.warnings.is_empty());
+    }
+
+    #[test]
+    fn duplicates_follow_element_rules() {
+        let headless = HeadlessGl::new().unwrap();
+        let gl = &headless.gl;
+        let dir = TempDir::new().unwrap();
+        let mut hat = Hat::new(dir.path(), "Pets");
+        let image = || Image::Bitmap(Bitmap::empty(32, 32));
+        let wearable = WearableHat::load(Default::default(), image(), gl).unwrap();
+        let wearable_id = hat.add_element(wearable).unwrap();
+        let pet = FlyingPetHat::load(Default::default(), image(), gl).unwrap();
+        let pet_id = hat.add_element(pet).unwrap();
+
+        assert_eq!(hat.duplicate_element(wearable_id, gl), None);
+        let copy_id = hat.duplicate_element(pet_id, gl).unwrap();
+        assert_ne!(copy_id, pet_id);
+        assert_eq!(hat.elements().count(), 3);
+    }
+
+    #[test]
+    fn integrity_check_lists_every_missing_file() {
+        let headless = HeadlessGl::new().unwrap();
+        let gl = &headless.gl;
+        let dir = TempDir::new().unwrap();
+        let mut hat = Hat::new(dir.path(), "Broken");
+        let mut data = WearableData::default();
+        data.base.local_image_path = Some("images/hat.png".into());
+        data.base.local_script_path = Some("scripts/hat.lua".into());
+        let wearable = WearableHat::load(data, Image::Bitmap(Bitmap::empty(64, 32)), gl).unwrap();
+        hat.add_element(wearable).unwrap();
+
+        let Err(HatError::Integrity(missing)) = hat.check_files_integrity() else {
+            panic!("expected integrity error");
+        };
+        assert_eq!(
+            missing,
+            [
+                dir.path().join("images/hat.png"),
+                dir.path().join("scripts/hat.lua")
+            ]
+        );
+    }
+
+    #[test]
+    fn script_is_created_from_template() {
+        let headless = HeadlessGl::new().unwrap();
+        let gl = &headless.gl;
+        let dir = TempDir::new().unwrap();
+        let mut hat = Hat::new(dir.path(), "Scripted");
+        let wearable = WearableHat::load(
+            WearableData::default(),
+            Image::Bitmap(Bitmap::empty(64, 32)),
+            gl,
+        )
+        .unwrap();
+        let id = hat.add_element(wearable).unwrap();
+
+        assert!(hat.add_script(id, "../outside").is_err());
+        let local_path = hat.add_script(id, "quack").unwrap();
+        assert_eq!(local_path, Path::new("src/quack.lua"));
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join(&local_path)).unwrap(),
+            include_str!("../script-templates/wearable.lua")
+        );
+        assert_eq!(
+            hat.element(id).unwrap().base().local_script_path,
+            Some(local_path)
+        );
+        assert!(hat.add_script(id, "again").is_err());
+    }
+
+    #[test]
+    fn gif_export_skips_missing_frames() {
+        let headless = HeadlessGl::new().unwrap();
+        let gl = &headless.gl;
+        let dir = TempDir::new().unwrap();
+        let mut data = WearableData::default();
+        data.base.frame_size = IVec2::new(32, 32);
+        let wearable = WearableHat::load(data, Image::Bitmap(Bitmap::empty(64, 32)), gl).unwrap();
+        let frames = vec![Frame::new(1), Frame::new(7)];
+        let animation = Animation::new(AnimType::OnDefault, 0.1, true, frames);
+        let gif_path = dir.path().join("idle.gif");
+
+        let warnings = wearable.export_gif(&animation, &gif_path).unwrap();
+        assert_eq!(warnings.len(), 1);
+        let mut decoder = gif::DecodeOptions::new()
+            .read_info(std::fs::File::open(&gif_path).unwrap())
+            .unwrap();
+        let mut frames = 0;
+        while decoder.read_next_frame().unwrap().is_some() {
+            frames += 1;
+        }
+        assert_eq!(frames, 1);
+    }
+
+    #[test]
+    fn frame_rects() {
+        let headless = HeadlessGl::new().unwrap();
+        let gl = &headless.gl;
+        let mut data = WearableData::default();
+        data.base.frame_size = IVec2::new(32, 32);
+        //two columns, two rows
+        let wearable = WearableHat::load(data, Image::Bitmap(Bitmap::empty(64, 64)), gl).unwrap();
+
+        assert_eq!(
+            wearable.frame_rect(0),
+            Some(egui::Rect::from_min_size(
+                egui::pos2(0.0, 0.0),
+                egui::vec2(32.0, 32.0)
+            ))
+        );
+        assert_eq!(
+            wearable.frame_rect(3),
+            Some(egui::Rect::from_min_size(
+                egui::pos2(32.0, 32.0),
+                egui::vec2(32.0, 32.0)
+            ))
+        );
+        assert_eq!(wearable.frame_rect(4), None);
+    }
+
+    #[test]
+    fn zero_frame_size_has_no_frames() {
+        let headless = HeadlessGl::new().unwrap();
+        let gl = &headless.gl;
+        let mut data = WearableData::default();
+        data.base.frame_size = IVec2::ZERO;
+        let wearable = WearableHat::load(data, Image::Bitmap(Bitmap::empty(64, 64)), gl).unwrap();
+
+        assert_eq!(wearable.frames_amount(), 0);
+    }
+
+    #[test]
+    fn save_and_reload() {
+        let headless = HeadlessGl::new().unwrap();
+        let gl = &headless.gl;
+        let dir = TempDir::new().unwrap();
+        let images_path = dir.path().join("images");
+        std::fs::create_dir(&images_path).unwrap();
+        let image_path = images_path.join("wearable.png");
+        Bitmap::empty(64, 32).save(&image_path).unwrap();
+
+        let mut hat = Hat::load(dir.path(), gl).unwrap();
+        let wearable = WearableHat::load_from_path(&image_path, gl).unwrap();
+        let frame_size = wearable.base().frame_size;
+        hat.add_element(wearable).unwrap();
+        hat.save(dir.path()).unwrap();
+
+        let reloaded = Hat::load(dir.path(), gl).unwrap();
+        let wearable = reloaded.wereable().unwrap();
+        assert_eq!(reloaded.summary(), hat.summary());
+        assert_eq!(reloaded.elements().count(), 1);
+        assert_eq!(wearable.base().hat_type, HatType::Wearable);
+        assert_eq!(wearable.base().frame_size, frame_size);
+        assert_eq!(wearable.bitmap().width(), 64);
+        assert_eq!(wearable.bitmap().height(), 32);
+    }
+
+    #[test]
+    fn external_images_are_copied_on_save() {
+        let headless = HeadlessGl::new().unwrap();
+        let gl = &headless.gl;
+        let hat_dir = TempDir::new().unwrap();
+        let downloads = TempDir::new().unwrap();
+        let image_path = downloads.path().join("wearable.png");
+        Bitmap::empty(64, 32).save(&image_path).unwrap();
+
+        let mut hat = Hat::load(hat_dir.path(), gl).unwrap();
+        let id = hat
+            .add_element(WearableHat::load_from_path(&image_path, gl).unwrap())
+            .unwrap();
+        hat.save(hat_dir.path()).unwrap();
+
+        let copied_path = hat_dir.path().join("images").join(format!("{}.png", id.0));
+        assert!(copied_path.exists());
+        assert_eq!(
+            hat.element(id).unwrap().bitmap().path(),
+            Some(copied_path.as_path())
+        );
+        let reloaded = Hat::load(hat_dir.path(), gl).unwrap();
+        assert_eq!(
+            reloaded.wereable().unwrap().bitmap().path(),
+            Some(copied_path.as_path())
+        );
+    }
+
+    #[test]
+    fn save_as_copies_images() {
+        let headless = HeadlessGl::new().unwrap();
+        let gl = &headless.gl;
+        let dir = TempDir::new().unwrap();
+        let old_folder = dir.path().join("old");
+        let new_folder = dir.path().join("new");
+        std::fs::create_dir_all(old_folder.join("images")).unwrap();
+        let image_path = old_folder.join("images").join("wearable.png");
+        Bitmap::empty(64, 32).save(&image_path).unwrap();
+
+        let mut hat = Hat::load(&old_folder, gl).unwrap();
+        hat.add_element(WearableHat::load_from_path(&image_path, gl).unwrap())
+            .unwrap();
+        hat.save(&old_folder).unwrap();
+        hat.save_as(&new_folder).unwrap();
+
+        assert_eq!(hat.path(), new_folder);
+        assert!(new_folder.join("images").join("wearable.png").exists());
+        assert!(image_path.exists());
+        let reloaded = Hat::load(&new_folder, gl).unwrap();
+        assert_eq!(
+            reloaded.wereable().unwrap().bitmap().path(),
+            Some(new_folder.join("images").join("wearable.png").as_path())
+        );
+    }
+
+    #[test]
+    fn unsaved_hat_saved_as_copies_images() {
+        let headless = HeadlessGl::new().unwrap();
+        let gl = &headless.gl;
+        let dir = TempDir::new().unwrap();
+        let image_path = dir.path().join("wearable.png");
+        Bitmap::empty(64, 32).save(&image_path).unwrap();
+        let folder = dir.path().join("hat");
+
+        let mut hat = Hat::new(Path::new(""), "Unsaved");
+        let id = hat
+            .add_element(WearableHat::load_from_path(&image_path, gl).unwrap())
+            .unwrap();
+        hat.save_as(&folder).unwrap();
+
+        let copied_path = folder.join("images").join(format!("{}.png", id.0));
+        assert_eq!(hat.path(), folder);
+        assert!(copied_path.exists());
+        let reloaded = Hat::load(&folder, gl).unwrap();
+        assert_eq!(
+            reloaded.wereable().unwrap().bitmap().path(),
+            Some(copied_path.as_path())
+        );
+    }
+
+    #[test]
+    fn external_secondary_images_are_copied_on_save() {
+        let headless = HeadlessGl::new().unwrap();
+        let gl = &headless.gl;
+        let dir = TempDir::new().unwrap();
+        let image_path = dir.path().join("wearable.png");
+        let secondary_path = dir.path().join("normal.png");
+        Bitmap::empty(64, 32).save(&image_path).unwrap();
+        Bitmap::empty(64, 32).save(&secondary_path).unwrap();
+        let folder = dir.path().join("hat");
+
+        let mut hat = Hat::new(Path::new(""), "Unsaved");
+        let id = hat
+            .add_element(WearableHat::load_from_path(&image_path, gl).unwrap())
+            .unwrap();
+        hat.attach_secondary_image(id, Bitmap::from_path(&secondary_path).unwrap(), gl)
+            .unwrap();
+        hat.save_as(&folder).unwrap();
+
+        let copied_path = folder
+            .join("images")
+            .join(format!("{}_secondary.png", id.0));
+        assert!(copied_path.exists());
+        let reloaded = Hat::load(&folder, gl).unwrap();
+        let secondary = reloaded.wereable().unwrap().secondary().unwrap();
+        assert_eq!(secondary.bitmap.path(), Some(copied_path.as_path()));
+    }
+
+    #[test]
+    fn secondary_image_follows_trim_and_convert() {
+        let headless = HeadlessGl::new().unwrap();
+        let gl = &headless.gl;
+        let (mut hat, id) = hat_with_secondary(gl);
+
+        let bounds = TrimBounds {
+            left: 2,
+            top: 0,
+            right: 0,
+            bottom: 0,
+        };
+        hat.trim_element(id, bounds, gl).unwrap();
+        assert_eq!(secondary_size(&hat, id), Some((60, 32)));
+
+        let new_id = hat.convert_element(id, HatType::FlyingPet, gl).unwrap();
+        assert_eq!(secondary_size(&hat, new_id), Some((60, 32)));
+    }
+
+    #[test]
+    fn mismatched_secondary_image_detached_on_replace() {
+        let headless = HeadlessGl::new().unwrap();
+        let gl = &headless.gl;
+        let (mut hat, id) = hat_with_secondary(gl);
+
+        hat.replace_element_image(id, Image::Bitmap(Bitmap::empty(64, 32)), gl)
+            .unwrap();
+        assert_eq!(secondary_size(&hat, id), Some((64, 32)));
+        assert!(hat.take_load_warnings().is_empty());
+
+        hat.replace_element_image(id, Image::Bitmap(Bitmap::empty(32, 32)), gl)
+            .unwrap();
+        assert_eq!(secondary_size(&hat, id), None);
+        assert_eq!(hat.take_load_warnings().len(), 1);
+
+        let (mut hat, id) = hat_with_secondary(gl);
+        hat.reimport_element_image(id, Image::Bitmap(Bitmap::empty(64, 32)), gl)
+            .unwrap();
+        assert_eq!(secondary_size(&hat, id), Some((64, 32)));
+        assert!(hat.take_load_warnings().is_empty());
+
+        hat.reimport_element_image(id, Image::Bitmap(Bitmap::empty(96, 32)), gl)
+            .unwrap();
+        assert_eq!(secondary_size(&hat, id), None);
+        let warnings = hat.take_load_warnings();
+        assert!(warnings[0].details.contains("secondary image"));
+    }
+
+    #[test]
+    fn external_secondary_image_listed_in_folder_data() {
+        let headless = HeadlessGl::new().unwrap();
+        let gl = &headless.gl;
+        let (hat, id) = hat_with_secondary(gl);
+
+        let data = hat.gen_hat_data(HatSaveType::Folder);
+        assert_eq!(
+            data.elements[0].base().local_secondary_image_path,
+            Some(Path::new("images").join(format!("{}_secondary.png", id.0)))
+        );
+    }
+
+    #[test]
+    fn frame_delays_survive_save_and_reload() {
+        let headless = HeadlessGl::new().unwrap();
+        let gl = &headless.gl;
+        let dir = TempDir::new().unwrap();
+        let mut hat = Hat::load(dir.path(), gl).unwrap();
+        let mut data = WearableData::default();
+        data.base.frame_size = IVec2::new(32, 32);
+        // How aseprite imports come in: the default delay, overridden by every frame.
+        let frames = vec![Frame::with_delay(0, 0.25), Frame::with_delay(1, 0.5)];
+        data.animations = vec![Animation::new(AnimType::OnDefault, 0.1, true, frames)];
+        let wearable = WearableHat::load(data, Image::Bitmap(Bitmap::empty(64, 32)), gl).unwrap();
+        hat.add_element(wearable).unwrap();
+        hat.save(dir.path()).unwrap();
+
+        let reloaded = Hat::load(dir.path(), gl).unwrap();
+        let animation = &reloaded.wereable().unwrap().animations().unwrap()[0];
+        let delays: Vec<_> = animation.frames.iter().map(|frame| frame.delay).collect();
+        assert_eq!(delays, [Some(0.25), Some(0.5)]);
+        assert_eq!(animation.total_duration(), 0.75);
+    }
+
+    #[test]
+    fn wings_frames_clamped_on_load() {
+        let headless = HeadlessGl::new().unwrap();
+        let gl = &headless.gl;
+        let dir = TempDir::new().unwrap();
+        let images_path = dir.path().join("images");
+        std::fs::create_dir(&images_path).unwrap();
+        //two 32x32 frames
+        Bitmap::empty(64, 32)
+            .save(&images_path.join("wings.png"))
+            .unwrap();
+
+        let mut wings = WingsData::default();
+        wings.base.local_image_path = Some("images/wings.png".into());
+        wings.glide_frame = 1;
+        wings.idle_frame = 5;
+        let mut data = HatData::new("Wings".to_string());
+        data.elements.push(HatElementData::Wings(wings));
+        std::fs::write(
+            dir.path().join("data.json"),
+            serde_json::to_string(&data).unwrap(),
+        )
+        .unwrap();
+
+        let mut hat = Hat::load(dir.path(), gl).unwrap();
+        let wings = hat.wings().unwrap();
+        assert_eq!(wings.frames_amount(), 2);
+        assert_eq!(wings.data().glide_frame, 1);
+        assert_eq!(wings.data().idle_frame, 1);
+        let warnings = hat.take_load_warnings();
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].details.contains("idle frame"));
+    }
+
+    fn load_extra_hat(width: i32, height: i32) -> Vec<HatWarning> {
+        let headless = HeadlessGl::new().unwrap();
+        let gl = &headless.gl;
+        let dir = TempDir::new().unwrap();
+        let images_path = dir.path().join("images");
+        std::fs::create_dir(&images_path).unwrap();
+        Bitmap::empty(width as _, height as _)
+            .save(&images_path.join("extra.png"))
+            .unwrap();
+
+        let mut extra = ExtraHatData::default();
+        extra.base.local_image_path = Some("images/extra.png".into());
+        let mut data = HatData::new("Extra".to_string());
+        data.elements.push(HatElementData::Extra(extra));
+        std::fs::write(
+            dir.path().join("data.json"),
+            serde_json::to_string(&data).unwrap(),
+        )
+        .unwrap();
+
+        let mut hat = Hat::load(dir.path(), gl).unwrap();
+        assert_eq!(hat.elements().count(), 1);
+        hat.take_load_warnings()
+    }
+
+    #[test]
+    fn frame_size_mismatch_warns_on_load() {
+        let headless = HeadlessGl::new().unwrap();
+        let gl = &headless.gl;
+        let dir = TempDir::new().unwrap();
+        let images_path = dir.path().join("images");
+        std::fs::create_dir(&images_path).unwrap();
+        Bitmap::empty(50, 32)
+            .save(&images_path.join("wearable.png"))
+            .unwrap();
+
+        let mut wearable = WearableData::default();
+        wearable.base.frame_size = IVec2::new(32, 32);
+        wearable.base.local_image_path = Some("images/wearable.png".into());
+        let mut data = HatData::new("Mismatched".to_string());
+        data.elements.push(HatElementData::Wearable(wearable));
+        std::fs::write(
+            dir.path().join("data.json"),
+            serde_json::to_string(&data).unwrap(),
+        )
+        .unwrap();
+
+        let mut hat = Hat::load(dir.path(), gl).unwrap();
+        let warnings = hat.take_load_warnings();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].key, Some("184"));
+        assert!(warnings[0].details.contains("50x32"));
+    }
+
+    #[test]
+    fn oversized_extra_hat_warns() {
+        let warnings = load_extra_hat(MAX_EXTRA_HAT_SIZE.x + 1, MAX_EXTRA_HAT_SIZE.y);
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].details.contains("extra hat"));
+
+        assert!(load_extra_hat(MAX_EXTRA_HAT_SIZE.x, MAX_EXTRA_HAT_SIZE.y).is_empty());
+    }
+
+    #[test]
+    fn oversized_extra_hat_image_replacement_warns() {
+        let headless = HeadlessGl::new().unwrap();
+        let gl = &headless.gl;
+        let mut hat = Hat::new(Path::new(""), "Extra");
+        let id = hat
+            .add_element_data(
+                HatElementData::Extra(ExtraHatData::default()),
+                Image::Bitmap(Bitmap::empty(32, 32)),
+                gl,
+            )
+            .unwrap()
+            .unwrap();
+        assert!(hat.take_load_warnings().is_empty());
+
+        let oversized = Bitmap::empty((MAX_EXTRA_HAT_SIZE.x + 1) as _, 32);
+        hat.replace_element_image(id, Image::Bitmap(oversized), gl)
+            .unwrap();
+        let warnings = hat.take_load_warnings();
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].details.contains("extra hat"));
+    }
+
+    #[test]
+    fn custom_images_dir_kept_on_save() {
+        let headless = HeadlessGl::new().unwrap();
+        let gl = &headless.gl;
+        let dir = TempDir::new().unwrap();
+        let images_path = dir.path().join("sprites");
+        std::fs::create_dir(&images_path).unwrap();
+        Bitmap::empty(32, 32)
+            .save(&images_path.join("hat.png"))
+            .unwrap();
+
+        let mut wearable = WearableData::default();
+        wearable.base.local_image_path = Some("sprites/hat.png".into());
+        let mut data = HatData::new("Sprites".to_string());
+        data.images_dir = Some("sprites".to_string());
+        data.elements.push(HatElementData::Wearable(wearable));
+        std::fs::write(
+            dir.path().join("data.json"),
+            serde_json::to_string(&data).unwrap(),
+        )
+        .unwrap();
+
+        let hat = Hat::load(dir.path(), gl).unwrap();
+        assert_eq!(hat.images_dir_name(), "sprites");
+        assert_eq!(hat.elements().count(), 1);
+        assert_eq!(
+            hat.gen_hat_data(HatSaveType::Folder).images_dir.as_deref(),
+            Some("sprites")
+        );
+        assert_eq!(hat.gen_hat_data(HatSaveType::File).images_dir, None);
+    }
+
+    #[test]
+    fn images_dir_outside_hat_rejected() {
+        let headless = HeadlessGl::new().unwrap();
+        let gl = &headless.gl;
+        let dir = TempDir::new().unwrap();
+        let hat_path = dir.path().join("hat");
+        std::fs::create_dir_all(hat_path.join("sprites")).unwrap();
+
+        for images_dir in ["..", "../hat", "sprites/..", "/tmp", "", "."] {
+            let mut data = HatData::new("Outside".to_string());
+            data.images_dir = Some(images_dir.to_string());
+            std::fs::write(
+                hat_path.join("data.json"),
+                serde_json::to_string(&data).unwrap(),
+            )
+            .unwrap();
+            assert!(
+                matches!(Hat::load(&hat_path, gl), Err(HatError::InvalidImagesDir(_))),
+                "{:?} should be rejected",
+                images_dir
+            );
+        }
+        assert!(is_folder_name("sprites"));
+    }
+
+    #[test]
+    fn loaded_name_precedence() {
+        let path = Path::new("hats/folder_name");
+        let mut data = HatData::new("stored name".to_string());
+        assert_eq!(Hat::loaded_name(path, &data), "folder_name");
+
+        data.name_set_by_user = true;
+        assert_eq!(Hat::loaded_name(path, &data), "stored name");
+    }
+
+    #[test]
+    fn summary_counts() {
+        let summary: HatSummary = [
+            (HatType::Wearable, 4),
+            (HatType::FlyingPet, 2),
+            (HatType::FlyingPet, 1),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(summary.count(HatType::FlyingPet), 2);
+        assert_eq!(summary.pets, 2);
+        assert_eq!(summary.total_frames, 7);
+        assert!(summary.has_wearable());
+        assert!(!summary.has_wings());
+    }
 }
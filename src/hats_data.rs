@@ -2,15 +2,23 @@ use std::path::{Path, PathBuf};
 
 use bevy_math::IVec2;
 use num_derive::FromPrimitive;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use crate::{animations::Animation, ui_text::Translatable};
+use crate::{
+    animations::{Animation, avalible_animations},
+    ui_text::Translatable,
+};
 
 pub const HPP_EXTENSION: &str = "hatspp";
 pub const DOT_HPP_EXTENSION: &str = ".hatspp";
 pub const DEFAULT_PET_SPEED: i32 = 10;
 pub const DEFAULT_PET_DISTANCE: i32 = 10;
 pub const MAX_PETS: usize = 5;
+/// Where a hat keeps its images unless its data.json says otherwise.
+pub const DEFAULT_IMAGES_DIR: &str = "images";
+/// Where scripts made from the template go.
+pub const SCRIPTS_DIR: &str = "src";
 // pub const DEFAULT_WINGS_IDLE_FRAME: i32 = 0;
 // pub const DEFAULT_AUTO_SPEED: i32 = 4;
 pub const MAX_EXTRA_HAT_SIZE: IVec2 = IVec2::new(97, 56);
@@ -18,17 +26,39 @@ pub const MIN_FRAME_SIZE: i32 = 32;
 pub const MAX_FRAME_SIZE: i32 = 64;
 
 #[derive(
-    Copy, Clone, Debug, Default, PartialEq, Eq, Hash, FromPrimitive, Serialize, Deserialize, strum::EnumIter
+    Copy, Clone, Debug, Default, PartialEq, Eq, Hash, FromPrimitive, Serialize, Deserialize, JsonSchema, strum::EnumIter
 )]
 //TODO: add preview back
+/// The discriminants are the indices hats are stored with, so existing variants must keep
+/// their values; new variants get the next free one.
+#[repr(u8)]
 pub enum HatType {
     #[default]
-    Wearable,
-    Wings,
-    Extra,
-    FlyingPet,
-    WalkingPet,
-    Room,
+    Wearable = 0,
+    Wings = 1,
+    Extra = 2,
+    FlyingPet = 3,
+    WalkingPet = 4,
+    Room = 5,
+}
+
+impl HatType {
+    pub fn from_index(index: u32) -> Option<Self> {
+        num_traits::FromPrimitive::from_u32(index)
+    }
+
+    pub fn to_index(self) -> u32 {
+        self as u32
+    }
+
+    /// A hat can have at most one element of a unique type.
+    pub fn is_unique(&self) -> bool {
+        matches!(self, HatType::Wearable | HatType::Wings)
+    }
+
+    pub fn is_pet(&self) -> bool {
+        matches!(self, HatType::FlyingPet | HatType::WalkingPet)
+    }
 }
 
 impl Translatable for HatType {
@@ -52,15 +82,50 @@ pub enum LinkFrameState {
     Inverted,
 }
 
-#[derive(Debug, Default, Serialize, Deserialize, Clone)]
+#[derive(Debug, Default, Serialize, Deserialize, JsonSchema, Clone)]
 pub struct HatBaseData {
     pub hat_type: HatType,
+    #[schemars(with = "[i32; 2]")]
     pub frame_size: IVec2,
+    #[serde(default)]
     pub local_image_path: Option<PathBuf>,
+    #[serde(default)]
     pub local_script_path: Option<PathBuf>,
+    /// An optional second image over the same frames, e.g. a normal or emissive map. Only
+    /// newer game builds read it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub local_secondary_image_path: Option<PathBuf>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+impl HatBaseData {
+    /// Smallest and largest frame size the game handles for this kind of element. Extra
+    /// hats are a single frame that can be larger.
+    pub fn frame_size_range(&self) -> (IVec2, IVec2) {
+        match self.hat_type {
+            HatType::Extra => (IVec2::ONE, MAX_EXTRA_HAT_SIZE),
+            _ => (IVec2::splat(MIN_FRAME_SIZE), IVec2::splat(MAX_FRAME_SIZE)),
+        }
+    }
+
+    /// Clamps `frame_size` into [`HatBaseData::frame_size_range`], which also keeps it from
+    /// being zero. Returns a warning if it had to change.
+    pub fn clamp_frame_size(&mut self) -> Option<String> {
+        let (min, max) = self.frame_size_range();
+        let clamped = self.frame_size.clamp(min, max);
+        if clamped == self.frame_size {
+            return None;
+        }
+        let warning = format!(
+            "{:?} frame size {}x{} is out of range, clamped to {}x{}",
+            self.hat_type, self.frame_size.x, self.frame_size.y, clamped.x, clamped.y
+        );
+        self.frame_size = clamped;
+        Some(warning)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
+#[serde(default)]
 pub struct PetBaseData {
     pub distance: i32,
     pub flipped: bool,
@@ -74,7 +139,8 @@ impl Default for PetBaseData {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
+#[serde(default)]
 pub struct WearableData {
     pub base: HatBaseData,
     pub strapped_on: bool,
@@ -89,6 +155,7 @@ impl Default for WearableData {
                 frame_size: IVec2::splat(MIN_FRAME_SIZE),
                 local_image_path: None,
                 local_script_path: None,
+                local_secondary_image_path: None,
             },
             strapped_on: Default::default(),
             animations: Default::default(),
@@ -96,12 +163,18 @@ impl Default for WearableData {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
+#[serde(default)]
 pub struct WingsData {
+    #[schemars(with = "[i32; 2]")]
     pub general_offset: IVec2,
+    #[schemars(with = "[i32; 2]")]
     pub crouch_offset: IVec2,
+    #[schemars(with = "[i32; 2]")]
     pub ragdoll_offset: IVec2,
+    #[schemars(with = "[i32; 2]")]
     pub slide_offset: IVec2,
+    #[schemars(with = "[i32; 2]")]
     pub net_offset: IVec2,
     pub glide_frame: i32,
     pub idle_frame: i32,
@@ -111,6 +184,71 @@ pub struct WingsData {
     pub animations: Vec<Animation>,
 }
 
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, strum::EnumIter)]
+pub enum WingsOffset {
+    #[default]
+    General,
+    Crouch,
+    Ragdoll,
+    Slide,
+    Net,
+}
+
+impl Translatable for WingsOffset {
+    fn translate_key(&self) -> &str {
+        match self {
+            WingsOffset::General => "77",
+            WingsOffset::Crouch => "78",
+            WingsOffset::Ragdoll => "79",
+            WingsOffset::Slide => "80",
+            WingsOffset::Net => "81",
+        }
+    }
+}
+
+impl WingsData {
+    pub fn offset(&self, offset: WingsOffset) -> IVec2 {
+        match offset {
+            WingsOffset::General => self.general_offset,
+            WingsOffset::Crouch => self.crouch_offset,
+            WingsOffset::Ragdoll => self.ragdoll_offset,
+            WingsOffset::Slide => self.slide_offset,
+            WingsOffset::Net => self.net_offset,
+        }
+    }
+
+    pub fn offset_mut(&mut self, offset: WingsOffset) -> &mut IVec2 {
+        match offset {
+            WingsOffset::General => &mut self.general_offset,
+            WingsOffset::Crouch => &mut self.crouch_offset,
+            WingsOffset::Ragdoll => &mut self.ragdoll_offset,
+            WingsOffset::Slide => &mut self.slide_offset,
+            WingsOffset::Net => &mut self.net_offset,
+        }
+    }
+
+    /// Clamps `glide_frame` and `idle_frame` into `0..frames_amount`, since the game crashes
+    /// on frames past the end. Returns a warning for every value that had to change.
+    pub fn clamp_frames(&mut self, frames_amount: u32) -> Vec<String> {
+        let last_frame = frames_amount.saturating_sub(1) as i32;
+        let mut warnings = vec![];
+        for (name, frame) in [
+            ("glide frame", &mut self.glide_frame),
+            ("idle frame", &mut self.idle_frame),
+        ] {
+            let clamped = (*frame).clamp(0, last_frame);
+            if clamped != *frame {
+                warnings.push(format!(
+                    "wings {} {} is out of range, clamped to {}",
+                    name, frame, clamped
+                ));
+                *frame = clamped;
+            }
+        }
+        warnings
+    }
+}
+
 impl Default for WingsData {
     fn default() -> Self {
         Self {
@@ -128,13 +266,15 @@ impl Default for WingsData {
                 frame_size: IVec2::splat(MIN_FRAME_SIZE),
                 local_image_path: None,
                 local_script_path: None,
+                local_secondary_image_path: None,
             },
             animations: Default::default(),
         }
     }
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
+#[serde(default)]
 pub struct FlyingPetData {
     pub base: HatBaseData,
     pub pet_base: PetBaseData,
@@ -150,6 +290,7 @@ impl Default for FlyingPetData {
                 frame_size: IVec2::splat(MIN_FRAME_SIZE),
                 local_image_path: None,
                 local_script_path: None,
+                local_secondary_image_path: None,
             },
             pet_base: Default::default(),
             speed: Default::default(),
@@ -158,7 +299,8 @@ impl Default for FlyingPetData {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
+#[serde(default)]
 pub struct WalkingPetData {
     pub base: HatBaseData,
     pub pet_base: PetBaseData,
@@ -173,6 +315,7 @@ impl Default for WalkingPetData {
                 frame_size: IVec2::splat(MIN_FRAME_SIZE),
                 local_image_path: None,
                 local_script_path: None,
+                local_secondary_image_path: None,
             },
             pet_base: Default::default(),
             animations: Default::default(),
@@ -180,7 +323,8 @@ impl Default for WalkingPetData {
     }
 }
 
-#[derive(Debug, Deserialize, Serialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone)]
+#[serde(default)]
 pub struct ExtraHatData {
     pub base: HatBaseData,
 }
@@ -189,16 +333,17 @@ impl Default for ExtraHatData {
     fn default() -> Self {
         Self {
             base: HatBaseData {
-                hat_type: HatType::WalkingPet,
+                hat_type: HatType::Extra,
                 frame_size: IVec2::splat(MIN_FRAME_SIZE),
                 local_image_path: None,
                 local_script_path: None,
+                local_secondary_image_path: None,
             },
         }
     }
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
 pub enum HatElementData {
     Wearable(WearableData),
     Wings(WingsData),
@@ -208,6 +353,99 @@ pub enum HatElementData {
 }
 
 impl HatElementData {
+    /// Default data for an element of `hat_type`, `None` for types without element data.
+    pub fn default_for(hat_type: HatType) -> Option<Self> {
+        match hat_type {
+            HatType::Wearable => Some(HatElementData::Wearable(Default::default())),
+            HatType::Wings => Some(HatElementData::Wings(Default::default())),
+            HatType::Extra => Some(HatElementData::Extra(Default::default())),
+            HatType::FlyingPet => Some(HatElementData::FlyingPet(Default::default())),
+            HatType::WalkingPet => Some(HatElementData::WalkingPet(Default::default())),
+            HatType::Room => None,
+        }
+    }
+
+    pub fn animations(&self) -> Option<&Vec<Animation>> {
+        match self {
+            HatElementData::Wearable(wearable_data) => Some(&wearable_data.animations),
+            HatElementData::Wings(wings_data) => Some(&wings_data.animations),
+            HatElementData::Extra(_) => None,
+            HatElementData::FlyingPet(flying_pet_data) => Some(&flying_pet_data.animations),
+            HatElementData::WalkingPet(walking_pet_data) => Some(&walking_pet_data.animations),
+        }
+    }
+
+    pub fn animations_mut(&mut self) -> Option<&mut Vec<Animation>> {
+        match self {
+            HatElementData::Wearable(wearable_data) => Some(&mut wearable_data.animations),
+            HatElementData::Wings(wings_data) => Some(&mut wings_data.animations),
+            HatElementData::Extra(_) => None,
+            HatElementData::FlyingPet(flying_pet_data) => Some(&mut flying_pet_data.animations),
+            HatElementData::WalkingPet(walking_pet_data) => Some(&mut walking_pet_data.animations),
+        }
+    }
+
+    pub fn pet_base_mut(&mut self) -> Option<&mut PetBaseData> {
+        match self {
+            HatElementData::FlyingPet(flying_pet_data) => Some(&mut flying_pet_data.pet_base),
+            HatElementData::WalkingPet(walking_pet_data) => Some(&mut walking_pet_data.pet_base),
+            _ => None,
+        }
+    }
+
+    /// Rebuilds the data as `target`, carrying over the image, frame size, script, pet settings
+    /// and the animations `target` supports. Returns what couldn't be carried over.
+    pub fn convert(&self, target: HatType) -> Option<(Self, Vec<String>)> {
+        let mut converted = Self::default_for(target)?;
+        let mut lost = vec![];
+        {
+            let base = converted.base_mut();
+            base.frame_size = self.base().frame_size;
+            base.local_image_path = self.base().local_image_path.clone();
+            base.local_script_path = self.base().local_script_path.clone();
+        }
+
+        let supported = avalible_animations(target).unwrap_or_default();
+        match (self.animations(), converted.animations_mut()) {
+            (Some(animations), Some(converted_animations)) => {
+                for animation in animations {
+                    if supported.contains(&animation.anim_type) {
+                        converted_animations.push(animation.clone());
+                    } else {
+                        lost.push(format!("{:?} animation", animation.anim_type));
+                    }
+                }
+            }
+            (Some(animations), None) if !animations.is_empty() => {
+                lost.push(format!("{} animations", animations.len()));
+            }
+            _ => {}
+        }
+
+        let pet_base = match self {
+            HatElementData::FlyingPet(flying_pet_data) => Some(&flying_pet_data.pet_base),
+            HatElementData::WalkingPet(walking_pet_data) => Some(&walking_pet_data.pet_base),
+            _ => None,
+        };
+        match (pet_base, converted.pet_base_mut()) {
+            (Some(pet_base), Some(converted_pet_base)) => *converted_pet_base = pet_base.clone(),
+            (Some(_), None) => lost.push("pet distance and flip".to_string()),
+            _ => {}
+        }
+
+        match self {
+            HatElementData::Wearable(wearable_data) if wearable_data.strapped_on => {
+                lost.push("strapped on".to_string())
+            }
+            HatElementData::Wings(_) => lost.push("wings offsets and frames".to_string()),
+            HatElementData::FlyingPet(_) if target != HatType::FlyingPet => {
+                lost.push("flying speed".to_string())
+            }
+            _ => {}
+        }
+        Some((converted, lost))
+    }
+
     pub fn base(&self) -> &HatBaseData {
         match self {
             HatElementData::Wearable(wearable_data) => &wearable_data.base,
@@ -238,7 +476,22 @@ pub enum HatElementDataRef<'a> {
     WalkingPet(&'a WalkingPetData),
 }
 
-impl HatElementDataRef<'_> {
+impl<'a> HatElementDataRef<'a> {
+    pub fn wings(&self) -> Option<&'a WingsData> {
+        match self {
+            HatElementDataRef::Wings(wings_data) => Some(wings_data),
+            _ => None,
+        }
+    }
+
+    pub fn pet_base(&self) -> Option<&'a PetBaseData> {
+        match self {
+            HatElementDataRef::FlyingPet(flying_pet_data) => Some(&flying_pet_data.pet_base),
+            HatElementDataRef::WalkingPet(walking_pet_data) => Some(&walking_pet_data.pet_base),
+            _ => None,
+        }
+    }
+
     pub fn to_hat_element_data(&self) -> HatElementData {
         match self {
             HatElementDataRef::Wearable(wearable_data) => {
@@ -258,10 +511,31 @@ impl HatElementDataRef<'_> {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// Optional information for sharing a hat. None of it affects the hat in game.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct HatMetadata {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub author: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub struct HatData {
+    #[serde(default)]
     pub elements: Vec<HatElementData>,
+    #[serde(default)]
     pub name: String,
+    /// Without it, the name follows the hat's folder on load.
+    #[serde(default)]
+    pub name_set_by_user: bool,
+    /// Subfolder holding the images, for hats not using the standard `images` layout.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub images_dir: Option<String>,
+    #[serde(flatten)]
+    pub metadata: HatMetadata,
 }
 
 impl HatData {
@@ -269,6 +543,167 @@ impl HatData {
         Self {
             elements: Default::default(),
             name,
+            name_set_by_user: false,
+            images_dir: None,
+            metadata: HatMetadata::default(),
+        }
+    }
+
+    /// Flattens the per-frame delays of every animation, see [`Animation::flatten_delays`].
+    /// Returns a message for every animation whose timing changed.
+    pub fn flatten_frame_delays(&mut self) -> Vec<String> {
+        let mut warnings = vec![];
+        for element in &mut self.elements {
+            let hat_type = element.base().hat_type;
+            for animation in element.animations_mut().into_iter().flatten() {
+                if !animation.flatten_delays() {
+                    warnings.push(format!(
+                        "{:?} animation of {:?} had different frame delays, exported with \
+                         their average of {}",
+                        animation.anim_type, hat_type, animation.delay
+                    ));
+                }
+            }
+        }
+        warnings
+    }
+
+    /// A message for every unique element type listed more than once.
+    pub fn duplicate_unique_elements(&self) -> Vec<String> {
+        HatType::iter()
+            .filter(|hat_type| hat_type.is_unique())
+            .filter_map(|hat_type| {
+                let count = self
+                    .elements
+                    .iter()
+                    .filter(|e| e.base().hat_type == hat_type)
+                    .count();
+                (count > 1).then(|| {
+                    format!(
+                        "data.json contains {} {:?} elements; only one allowed",
+                        count, hat_type
+                    )
+                })
+            })
+            .collect()
+    }
+
+    /// JSON Schema of data.json, derived from its serde structure.
+    pub fn json_schema() -> String {
+        serde_json::to_string_pretty(&schemars::schema_for!(HatData))
+            .expect("should always succeed")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bevy_math::IVec2;
+
+    use strum::IntoEnumIterator;
+
+    use crate::animations::{AnimType, Animation, Frame};
+
+    use super::{
+        DEFAULT_PET_DISTANCE, HatData, HatElementData, HatMetadata, HatType, MAX_EXTRA_HAT_SIZE,
+        MIN_FRAME_SIZE, WearableData,
+    };
+
+    #[test]
+    fn partial_data_json() {
+        let data: HatData = serde_json::from_str(
+            r#"{
+                "elements": [
+                    {"Wearable": {"base": {"hat_type": "Wearable", "frame_size": [32, 32]}}},
+                    {"WalkingPet": {"base": {"hat_type": "WalkingPet", "frame_size": [48, 32]}}},
+                    {"Wings": {"base": {"hat_type": "Wings", "frame_size": [64, 64]}}}
+                ]
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(data.elements.len(), 3);
+        assert_eq!(data.metadata, HatMetadata::default());
+
+        let HatElementData::Wearable(wearable) = &data.elements[0] else {
+            panic!("expected wearable");
+        };
+        assert_eq!(wearable.base.hat_type, HatType::Wearable);
+        assert_eq!(wearable.base.frame_size, IVec2::new(32, 32));
+        assert!(wearable.base.local_image_path.is_none());
+        assert!(!wearable.strapped_on);
+        assert!(wearable.animations.is_empty());
+
+        let HatElementData::WalkingPet(pet) = &data.elements[1] else {
+            panic!("expected walking pet");
+        };
+        assert_eq!(pet.pet_base.distance, DEFAULT_PET_DISTANCE);
+        assert!(!pet.pet_base.flipped);
+
+        let HatElementData::Wings(wings) = &data.elements[2] else {
+            panic!("expected wings");
+        };
+        assert_eq!(wings.general_offset, IVec2::ZERO);
+    }
+
+    #[test]
+    fn hat_type_indices() {
+        assert_eq!(HatType::Wearable.to_index(), 0);
+        assert_eq!(HatType::Wings.to_index(), 1);
+        assert_eq!(HatType::Extra.to_index(), 2);
+        assert_eq!(HatType::FlyingPet.to_index(), 3);
+        assert_eq!(HatType::WalkingPet.to_index(), 4);
+        assert_eq!(HatType::Room.to_index(), 5);
+        for hat_type in HatType::iter() {
+            assert_eq!(HatType::from_index(hat_type.to_index()), Some(hat_type));
+        }
+        assert_eq!(HatType::from_index(6), None);
+    }
+    #[test]
+    fn duplicate_unique_elements() {
+        let mut data = HatData::new("Duplicates".to_string());
+        for hat_type in [
+            HatType::Wearable,
+            HatType::Wearable,
+            HatType::Wings,
+            HatType::WalkingPet,
+            HatType::WalkingPet,
+        ] {
+            data.elements
+                .push(HatElementData::default_for(hat_type).unwrap());
         }
+        assert_eq!(
+            data.duplicate_unique_elements(),
+            vec!["data.json contains 2 Wearable elements; only one allowed".to_string()]
+        );
+    }
+
+    #[test]
+    fn zero_frame_size_is_clamped() {
+        let mut wearable = HatElementData::default_for(HatType::Wearable).unwrap();
+        wearable.base_mut().frame_size = IVec2::new(0, 32);
+        assert!(wearable.base_mut().clamp_frame_size().is_some());
+        assert_eq!(wearable.base().frame_size, IVec2::splat(MIN_FRAME_SIZE));
+        assert!(wearable.base_mut().clamp_frame_size().is_none());
+
+        let mut extra = HatElementData::default_for(HatType::Extra).unwrap();
+        extra.base_mut().frame_size = IVec2::new(0, 100);
+        assert!(extra.base_mut().clamp_frame_size().is_some());
+        assert_eq!(extra.base().frame_size, IVec2::new(1, MAX_EXTRA_HAT_SIZE.y));
+    }
+
+    #[test]
+    fn flatten_frame_delays() {
+        let mut frames = vec![Frame::new(0), Frame::new(1)];
+        frames[1].delay = Some(0.3);
+        let mut data = HatData::new(String::new());
+        data.elements.push(HatElementData::Wearable(WearableData {
+            animations: vec![Animation::new(AnimType::OnDefault, 0.1, true, frames)],
+            ..Default::default()
+        }));
+
+        assert_eq!(data.flatten_frame_delays().len(), 1);
+        let animation = &data.elements[0].animations().unwrap()[0];
+        assert!((animation.delay - 0.2).abs() < f32::EPSILON);
+        assert!(animation.frames.iter().all(|frame| frame.delay.is_none()));
+        assert!(data.flatten_frame_delays().is_empty());
     }
 }
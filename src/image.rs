@@ -1,66 +1,283 @@
 use anyhow::{Result, anyhow, bail};
 use bevy_math::IVec2;
-use log::warn;
 use pixas::bitmap::Bitmap;
-use std::path::{Path, PathBuf};
+use std::{
+    collections::HashMap,
+    io::Write,
+    path::{Path, PathBuf},
+};
 
 use asefile::AsepriteFile;
+use serde::{Deserialize, Serialize};
+use strum::EnumIter;
 
-use crate::animations::{AnimType, Animation, Frame};
+use crate::{
+    animations::{AnimType, Animation, DEFAULT_ANIMATION_DELAY, Frame},
+    ui_text::Translatable,
+};
 
 #[derive(Debug)]
 pub enum Image {
     Bitmap(Bitmap),
-    Aseprite(Box<AsepriteFile>, PathBuf),
+    /// The `f32` is the delay given to the animations read from the file's tags.
+    Aseprite(Box<AsepriteFile>, PathBuf, AsepriteLayout, f32),
 }
 
-pub fn bitmap_from_ase(ase_file: &AsepriteFile) -> Bitmap {
+/// How the frames of an aseprite file are tiled into an atlas.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, EnumIter)]
+pub enum AsepriteLayout {
+    /// A roughly square grid.
+    #[default]
+    Square,
+    /// All frames side by side, the way Duck Game sprite sheets usually are.
+    SingleRow,
+    SingleColumn,
+}
+
+impl AsepriteLayout {
+    pub fn columns(&self, frames_count: u32) -> u32 {
+        match self {
+            AsepriteLayout::Square => (frames_count as f64).sqrt().ceil() as u32,
+            AsepriteLayout::SingleRow => frames_count,
+            AsepriteLayout::SingleColumn => 1,
+        }
+    }
+}
+
+impl Translatable for AsepriteLayout {
+    fn translate_key(&self) -> &str {
+        match self {
+            AsepriteLayout::Square => "171",
+            AsepriteLayout::SingleRow => "172",
+            AsepriteLayout::SingleColumn => "173",
+        }
+    }
+}
+
+/// Packs the frames of `ase_file` into an atlas, see [`tile_frames`].
+pub fn bitmap_from_ase(ase_file: &AsepriteFile, layout: AsepriteLayout) -> Bitmap {
+    let frame_size = IVec2::new(ase_file.width() as _, ase_file.height() as _);
     let frames: Vec<_> = (0..ase_file.num_frames())
-        .map(|n| ase_file.frame(n))
-        .map(|f| {
-            let bitmap = Bitmap::from_bytes(
-                ase_file.width() as _,
-                ase_file.height() as _,
-                f.image().as_ref(),
-            );
-            bitmap
+        .map(|n| {
+            Bitmap::from_bytes(
+                frame_size.x,
+                frame_size.y,
+                ase_file.frame(n).image().as_ref(),
+            )
         })
         .collect();
-    let size_scale_x = (frames.len() as f64).sqrt().ceil() as i32;
-    //we can shave off the last row, but only if it's empty
-    let size_scale_y = {
-        let max_frames = size_scale_x * size_scale_x;
-        let current_frames = frames.len() as i32;
-        let row_of_frames = size_scale_x;
-        let empty_frames = max_frames - current_frames;
-        if empty_frames >= row_of_frames {
-            size_scale_x - 1
-        } else {
-            size_scale_x
+    tile_frames(&frames, frame_size, layout)
+}
+
+/// Packs `frames` into an atlas in reading order: row by row, left to right. Only the last
+/// row can be partly empty, which is how the game reads frames back.
+pub fn tile_frames(frames: &[Bitmap], frame_size: IVec2, layout: AsepriteLayout) -> Bitmap {
+    let frames_count = frames.len() as u32;
+    let columns = layout.columns(frames_count).clamp(1, frames_count.max(1));
+    //only as many rows as needed, so there are never empty rows at the bottom
+    let rows = frames_count.div_ceil(columns).max(1);
+    let mut bitmap = Bitmap::empty(frame_size.x * columns as i32, frame_size.y * rows as i32);
+    for (i, frame) in frames.iter().enumerate() {
+        let column = i as u32 % columns;
+        let row = i as u32 / columns;
+        bitmap.draw(
+            frame,
+            column as i32 * frame_size.x,
+            row as i32 * frame_size.y,
+        );
+    }
+    bitmap
+}
+
+/// Transparent padding shared by every frame of an atlas, in pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TrimBounds {
+    pub left: i32,
+    pub top: i32,
+    pub right: i32,
+    pub bottom: i32,
+}
+
+impl TrimBounds {
+    pub fn is_empty(&self) -> bool {
+        self.left == 0 && self.top == 0 && self.right == 0 && self.bottom == 0
+    }
+
+    pub fn trimmed_size(&self, frame_size: IVec2) -> IVec2 {
+        IVec2::new(
+            frame_size.x - self.left - self.right,
+            frame_size.y - self.top - self.bottom,
+        )
+    }
+}
+
+/// The padding that can be cut off every frame without losing a visible pixel,
+/// or `None` if the atlas is fully transparent.
+pub fn trim_bounds(bitmap: &Bitmap, frame_size: IVec2) -> Option<TrimBounds> {
+    if frame_size.x <= 0 || frame_size.y <= 0 {
+        return None;
+    }
+    let bytes = bitmap.bytes();
+    //only whole frames count
+    let width = bitmap.width() / frame_size.x * frame_size.x;
+    let height = bitmap.height() / frame_size.y * frame_size.y;
+    let mut min = IVec2::splat(i32::MAX);
+    let mut max = IVec2::splat(i32::MIN);
+    for y in 0..height {
+        for x in 0..width {
+            let alpha = bytes[((y * bitmap.width() + x) * 4 + 3) as usize];
+            if alpha != 0 {
+                let local = IVec2::new(x % frame_size.x, y % frame_size.y);
+                min = min.min(local);
+                max = max.max(local);
+            }
         }
-    };
-    let mut bitmap = Bitmap::empty(
-        (ase_file.width() as i32) * size_scale_x,
-        (ase_file.height() as i32) * size_scale_y,
-    );
-    for x in 0..size_scale_x {
-        for y in 0..size_scale_y {
-            let Some(frame) = &frames.get((y * size_scale_x + x) as usize) else {
-                break;
-            };
-            bitmap.draw(
-                frame,
-                x * (ase_file.width() as i32),
-                y * (ase_file.height() as i32),
-            );
+    }
+    if min.x > max.x {
+        return None;
+    }
+    Some(TrimBounds {
+        left: min.x,
+        top: min.y,
+        right: frame_size.x - 1 - max.x,
+        bottom: frame_size.y - 1 - max.y,
+    })
+}
+
+/// Cuts `bounds` off every frame. Frames keep their place in the grid, so frame indices
+/// stay the same.
+pub fn trim_bitmap(bitmap: &Bitmap, frame_size: IVec2, bounds: TrimBounds) -> Bitmap {
+    let columns = bitmap.width() / frame_size.x;
+    let rows = bitmap.height() / frame_size.y;
+    let new_size = bounds.trimmed_size(frame_size);
+    let width = columns * new_size.x;
+    let height = rows * new_size.y;
+    let bytes = bitmap.bytes();
+    let mut trimmed = vec![0u8; (width * height * 4) as usize];
+    for y in 0..height {
+        for x in 0..width {
+            let source_x = x / new_size.x * frame_size.x + bounds.left + x % new_size.x;
+            let source_y = y / new_size.y * frame_size.y + bounds.top + y % new_size.y;
+            let source = ((source_y * bitmap.width() + source_x) * 4) as usize;
+            let target = ((y * width + x) * 4) as usize;
+            trimmed[target..target + 4].copy_from_slice(&bytes[source..source + 4]);
         }
     }
-    bitmap
+    Bitmap::from_bytes(width as _, height as _, &trimmed)
+}
+
+/// An exact RGBA color and the one it gets replaced with.
+pub type ColorSwap = ([u8; 4], [u8; 4]);
+
+/// Colors the bitmap uses, most common first. Fully transparent pixels don't count.
+pub fn distinct_colors(bitmap: &Bitmap) -> Vec<[u8; 4]> {
+    let mut counts: HashMap<[u8; 4], usize> = HashMap::new();
+    for pixel in bitmap.bytes().chunks_exact(4) {
+        if pixel[3] != 0 {
+            *counts
+                .entry([pixel[0], pixel[1], pixel[2], pixel[3]])
+                .or_default() += 1;
+        }
+    }
+    let mut colors: Vec<_> = counts.into_iter().collect();
+    colors.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    colors.into_iter().map(|(color, _)| color).collect()
+}
+
+/// Replaces every pixel matching one of the swapped colors exactly. Swaps are looked up
+/// by the original color, so two colors can trade places.
+pub fn swap_colors(bitmap: &Bitmap, swaps: &[ColorSwap]) -> Bitmap {
+    let swaps: HashMap<[u8; 4], [u8; 4]> = swaps.iter().copied().collect();
+    let mut bytes = bitmap.bytes().to_vec();
+    for pixel in bytes.chunks_exact_mut(4) {
+        if let Some(new) = swaps.get(&pixel[..]) {
+            pixel.copy_from_slice(new);
+        }
+    }
+    Bitmap::from_bytes(bitmap.width() as _, bitmap.height() as _, &bytes)
+}
+
+/// A rectangle of pixels, e.g. the part of a bitmap an edit touched.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PixelRegion {
+    pub min: IVec2,
+    pub size: IVec2,
+}
+
+/// The smallest region holding every pixel that differs between two bitmaps. `None` if
+/// they are the same, or sized differently so that only a full upload makes sense.
+pub fn changed_region(old: &Bitmap, new: &Bitmap) -> Option<PixelRegion> {
+    if (old.width(), old.height()) != (new.width(), new.height()) {
+        return None;
+    }
+    let mut min = IVec2::new(old.width(), old.height());
+    let mut max = IVec2::splat(-1);
+    let pixels = old.bytes().chunks_exact(4).zip(new.bytes().chunks_exact(4));
+    for (i, (old_pixel, new_pixel)) in pixels.enumerate() {
+        if old_pixel != new_pixel {
+            let position = IVec2::new(i as i32 % old.width(), i as i32 / old.width());
+            min = min.min(position);
+            max = max.max(position);
+        }
+    }
+    (max.x >= 0).then(|| PixelRegion {
+        min,
+        size: max - min + IVec2::ONE,
+    })
+}
+
+/// The RGBA bytes of `region`, row after row.
+pub fn region_bytes(bitmap: &Bitmap, region: PixelRegion) -> Vec<u8> {
+    let bytes = bitmap.bytes();
+    let row_len = (region.size.x * 4) as usize;
+    let mut data = Vec::with_capacity(row_len * region.size.y as usize);
+    for y in region.min.y..region.min.y + region.size.y {
+        let start = ((y * bitmap.width() + region.min.x) * 4) as usize;
+        data.extend_from_slice(&bytes[start..start + row_len]);
+    }
+    data
+}
+
+/// Encodes `frames`, RGBA bytes of `size` each shown for the given seconds, as an animated
+/// GIF. Looping GIFs repeat forever, others play once.
+pub fn write_gif(
+    writer: impl Write,
+    size: IVec2,
+    frames: &mut [(Vec<u8>, f32)],
+    looping: bool,
+) -> Result<()> {
+    let (width, height) = (size.x as u16, size.y as u16);
+    let mut encoder = gif::Encoder::new(writer, width, height, &[])?;
+    //without the looping extension, players stop after showing the frames once
+    if looping {
+        encoder.set_repeat(gif::Repeat::Infinite)?;
+    }
+    for (bytes, delay) in frames {
+        let mut frame = gif::Frame::from_rgba_speed(width, height, bytes, 10);
+        //gifs count in hundredths of a second, and players speed up zero delays
+        frame.delay = (*delay * 100.0).round().max(1.0) as u16;
+        frame.dispose = gif::DisposalMethod::Background;
+        encoder.write_frame(&frame)?;
+    }
+    Ok(())
 }
 
 pub struct AsepriteData {
     pub frame_size: IVec2,
     pub animations: Vec<Animation>,
+    /// Names of tags that aren't an animation, e.g. misspelled ones.
+    pub ignored_tags: Vec<String>,
+}
+
+/// A warning naming the tags left out of an import, if there were any.
+pub fn ignored_tags_warning(ignored_tags: &[String]) -> Option<String> {
+    (!ignored_tags.is_empty()).then(|| {
+        format!(
+            "ignored {} unknown animations: {}",
+            ignored_tags.len(),
+            ignored_tags.join(", ")
+        )
+    })
 }
 
 impl Image {
@@ -75,23 +292,42 @@ impl Image {
             "aseprite" => Ok(Image::Aseprite(
                 AsepriteFile::read_file(path)?.into(),
                 path.to_path_buf(),
+                AsepriteLayout::default(),
+                DEFAULT_ANIMATION_DELAY,
             )),
             _ => bail!("expected png or aseprite extension"),
         }
     }
 
+    /// Sets how aseprite frames get tiled. Does nothing for bitmaps.
+    pub fn with_aseprite_layout(mut self, layout: AsepriteLayout) -> Self {
+        if let Image::Aseprite(_, _, image_layout, _) = &mut self {
+            *image_layout = layout;
+        }
+        self
+    }
+
+    /// Sets the delay of animations imported from aseprite tags. Does nothing for bitmaps.
+    pub fn with_animation_delay(mut self, delay: f32) -> Self {
+        if let Image::Aseprite(_, _, _, animation_delay) = &mut self {
+            *animation_delay = delay;
+        }
+        self
+    }
+
+    /// Aseprite frames are tiled according to the image's [`AsepriteLayout`].
     pub fn to_bitmap_with_data(self) -> (Bitmap, Option<AsepriteData>) {
         match self {
             Image::Bitmap(bitmap) => (bitmap, None),
-            Image::Aseprite(ref aseprite_file, _) => {
+            Image::Aseprite(ref aseprite_file, _, layout, _) => {
                 let aseprite_data = self.aseprite_data();
-                (bitmap_from_ase(&aseprite_file), aseprite_data)
+                (bitmap_from_ase(aseprite_file, layout), aseprite_data)
             }
         }
     }
 
     pub fn aseprite_data(&self) -> Option<AsepriteData> {
-        let Image::Aseprite(ase_file, _) = self else {
+        let Image::Aseprite(ase_file, _, _, animation_delay) = self else {
             return None;
         };
         let num_tags = ase_file.num_tags();
@@ -99,30 +335,11 @@ impl Image {
             frame_size: IVec2::new(ase_file.width() as _, ase_file.height() as _),
             animations: (0..num_tags)
                 .map(|i| ase_file.tag(i))
-                .filter_map(|t| match t.name().to_string().to_lowercase().as_str() {
-                    "ondefault" => Some((t, AnimType::OnDefault)),
-                    "onpressquack" => Some((t, AnimType::OnPressQuack)),
-                    "onreleasequack" => Some((t, AnimType::OnReleaseQuack)),
-                    "onpetstop" => Some((t, AnimType::OnPetStop)),
-                    "onpetapproach" => Some((t, AnimType::OnPetApproach)),
-                    "onduckdeath" => Some((t, AnimType::OnDuckDeath)),
-                    "onduckjump" => Some((t, AnimType::OnDuckJump)),
-                    "onduckland" => Some((t, AnimType::OnDuckLand)),
-                    "onduckglide" => Some((t, AnimType::OnDuckGlide)),
-                    "onduckwalk" => Some((t, AnimType::OnDuckWalk)),
-                    "onducksneak" => Some((t, AnimType::OnDuckSneak)),
-                    "onducknetted" => Some((t, AnimType::OnDuckNetted)),
-                    "onduckspawned" => Some((t, AnimType::OnDuckSpawned)),
-                    "onhatpickedup" => Some((t, AnimType::OnHatPickedUp)),
-                    other => {
-                        warn!("encountered unknown animation: {}", other);
-                        None
-                    }
-                })
+                .filter_map(|t| Some((t, AnimType::from_tag_name(t.name())?)))
                 .map(|(t, anim_type)| {
                     Animation::new(
                         anim_type,
-                        -1.,
+                        *animation_delay,
                         false,
                         (t.from_frame()..t.to_frame())
                             .map(|f| {
@@ -132,34 +349,171 @@ impl Image {
                     )
                 })
                 .collect::<Vec<_>>(),
+            ignored_tags: self.ignored_tags(),
         })
     }
 
+    /// Tags of an aseprite file whose names don't match any [`AnimType`].
+    pub fn ignored_tags(&self) -> Vec<String> {
+        let Image::Aseprite(ase_file, ..) = self else {
+            return vec![];
+        };
+        (0..ase_file.num_tags())
+            .map(|i| ase_file.tag(i).name())
+            .filter(|name| AnimType::from_tag_name(name).is_none())
+            .map(str::to_string)
+            .collect()
+    }
+
     pub fn width(&self) -> i32 {
         match self {
             Image::Bitmap(bitmap) => bitmap.width(),
-            Image::Aseprite(aseprite_file, _) => aseprite_file.width() as _,
+            Image::Aseprite(aseprite_file, ..) => aseprite_file.width() as _,
         }
     }
 
     pub fn height(&self) -> i32 {
         match self {
             Image::Bitmap(bitmap) => bitmap.height(),
-            Image::Aseprite(aseprite_file, _) => aseprite_file.height() as _,
+            Image::Aseprite(aseprite_file, ..) => aseprite_file.height() as _,
+        }
+    }
+
+    /// How many frames the image holds. Aseprite files know their frames, bitmaps are cut
+    /// into `frame_size` pieces.
+    pub fn frames_amount(&self, frame_size: IVec2) -> u32 {
+        match self {
+            Image::Bitmap(bitmap) => {
+                let frame_size = frame_size.max(IVec2::ONE);
+                ((bitmap.width() / frame_size.x) * (bitmap.height() / frame_size.y)) as u32
+            }
+            Image::Aseprite(aseprite_file, ..) => aseprite_file.num_frames(),
         }
     }
 
     pub fn path(&self) -> Option<&Path> {
         match self {
             Image::Bitmap(bitmap) => bitmap.path(),
-            Image::Aseprite(_, path_buf) => Some(path_buf),
+            Image::Aseprite(_, path_buf, ..) => Some(path_buf),
         }
     }
 
     pub fn save(&self, path: &Path) -> Result<()> {
         match self {
             Image::Bitmap(bitmap) => bitmap.save(path),
-            Image::Aseprite(aseprite_file, _) => bitmap_from_ase(aseprite_file).save(path),
+            Image::Aseprite(aseprite_file, _, layout, _) => {
+                bitmap_from_ase(aseprite_file, *layout).save(path)
+            }
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use bevy_math::IVec2;
+    use pixas::bitmap::Bitmap;
+
+    use super::{
+        AsepriteLayout, PixelRegion, TrimBounds, changed_region, distinct_colors,
+        ignored_tags_warning, region_bytes, swap_colors, tile_frames, trim_bitmap, trim_bounds,
+    };
+
+    #[test]
+    fn changed_region_bounds_edits() {
+        let old = Bitmap::from_bytes(4, 4, &[0u8; 4 * 4 * 4]);
+        let mut bytes = vec![0u8; 4 * 4 * 4];
+        bytes[(4 + 1) * 4] = 255;
+        bytes[(2 * 4 + 2) * 4 + 3] = 255;
+        let new = Bitmap::from_bytes(4, 4, &bytes);
+
+        let region = changed_region(&old, &new).unwrap();
+        assert_eq!(
+            region,
+            PixelRegion {
+                min: IVec2::new(1, 1),
+                size: IVec2::new(2, 2)
+            }
+        );
+        let data = region_bytes(&new, region);
+        assert_eq!(data.len(), 2 * 2 * 4);
+        assert_eq!(data[0], 255);
+        assert_eq!(data[15], 255);
+        assert_eq!(changed_region(&old, &old), None);
+    }
+
+    #[test]
+    fn trim_padding_shared_by_frames() {
+        //two 4x4 frames side by side, with one visible pixel each
+        let mut bytes = vec![0u8; 8 * 4 * 4];
+        let mut set_visible = |x: usize, y: usize| bytes[(y * 8 + x) * 4 + 3] = 255;
+        set_visible(1, 1);
+        set_visible(4 + 2, 2);
+        let bitmap = Bitmap::from_bytes(8, 4, &bytes);
+
+        let bounds = trim_bounds(&bitmap, IVec2::splat(4)).unwrap();
+        assert_eq!(
+            bounds,
+            TrimBounds {
+                left: 1,
+                top: 1,
+                right: 1,
+                bottom: 1
+            }
+        );
+        let trimmed = trim_bitmap(&bitmap, IVec2::splat(4), bounds);
+        assert_eq!((trimmed.width(), trimmed.height()), (4, 2));
+        let alpha = |x: i32, y: i32| trimmed.bytes()[((y * 4 + x) * 4 + 3) as usize];
+        assert_eq!(alpha(0, 0), 255);
+        assert_eq!(alpha(2 + 1, 1), 255);
+        assert_eq!(alpha(1, 1), 0);
+
+        assert!(trim_bounds(&Bitmap::from_bytes(8, 4, &[0; 8 * 4 * 4]), IVec2::splat(4)).is_none());
+    }
+
+    #[test]
+    fn layouts_pick_column_count() {
+        assert_eq!(AsepriteLayout::Square.columns(5), 3);
+        assert_eq!(AsepriteLayout::SingleRow.columns(5), 5);
+        assert_eq!(AsepriteLayout::SingleColumn.columns(5), 1);
+    }
+
+    #[test]
+    fn frames_are_tiled_in_reading_order() {
+        for frames_count in [3u8, 5, 7] {
+            //1x1 frames, each red channel holding the frame number counting from 1
+            let frames: Vec<_> = (1..=frames_count)
+                .map(|n| Bitmap::from_bytes(1, 1, &[n, 0, 0, 255]))
+                .collect();
+            let atlas = tile_frames(&frames, IVec2::ONE, AsepriteLayout::Square);
+            let columns = AsepriteLayout::Square.columns(frames_count as u32) as i32;
+            assert_eq!(atlas.width(), columns);
+            assert_eq!(atlas.height(), (frames_count as i32).div_ceil(columns));
+
+            let cells: Vec<_> = atlas.bytes().chunks(4).map(|pixel| pixel[0]).collect();
+            let (filled, rest) = cells.split_at(frames_count as usize);
+            assert_eq!(filled, (1..=frames_count).collect::<Vec<_>>());
+            assert!(rest.iter().all(|&cell| cell == 0));
+        }
+    }
+
+    #[test]
+    fn ignored_tags_are_listed() {
+        assert_eq!(ignored_tags_warning(&[]), None);
+        assert_eq!(
+            ignored_tags_warning(&["onduckjmp".to_string(), "idle".to_string()]).unwrap(),
+            "ignored 2 unknown animations: onduckjmp, idle"
+        );
+    }
+
+    #[test]
+    fn colors_trade_places() {
+        let red = [255, 0, 0, 255];
+        let blue = [0, 0, 255, 255];
+        let clear = [0, 0, 0, 0];
+        let bitmap = Bitmap::from_bytes(4, 1, &[red, red, blue, clear].concat());
+        assert_eq!(distinct_colors(&bitmap), vec![red, blue]);
+
+        let swapped = swap_colors(&bitmap, &[(red, blue), (blue, red)]);
+        assert_eq!(swapped.bytes().to_vec(), [blue, blue, red, clear].concat());
+    }
+}
@@ -0,0 +1,175 @@
+use std::{collections::HashMap, fmt};
+
+use serde_json::Value;
+
+/// A single difference between two JSON documents. Paths look like
+/// `elements.Wings.general_offset`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum JsonChange {
+    Added {
+        path: String,
+        value: Value,
+    },
+    Removed {
+        path: String,
+        value: Value,
+    },
+    Changed {
+        path: String,
+        old: Value,
+        new: Value,
+    },
+}
+
+impl fmt::Display for JsonChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JsonChange::Added { path, value } => write!(f, "+ {}: {}", path, value),
+            JsonChange::Removed { path, value } => write!(f, "- {}: {}", path, value),
+            JsonChange::Changed { path, old, new } => write!(f, "~ {}: {} → {}", path, old, new),
+        }
+    }
+}
+
+/// Field-level differences between `old` and `new`. Array items are matched by their
+/// name where they have one (the variant of an element, the `anim_type` of an animation),
+/// so reordering alone is not reported.
+pub fn json_diff(old: &Value, new: &Value) -> Vec<JsonChange> {
+    let mut changes = vec![];
+    diff_values("", old, new, &mut changes);
+    changes
+}
+
+fn diff_values(path: &str, old: &Value, new: &Value, changes: &mut Vec<JsonChange>) {
+    match (old, new) {
+        (Value::Object(old), Value::Object(new)) => {
+            diff_maps(path, &to_keyed(old), &to_keyed(new), changes)
+        }
+        (Value::Array(old), Value::Array(new)) if has_objects(old) || has_objects(new) => {
+            diff_maps(path, &array_items(old), &array_items(new), changes)
+        }
+        _ if old != new => changes.push(JsonChange::Changed {
+            path: path.to_string(),
+            old: old.clone(),
+            new: new.clone(),
+        }),
+        _ => {}
+    }
+}
+
+fn diff_maps(
+    path: &str,
+    old: &[(String, &Value)],
+    new: &[(String, &Value)],
+    changes: &mut Vec<JsonChange>,
+) {
+    let join = |key: &str| match path {
+        "" => key.to_string(),
+        _ => format!("{}.{}", path, key),
+    };
+    let new_by_key: HashMap<_, _> = new.iter().map(|(key, value)| (key, *value)).collect();
+    for (key, old_value) in old {
+        match new_by_key.get(key) {
+            Some(new_value) => diff_values(&join(key), old_value, new_value, changes),
+            None => changes.push(JsonChange::Removed {
+                path: join(key),
+                value: (*old_value).clone(),
+            }),
+        }
+    }
+    let old_keys: Vec<_> = old.iter().map(|(key, _)| key).collect();
+    for (key, new_value) in new {
+        if !old_keys.contains(&key) {
+            changes.push(JsonChange::Added {
+                path: join(key),
+                value: (*new_value).clone(),
+            });
+        }
+    }
+}
+
+fn to_keyed(map: &serde_json::Map<String, Value>) -> Vec<(String, &Value)> {
+    map.iter()
+        .map(|(key, value)| (key.clone(), value))
+        .collect()
+}
+
+/// Arrays of plain values, like offsets, are compared as a whole.
+fn has_objects(items: &[Value]) -> bool {
+    items.iter().any(Value::is_object)
+}
+
+/// Names array items, numbering repeated names so every key is unique.
+fn array_items(items: &[Value]) -> Vec<(String, &Value)> {
+    let mut seen: HashMap<String, usize> = HashMap::new();
+    items
+        .iter()
+        .enumerate()
+        .map(|(index, item)| {
+            let (name, value) = named_item(item).unwrap_or((index.to_string(), item));
+            let count = seen.entry(name.clone()).or_default();
+            *count += 1;
+            match *count {
+                1 => (name, value),
+                count => (format!("{}#{}", name, count), value),
+            }
+        })
+        .collect()
+}
+
+/// The item's name and the value to compare under it. Enum variants like
+/// `{"Wings": {...}}` are unwrapped, so their fields aren't nested under the name twice.
+fn named_item(item: &Value) -> Option<(String, &Value)> {
+    let object = item.as_object()?;
+    if let Some(anim_type) = object.get("anim_type").and_then(Value::as_str) {
+        return Some((anim_type.to_string(), item));
+    }
+    match object.iter().collect::<Vec<_>>()[..] {
+        [(variant, value)] => Some((variant.clone(), value)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use super::{JsonChange, json_diff};
+
+    #[test]
+    fn elements_matched_by_variant() {
+        let old = json!({
+            "name": "hat",
+            "elements": [
+                {"Wearable": {"strapped_on": false}},
+                {"Wings": {"general_offset": [0, 0]}}
+            ]
+        });
+        let new = json!({
+            "name": "hat",
+            "elements": [
+                {"Wings": {"general_offset": [0, 2]}},
+                {"FlyingPet": {}}
+            ]
+        });
+
+        assert_eq!(
+            json_diff(&old, &new),
+            vec![
+                JsonChange::Removed {
+                    path: "elements.Wearable".to_string(),
+                    value: json!({"strapped_on": false}),
+                },
+                JsonChange::Changed {
+                    path: "elements.Wings.general_offset".to_string(),
+                    old: json!([0, 0]),
+                    new: json!([0, 2]),
+                },
+                JsonChange::Added {
+                    path: "elements.FlyingPet".to_string(),
+                    value: json!({}),
+                },
+            ]
+        );
+    }
+}
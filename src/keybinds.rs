@@ -0,0 +1,192 @@
+use std::collections::HashMap;
+
+use eframe::egui::{self, Key, KeyboardShortcut, Modifiers};
+use serde::{Deserialize, Serialize};
+use strum::{EnumIter, IntoEnumIterator};
+
+use crate::ui_text::{Translatable, UiText};
+
+/// Editor actions that can be triggered from the keyboard.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, EnumIter)]
+pub enum EditorAction {
+    NewHat,
+    OpenHat,
+    // Before `SaveHat`, for the same reason as `Redo`.
+    SaveHatAs,
+    SaveHat,
+    ExportHat,
+    // Before `Undo`: egui ignores an extra Shift when matching shortcuts, so `Undo` would
+    // otherwise consume the default redo shortcut as well.
+    Redo,
+    Undo,
+}
+
+impl EditorAction {
+    fn default_shortcut(&self) -> KeyboardShortcut {
+        let key = match self {
+            EditorAction::NewHat => Key::N,
+            EditorAction::OpenHat => Key::O,
+            EditorAction::SaveHatAs | EditorAction::SaveHat => Key::S,
+            EditorAction::ExportHat => Key::E,
+            EditorAction::Redo | EditorAction::Undo => Key::Z,
+        };
+        let modifiers = match self {
+            EditorAction::SaveHatAs | EditorAction::Redo => Modifiers::COMMAND | Modifiers::SHIFT,
+            _ => Modifiers::COMMAND,
+        };
+        KeyboardShortcut::new(modifiers, key)
+    }
+}
+
+impl Translatable for EditorAction {
+    fn translate_key(&self) -> &str {
+        match self {
+            EditorAction::NewHat => "New",
+            EditorAction::OpenHat => "Open",
+            EditorAction::SaveHatAs => "167",
+            EditorAction::SaveHat => "Save",
+            EditorAction::ExportHat => "Export",
+            EditorAction::Redo => "162",
+            EditorAction::Undo => "161",
+        }
+    }
+}
+
+/// Which shortcut triggers each action. Actions missing from the map are unbound.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Keybinds {
+    bindings: HashMap<EditorAction, KeyboardShortcut>,
+    /// The action waiting for a new shortcut to be pressed in the settings.
+    #[serde(skip)]
+    capturing: Option<EditorAction>,
+}
+
+impl Default for Keybinds {
+    fn default() -> Self {
+        Self {
+            bindings: EditorAction::iter()
+                .map(|action| (action, action.default_shortcut()))
+                .collect(),
+            capturing: None,
+        }
+    }
+}
+
+impl Keybinds {
+    pub fn shortcut(&self, action: EditorAction) -> Option<&KeyboardShortcut> {
+        self.bindings.get(&action)
+    }
+
+    pub fn bind(&mut self, action: EditorAction, shortcut: KeyboardShortcut) {
+        self.bindings.insert(action, shortcut);
+    }
+
+    /// Actions sharing a shortcut with another one. Only one of them would ever trigger.
+    pub fn conflicts(&self) -> Vec<EditorAction> {
+        EditorAction::iter()
+            .filter(|action| {
+                self.shortcut(*action).is_some_and(|shortcut| {
+                    EditorAction::iter()
+                        .filter(|other| other != action)
+                        .any(|other| self.shortcut(other) == Some(shortcut))
+                })
+            })
+            .collect()
+    }
+
+    /// Consumes the pressed shortcuts, so they don't reach the focused widget.
+    pub fn triggered(&self, ctx: &egui::Context) -> Vec<EditorAction> {
+        if self.capturing.is_some() {
+            return vec![];
+        }
+        ctx.input_mut(|input| {
+            EditorAction::iter()
+                .filter(|action| {
+                    self.shortcut(*action)
+                        .is_some_and(|shortcut| input.consume_shortcut(shortcut))
+                })
+                .collect()
+        })
+    }
+
+    /// Text for the shortcut of `action`, for menu buttons.
+    pub fn shortcut_text(&self, ctx: &egui::Context, action: EditorAction) -> String {
+        self.shortcut(action)
+            .map(|shortcut| ctx.format_shortcut(shortcut))
+            .unwrap_or_default()
+    }
+
+    pub fn ui(&mut self, ui: &mut egui::Ui, text: &UiText) {
+        self.capture(ui);
+        let conflicts = self.conflicts();
+        egui::Grid::new("keybinds").num_columns(3).show(ui, |ui| {
+            for action in EditorAction::iter() {
+                ui.label(text.get(action.translate_key()));
+                let label = if self.capturing == Some(action) {
+                    text.get("145").to_string()
+                } else {
+                    self.shortcut_text(ui.ctx(), action)
+                };
+                if ui.button(label).clicked() {
+                    self.capturing = Some(action);
+                }
+                if conflicts.contains(&action) {
+                    ui.colored_label(ui.visuals().warn_fg_color, text.get("146"));
+                }
+                ui.end_row();
+            }
+        });
+        if ui.button(text.get("147")).clicked() {
+            *self = Self::default();
+        }
+    }
+
+    /// Binds the next key pressed with its modifiers to the action being captured. Escape
+    /// cancels the capture.
+    fn capture(&mut self, ui: &egui::Ui) {
+        let Some(action) = self.capturing else {
+            return;
+        };
+        let pressed = ui.input(|input| {
+            input.events.iter().find_map(|event| match event {
+                egui::Event::Key {
+                    key,
+                    pressed: true,
+                    modifiers,
+                    ..
+                } => Some(KeyboardShortcut::new(*modifiers, *key)),
+                _ => None,
+            })
+        });
+        let Some(shortcut) = pressed else {
+            return;
+        };
+        if shortcut.logical_key != Key::Escape {
+            self.bind(action, shortcut);
+        }
+        self.capturing = None;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use eframe::egui::{Key, KeyboardShortcut, Modifiers};
+
+    use super::{EditorAction, Keybinds};
+
+    #[test]
+    fn conflicting_bindings_are_found() {
+        let mut keybinds = Keybinds::default();
+        assert!(keybinds.conflicts().is_empty());
+
+        keybinds.bind(
+            EditorAction::ExportHat,
+            KeyboardShortcut::new(Modifiers::COMMAND, Key::S),
+        );
+        assert_eq!(
+            keybinds.conflicts(),
+            vec![EditorAction::SaveHat, EditorAction::ExportHat]
+        );
+    }
+}
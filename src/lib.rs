@@ -6,12 +6,24 @@ pub mod animation_window;
 pub mod animations;
 pub mod console;
 pub mod editor_app;
+pub mod file_dialogs;
 pub mod file_watcher;
 pub mod files_watcher;
+pub mod hat_templates;
 pub mod hats;
 pub mod hats_data;
 pub mod image;
+pub mod json_diff;
+pub mod keybinds;
+pub mod preview;
+pub mod project;
+pub mod recent_hats;
+pub mod settings;
 pub mod shader;
 pub mod tabs;
+#[cfg(test)]
+pub(crate) mod test_utils;
 pub mod texture;
+pub mod timeline;
 pub mod ui_text;
+pub mod undo;
@@ -1,12 +1,17 @@
+use std::path::PathBuf;
+
 use eframe::egui;
 use hpp_editor_v2::editor_app::EditorApp;
 
 fn main() -> anyhow::Result<()> {
     color_backtrace::install();
+    let startup_hat_path = std::env::args_os().nth(1).map(PathBuf::from);
     unsafe { std::env::set_var("RUST_LOG", "hats_plus_plus_editor=info,egui_glow=off,info") };
     let native_opts = eframe::NativeOptions {
         renderer: eframe::Renderer::Glow,
         viewport: egui::ViewportBuilder::default().with_inner_size((1600.0, 900.0)),
+        //the editor restores its own window placement, clamped to the screen
+        persist_window: false,
         ..Default::default()
     };
     let _ = eframe::run_native(
@@ -14,7 +19,7 @@ fn main() -> anyhow::Result<()> {
         native_opts,
         Box::new(|cc| {
             egui_extras::install_image_loaders(&cc.egui_ctx);
-            Ok(Box::new(EditorApp::new(cc)))
+            Ok(Box::new(EditorApp::new(cc, startup_hat_path)))
         }),
     );
     Ok(())
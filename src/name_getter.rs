@@ -2,6 +2,28 @@ use eframe::egui;
 
 use crate::{hats::Hat, ui_text::UiText};
 
+/// Characters that aren't allowed in file names on at least one platform.
+const INVALID_FILE_NAME_CHARS: &[char] = &['/', '\\', ':', '*', '?', '"', '<', '>', '|'];
+
+/// Checks a name before it can be confirmed. The error is looked up in the translations,
+/// so it can be a translation key.
+pub type NameValidator = Box<dyn FnMut(&str) -> Result<(), String>>;
+
+pub fn validate_hat_name(name: &str) -> Result<(), String> {
+    if name.trim().is_empty() {
+        return Err("176".to_string());
+    }
+    Ok(())
+}
+
+pub fn validate_file_name(name: &str) -> Result<(), String> {
+    validate_hat_name(name)?;
+    if name.contains(INVALID_FILE_NAME_CHARS) || name.chars().any(char::is_control) {
+        return Err("177".to_string());
+    }
+    Ok(())
+}
+
 #[derive(Debug)]
 pub struct NameGetter<T> {
     state: State<T>,
@@ -23,16 +45,36 @@ pub enum NameGetterResult<T> {
 
 type State<T> = NameGetterState<T>;
 
-#[derive(Debug, Clone)]
 enum NameGetterState<T> {
     Closed,
     Opened {
         message: String,
         buffer: String,
         data: T,
+        validator: Option<NameValidator>,
     },
 }
 
+impl<T: std::fmt::Debug> std::fmt::Debug for NameGetterState<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Closed => write!(f, "Closed"),
+            Self::Opened {
+                message,
+                buffer,
+                data,
+                validator,
+            } => f
+                .debug_struct("Opened")
+                .field("message", message)
+                .field("buffer", buffer)
+                .field("data", data)
+                .field("validated", &validator.is_some())
+                .finish(),
+        }
+    }
+}
+
 impl<T> Default for NameGetterState<T> {
     fn default() -> Self {
         Self::Closed
@@ -44,12 +86,14 @@ impl<T> NameGetter<T> {
         Self::default()
     }
 
-    pub fn open(&mut self, message: String, data: T) {
+    /// Without a `validator` any name can be confirmed.
+    pub fn open(&mut self, message: String, data: T, validator: Option<NameValidator>) {
         if matches!(self.state, State::Closed) {
             self.state = State::Opened {
                 message,
                 buffer: String::new(),
                 data,
+                validator,
             };
         }
     }
@@ -63,15 +107,28 @@ impl<T> NameGetter<T> {
         let mut result = None;
         modal.show(|ui| {
             let State::Opened {
-                message, buffer, ..
+                message,
+                buffer,
+                validator,
+                ..
             } = &mut self.state
             else {
                 unreachable!()
             };
             modal.title(ui, message);
-            ui.vertical_centered(|ui| {
-                ui.add(egui::TextEdit::singleline(buffer));
-            });
+            // After the text edit, so the name that gets confirmed is the one just checked.
+            let error = ui
+                .vertical_centered(|ui| {
+                    ui.add(egui::TextEdit::singleline(buffer));
+                    let error = validator
+                        .as_mut()
+                        .and_then(|validator| validator(buffer).err());
+                    if let Some(error) = &error {
+                        ui.colored_label(ui.visuals().error_fg_color, text.get(error));
+                    }
+                    error
+                })
+                .inner;
 
             modal.buttons(ui, |ui| {
                 if modal.button(ui, text.get("15")).clicked() {
@@ -79,7 +136,11 @@ impl<T> NameGetter<T> {
                     result = Some(NameGetterResult::Closed);
                     return;
                 }
-                if modal.button(ui, text.get("16")).clicked() {
+                let confirmed = ui
+                    .add_enabled_ui(error.is_none(), |ui| modal.button(ui, text.get("16")))
+                    .inner
+                    .clicked();
+                if confirmed {
                     let state = std::mem::replace(&mut self.state, NameGetterState::Closed);
                     let State::Opened { buffer, data, .. } = state else {
                         unreachable!()
@@ -92,3 +153,18 @@ impl<T> NameGetter<T> {
         result
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{validate_file_name, validate_hat_name};
+
+    #[test]
+    fn names_are_validated() {
+        assert!(validate_hat_name("  ").is_err());
+        assert!(validate_hat_name("Cool hat?").is_ok());
+        assert!(validate_file_name("quack_script").is_ok());
+        assert!(validate_file_name("").is_err());
+        assert!(validate_file_name("../quack").is_err());
+        assert!(validate_file_name("what?").is_err());
+    }
+}
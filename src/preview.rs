@@ -0,0 +1,659 @@
+use bevy_math::IVec2;
+use eframe::egui;
+
+use strum::IntoEnumIterator;
+
+use crate::{
+    animations::{AnimType, Animation, AnimationPlayer},
+    hats::{Hat, HatElement, HatElementId, IsPet, WingsHat},
+    hats_data::{MIN_FRAME_SIZE, WingsOffset},
+    timeline,
+    ui_text::{Translatable, UiText},
+};
+
+const CHECKER_SIZE: f32 = 8.0;
+const PREVIEW_HEIGHT: f32 = 256.0;
+const MIN_PLAYBACK_SPEED: f32 = 0.1;
+const MAX_PLAYBACK_SPEED: f32 = 4.0;
+const MAX_ZOOM: f32 = 16.0;
+/// How much of the scene stays inside the preview when panning, in points.
+const MIN_VISIBLE: f32 = 32.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PreviewBackground {
+    #[default]
+    Checkerboard,
+    Solid,
+    Duck,
+}
+
+impl Translatable for PreviewBackground {
+    fn translate_key(&self) -> &str {
+        match self {
+            PreviewBackground::Checkerboard => "59",
+            PreviewBackground::Solid => "60",
+            PreviewBackground::Duck => "61",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PreviewState {
+    pub background: PreviewBackground,
+    pub solid_color: egui::Color32,
+    pub flipped: bool,
+    pub wings_offset: WingsOffset,
+    /// Shows the whole hat on a duck instead of only the selected element.
+    pub composite: bool,
+    /// When set, the selected element shows the frame under the timeline's playhead.
+    pub timeline: bool,
+    pub playhead: f32,
+    /// Moves the playhead on its own, looping over the timeline.
+    pub playing: bool,
+    /// How fast the timeline and the played animation go, as a multiple of the animations'
+    /// delays. Only affects the preview, the stored delays stay as they are.
+    pub playback_speed: f32,
+    /// The selected element's animation played on its own while the timeline is hidden.
+    pub played_animation: Option<AnimType>,
+    pub player: AnimationPlayer,
+    /// Draws the neighbouring frames of the timeline's animation faintly behind the
+    /// current one.
+    pub onion_skin: bool,
+    pub onion_frames: u32,
+    pub onion_opacity: f32,
+    /// Draws elements with their secondary image, where they have one.
+    pub show_secondary: bool,
+    /// Zoom steps on top of the zoom fitting the whole scene, changed with the scroll wheel.
+    pub zoom_steps: i32,
+    /// How far the scene is dragged away from the center of the preview, in points.
+    pub pan: egui::Vec2,
+    /// Set while the wings are dragged around: their offset when the drag started and how
+    /// far the pointer has moved since, in sprite pixels.
+    wings_drag: Option<(IVec2, egui::Vec2)>,
+}
+
+impl Default for PreviewState {
+    fn default() -> Self {
+        Self {
+            background: Default::default(),
+            solid_color: egui::Color32::from_gray(40),
+            flipped: false,
+            wings_offset: Default::default(),
+            composite: false,
+            timeline: false,
+            playhead: 0.0,
+            playing: false,
+            playback_speed: 1.0,
+            played_animation: None,
+            player: Default::default(),
+            onion_skin: false,
+            onion_frames: 1,
+            onion_opacity: 0.3,
+            show_secondary: false,
+            zoom_steps: 0,
+            pan: egui::Vec2::ZERO,
+            wings_drag: None,
+        }
+    }
+}
+
+/// `animations` are the selected element's, to pick one to play.
+pub fn draw_preview_toolbar(
+    ui: &mut egui::Ui,
+    text: &UiText,
+    state: &mut PreviewState,
+    animations: Option<&[Animation]>,
+) {
+    ui.horizontal(|ui| {
+        ui.label(text.get("58"));
+        egui::ComboBox::from_id_salt("preview_background")
+            .selected_text(text.get(state.background.translate_key()))
+            .show_ui(ui, |ui| {
+                for background in [
+                    PreviewBackground::Checkerboard,
+                    PreviewBackground::Solid,
+                    PreviewBackground::Duck,
+                ] {
+                    ui.selectable_value(
+                        &mut state.background,
+                        background,
+                        text.get(background.translate_key()),
+                    );
+                }
+            });
+        if state.background == PreviewBackground::Solid {
+            ui.color_edit_button_srgba(&mut state.solid_color);
+        }
+        ui.checkbox(&mut state.flipped, text.get("62"));
+        ui.checkbox(&mut state.composite, text.get("83"));
+        ui.checkbox(&mut state.timeline, text.get("95"));
+        ui.checkbox(&mut state.show_secondary, text.get("143"));
+        if state.timeline {
+            ui.checkbox(&mut state.playing, text.get("159"));
+            draw_playback_speed(ui, text, state);
+            ui.checkbox(&mut state.onion_skin, text.get("116"));
+        } else if let Some(animations) = animations.filter(|a| !a.is_empty()) {
+            draw_played_animation(ui, text, state, animations);
+        }
+        if state.timeline && state.onion_skin {
+            ui.label(text.get("117"));
+            ui.add(egui::DragValue::new(&mut state.onion_frames).range(1..=5));
+            ui.label(text.get("118"));
+            ui.add(egui::Slider::new(&mut state.onion_opacity, 0.05..=1.0));
+        }
+        ui.label(text.get("82"));
+        egui::ComboBox::from_id_salt("preview_wings_offset")
+            .selected_text(text.get(state.wings_offset.translate_key()))
+            .show_ui(ui, |ui| {
+                for offset in WingsOffset::iter() {
+                    ui.selectable_value(
+                        &mut state.wings_offset,
+                        offset,
+                        text.get(offset.translate_key()),
+                    );
+                }
+            });
+    });
+}
+
+fn draw_playback_speed(ui: &mut egui::Ui, text: &UiText, state: &mut PreviewState) {
+    ui.label(text.get("160"));
+    ui.add(
+        egui::Slider::new(
+            &mut state.playback_speed,
+            MIN_PLAYBACK_SPEED..=MAX_PLAYBACK_SPEED,
+        )
+        .logarithmic(true)
+        .suffix("×"),
+    );
+    if ui
+        .add_enabled(state.playback_speed != 1.0, egui::Button::new("1×"))
+        .clicked()
+    {
+        state.playback_speed = 1.0;
+    }
+}
+
+/// Picks which animation plays, starting it over whenever the pick changes.
+fn draw_played_animation(
+    ui: &mut egui::Ui,
+    text: &UiText,
+    state: &mut PreviewState,
+    animations: &[Animation],
+) {
+    let played = state.played_animation;
+    ui.label(text.get("165"));
+    egui::ComboBox::from_id_salt("preview_played_animation")
+        .selected_text(played.as_ref().map_or(text.get("166"), |anim_type| {
+            text.get(anim_type.translate_key())
+        }))
+        .show_ui(ui, |ui| {
+            ui.selectable_value(&mut state.played_animation, None, text.get("166"));
+            for animation in animations {
+                ui.selectable_value(
+                    &mut state.played_animation,
+                    Some(animation.anim_type),
+                    text.get(animation.anim_type.translate_key()),
+                );
+            }
+        });
+    if state.played_animation != played {
+        state.player.restart();
+    }
+    if state.played_animation.is_some() {
+        draw_playback_speed(ui, text, state);
+    }
+}
+
+/// Where an element ends up in the preview, in sprite pixels relative to the hat origin.
+struct Placement<'a> {
+    element: &'a dyn HatElement,
+    rect: egui::Rect,
+    flipped: bool,
+    /// Drawn before the duck of the composite view, so the duck covers it.
+    behind_duck: bool,
+}
+
+fn place<'a>(element: &'a dyn HatElement, center: egui::Vec2, flipped: bool) -> Placement<'a> {
+    let frame_size = element.base().frame_size.as_vec2();
+    Placement {
+        element,
+        rect: egui::Rect::from_center_size(
+            center.to_pos2(),
+            egui::vec2(frame_size.x, frame_size.y),
+        ),
+        flipped,
+        behind_duck: false,
+    }
+}
+
+/// Mirrors `offset` to the other side of the duck while the preview is flipped.
+fn facing(offset: egui::Vec2, flipped: bool) -> egui::Vec2 {
+    if flipped {
+        egui::vec2(-offset.x, offset.y)
+    } else {
+        offset
+    }
+}
+
+fn placements<'a>(
+    hat: &'a Hat,
+    selected_id: HatElementId,
+    state: &PreviewState,
+) -> Vec<Placement<'a>> {
+    let pet_flipped = |element: &dyn HatElement| {
+        element
+            .hat_element_data_ref()
+            .pet_base()
+            .is_some_and(|pet_base| pet_base.flipped)
+            != state.flipped
+    };
+    let wings_center = |element: &dyn HatElement| {
+        element
+            .hat_element_data_ref()
+            .wings()
+            .map(|wings_data| {
+                let offset = wings_data.offset(state.wings_offset);
+                facing(egui::vec2(offset.x as f32, offset.y as f32), state.flipped)
+            })
+            .unwrap_or_default()
+    };
+
+    if !state.composite {
+        return hat
+            .element(selected_id)
+            .map(|element| vec![place(element, wings_center(element), pet_flipped(element))])
+            .unwrap_or_default();
+    }
+
+    let mut placements = vec![];
+    //back to front: wings behind the duck, then the hat itself, then pets beside it
+    if let Some(wings) = hat.wings() {
+        placements.push(Placement {
+            behind_duck: true,
+            ..place(wings, wings_center(wings), state.flipped)
+        });
+    }
+    let wearable_width = hat
+        .wereable()
+        .map(|w| w.base().frame_size.x as f32)
+        .unwrap_or(MIN_FRAME_SIZE as f32);
+    if let Some(wearable) = hat.wereable() {
+        placements.push(place(wearable, egui::Vec2::ZERO, state.flipped));
+    }
+    let mut pets: Vec<_> = hat.elements().filter(|e| e.is_pet()).collect();
+    pets.sort_by_key(|e| e.id().0);
+    let mut pet_edge = -wearable_width / 2.0;
+    for pet in pets {
+        let distance = pet
+            .hat_element_data_ref()
+            .pet_base()
+            .map(|pet_base| pet_base.distance)
+            .unwrap_or_default() as f32;
+        let width = pet.base().frame_size.x as f32;
+        let center = egui::vec2(pet_edge - distance - width / 2.0, 0.0);
+        pet_edge = center.x - width / 2.0;
+        placements.push(place(pet, facing(center, state.flipped), pet_flipped(pet)));
+    }
+    placements
+}
+
+/// Draws the hat and lets the selected wings be dragged around to set their offset. Dragging
+/// anywhere else pans, scrolling zooms and double clicking fits the scene again.
+pub fn draw_preview(
+    ui: &mut egui::Ui,
+    text: &UiText,
+    hat: &mut Hat,
+    selected_id: HatElementId,
+    state: &mut PreviewState,
+) -> egui::Response {
+    let (rect, response) = ui.allocate_exact_size(
+        egui::vec2(ui.available_width(), PREVIEW_HEIGHT),
+        egui::Sense::click_and_drag(),
+    );
+    let painter = ui.painter_at(rect);
+    let placements = placements(hat, selected_id, state);
+    let origin_rect =
+        egui::Rect::from_center_size(egui::Pos2::ZERO, egui::Vec2::splat(MIN_FRAME_SIZE as f32));
+    let bounds = placements.iter().fold(origin_rect, |bounds, placement| {
+        bounds.union(placement.rect)
+    });
+    let fit_zoom = (rect.width() / bounds.width())
+        .min(rect.height() / bounds.height())
+        .floor()
+        .max(1.0);
+    zoom_view(ui, &response, state, fit_zoom);
+    let zoom = view_zoom(fit_zoom, state.zoom_steps);
+    state.pan = clamp_pan(state.pan, rect, bounds.size() * zoom);
+    //maps sprite pixels into the preview, keeping the whole scene centered
+    let center = rect.center() + state.pan;
+    let to_screen = |sprite_rect: egui::Rect| {
+        egui::Rect::from_min_size(
+            center + (sprite_rect.min - bounds.center()) * zoom,
+            sprite_rect.size() * zoom,
+        )
+    };
+
+    match state.background {
+        PreviewBackground::Checkerboard => draw_checkerboard(&painter, rect),
+        PreviewBackground::Solid => {
+            painter.rect_filled(rect, 0.0, state.solid_color);
+        }
+        PreviewBackground::Duck => {
+            draw_checkerboard(&painter, rect);
+            //the composite view draws its own duck in between the elements
+            if !state.composite {
+                draw_duck(&painter, to_screen(origin_rect), state.flipped);
+            }
+        }
+    }
+
+    if let Some(wings_data) = hat
+        .element(selected_id)
+        .and_then(|e| e.hat_element_data_ref().wings())
+    {
+        let offset = wings_data.offset(state.wings_offset);
+        let wings_size = wings_data.base.frame_size.as_vec2();
+        draw_origin(
+            &painter,
+            to_screen(egui::Rect::from_center_size(
+                egui::Pos2::ZERO,
+                egui::vec2(wings_size.x, wings_size.y),
+            )),
+        );
+        painter.text(
+            rect.left_top() + egui::vec2(4.0, 4.0),
+            egui::Align2::LEFT_TOP,
+            format!(
+                "{}: {}, {}",
+                text.get(state.wings_offset.translate_key()),
+                offset.x,
+                offset.y
+            ),
+            egui::FontId::monospace(12.0),
+            egui::Color32::WHITE,
+        );
+    }
+
+    let selected_position = hat
+        .element(selected_id)
+        .filter(|_| state.timeline)
+        .and_then(|e| e.animations())
+        .and_then(|animations| {
+            timeline::frame_position(animations, state.playhead)
+                .map(|(index, frame_index)| (&animations[index], frame_index))
+        });
+    let selected_frame = if state.timeline {
+        selected_position.map(|(animation, frame_index)| animation.frames[frame_index].value)
+    } else {
+        state
+            .played_animation
+            .and_then(|_| state.player.current_frame())
+    };
+    let ghost_frames = selected_position
+        .filter(|_| state.onion_skin)
+        .map(|(animation, frame_index)| onion_frames(animation, frame_index, state))
+        .unwrap_or_default();
+    let wings_rect = placements
+        .iter()
+        .find(|placement| placement.element.id() == selected_id)
+        .filter(|placement| placement.element.hat_element_data_ref().wings().is_some())
+        .map(|placement| to_screen(placement.rect));
+    let mut duck_drawn = !state.composite;
+    for placement in &placements {
+        if !duck_drawn && !placement.behind_duck {
+            draw_duck(&painter, to_screen(origin_rect), state.flipped);
+            duck_drawn = true;
+        }
+        let selected = placement.element.id() == selected_id;
+        if selected {
+            for &(frame, opacity) in &ghost_frames {
+                paint_element(
+                    &painter,
+                    placement.element,
+                    to_screen(placement.rect),
+                    placement.flipped,
+                    state.show_secondary,
+                    frame,
+                    egui::Color32::WHITE.gamma_multiply(opacity),
+                );
+            }
+        }
+        let frame = if selected {
+            selected_frame.unwrap_or(0)
+        } else {
+            0
+        };
+        paint_element(
+            &painter,
+            placement.element,
+            to_screen(placement.rect),
+            placement.flipped,
+            state.show_secondary,
+            frame,
+            egui::Color32::WHITE,
+        );
+    }
+    if !duck_drawn {
+        draw_duck(&painter, to_screen(origin_rect), state.flipped);
+    }
+    let dragging_wings = drag_wings(&response, wings_rect, zoom, hat, selected_id, state);
+    if !dragging_wings && response.dragged() {
+        state.pan += response.drag_delta();
+    }
+    response
+}
+
+/// The zoom `steps` away from `fit_zoom`. Whole numbers only, so the pixels stay square.
+fn view_zoom(fit_zoom: f32, steps: i32) -> f32 {
+    (fit_zoom + steps as f32).clamp(1.0, MAX_ZOOM.max(fit_zoom))
+}
+
+/// Zooms in or out a step per scroll wheel notch over the preview, keeping what's in the
+/// middle of it in place. Double clicking resets the zoom and the pan.
+fn zoom_view(ui: &egui::Ui, response: &egui::Response, state: &mut PreviewState, fit_zoom: f32) {
+    if response.double_clicked() {
+        state.zoom_steps = 0;
+        state.pan = egui::Vec2::ZERO;
+        return;
+    }
+    if !response.hovered() {
+        return;
+    }
+    //taken from the scroll areas around the preview, so they don't scroll along
+    let scroll = ui.input_mut(|i| {
+        i.smooth_scroll_delta = egui::Vec2::ZERO;
+        i.raw_scroll_delta.y
+    });
+    if scroll == 0.0 {
+        return;
+    }
+    let old_zoom = view_zoom(fit_zoom, state.zoom_steps);
+    let new_zoom = view_zoom(fit_zoom, state.zoom_steps + scroll.signum() as i32);
+    state.zoom_steps = (new_zoom - fit_zoom) as i32;
+    state.pan *= new_zoom / old_zoom;
+}
+
+/// Keeps at least `MIN_VISIBLE` points of a scene of `scene_size` inside `rect`.
+fn clamp_pan(pan: egui::Vec2, rect: egui::Rect, scene_size: egui::Vec2) -> egui::Vec2 {
+    let limit =
+        ((rect.size() + scene_size) / 2.0 - egui::Vec2::splat(MIN_VISIBLE)).max(egui::Vec2::ZERO);
+    pan.clamp(-limit, limit)
+}
+
+/// Moves the selected wings' offset along with a drag that started on them, snapping to
+/// whole sprite pixels. Holding shift keeps the offset on the axis moved along the most.
+/// Returns whether the wings are being dragged.
+fn drag_wings(
+    response: &egui::Response,
+    wings_rect: Option<egui::Rect>,
+    zoom: f32,
+    hat: &mut Hat,
+    selected_id: HatElementId,
+    state: &mut PreviewState,
+) -> bool {
+    let Some(wings) = hat
+        .element_mut(selected_id)
+        .and_then(|element| element.downcast_mut::<WingsHat>())
+    else {
+        return false;
+    };
+    let offset = wings.data_mut().offset_mut(state.wings_offset);
+    let (press_origin, shift) = response
+        .ctx
+        .input(|i| (i.pointer.press_origin(), i.modifiers.shift));
+    if response.drag_started()
+        && let Some(origin) = press_origin
+        && wings_rect.is_some_and(|rect| rect.contains(origin))
+    {
+        state.wings_drag = Some((*offset, egui::Vec2::ZERO));
+    }
+    let Some((start, moved)) = &mut state.wings_drag else {
+        return false;
+    };
+    *moved += facing(response.drag_delta(), state.flipped) / zoom;
+    let mut delta = moved.round();
+    if shift {
+        if delta.x.abs() >= delta.y.abs() {
+            delta.y = 0.0;
+        } else {
+            delta.x = 0.0;
+        }
+    }
+    *offset = *start + IVec2::new(delta.x as i32, delta.y as i32);
+    if response.drag_stopped() {
+        state.wings_drag = None;
+    }
+    true
+}
+
+/// Frames around `frame_index` to ghost behind it, with their opacity. The farthest come
+/// first, so nearer ones end up on top.
+fn onion_frames(
+    animation: &Animation,
+    frame_index: usize,
+    state: &PreviewState,
+) -> Vec<(u32, f32)> {
+    let mut frames = vec![];
+    for distance in (1..=state.onion_frames as usize).rev() {
+        let fade = 1.0 - (distance - 1) as f32 / state.onion_frames as f32;
+        let neighbours = [
+            frame_index.checked_sub(distance),
+            Some(frame_index + distance),
+        ];
+        for index in neighbours.into_iter().flatten() {
+            if let Some(frame) = animation.frames.get(index) {
+                frames.push((frame.value, state.onion_opacity * fade));
+            }
+        }
+    }
+    frames
+}
+
+/// The part of the element's texture that holds `frame`, in uv coordinates.
+pub fn frame_uv(element: &dyn HatElement, frame: u32) -> Option<egui::Rect> {
+    let texture = element.texture();
+    let texture_size = egui::vec2(texture.width() as f32, texture.height() as f32);
+    element.frame_rect(frame).map(|rect| {
+        egui::Rect::from_min_max(
+            (rect.min.to_vec2() / texture_size).to_pos2(),
+            (rect.max.to_vec2() / texture_size).to_pos2(),
+        )
+    })
+}
+
+/// The element's first frame at twice its size, e.g. to tell similar elements apart.
+pub fn draw_thumbnail(ui: &mut egui::Ui, element: &dyn HatElement) {
+    let (Some(texture_id), Some(uv)) = (element.texture().egui_id(), frame_uv(element, 0)) else {
+        return;
+    };
+    let frame_size = element.base().frame_size.as_vec2();
+    ui.add(egui::Image::new((texture_id, egui::vec2(frame_size.x, frame_size.y) * 2.0)).uv(uv));
+}
+
+fn paint_element(
+    painter: &egui::Painter,
+    element: &dyn HatElement,
+    screen_rect: egui::Rect,
+    flipped: bool,
+    secondary: bool,
+    frame: u32,
+    tint: egui::Color32,
+) {
+    let texture = element
+        .secondary()
+        .filter(|_| secondary)
+        .map_or(element.texture(), |secondary| &secondary.texture);
+    let (Some(texture_id), Some(mut uv)) = (texture.egui_id(), frame_uv(element, frame)) else {
+        return;
+    };
+    //mirror through the uvs so the bitmap itself stays untouched
+    if flipped {
+        std::mem::swap(&mut uv.min.x, &mut uv.max.x);
+    }
+    painter.image(texture_id, screen_rect, uv, tint);
+}
+
+/// Marks where the element would be drawn without any offset.
+fn draw_origin(painter: &egui::Painter, sprite_rect: egui::Rect) {
+    let stroke = egui::Stroke::new(1.0, egui::Color32::from_white_alpha(120));
+    painter.rect_stroke(sprite_rect, 0.0, stroke, egui::StrokeKind::Inside);
+    let center = sprite_rect.center();
+    painter.line_segment(
+        [center - egui::vec2(6.0, 0.0), center + egui::vec2(6.0, 0.0)],
+        stroke,
+    );
+    painter.line_segment(
+        [center - egui::vec2(0.0, 6.0), center + egui::vec2(0.0, 6.0)],
+        stroke,
+    );
+}
+
+fn draw_checkerboard(painter: &egui::Painter, rect: egui::Rect) {
+    let light = egui::Color32::from_gray(70);
+    let dark = egui::Color32::from_gray(50);
+    painter.rect_filled(rect, 0.0, dark);
+    let columns = (rect.width() / CHECKER_SIZE).ceil() as i32;
+    let rows = (rect.height() / CHECKER_SIZE).ceil() as i32;
+    for y in 0..rows {
+        for x in (y % 2..columns).step_by(2) {
+            let min = rect.min + egui::vec2(x as f32, y as f32) * CHECKER_SIZE;
+            painter.rect_filled(
+                egui::Rect::from_min_size(min, egui::Vec2::splat(CHECKER_SIZE)),
+                0.0,
+                light,
+            );
+        }
+    }
+}
+
+/// Rough duck silhouette so the hat can be judged against its wearer. It looks right, or
+/// left when `flipped`.
+fn draw_duck(painter: &egui::Painter, sprite_rect: egui::Rect, flipped: bool) {
+    let unit = sprite_rect.width() / 32.0;
+    let forward = if flipped { -unit } else { unit };
+    let body_color = egui::Color32::from_rgb(240, 240, 240);
+    let head_center = sprite_rect.center() + egui::vec2(0.0, 6.0 * unit);
+    painter.circle_filled(head_center, 7.0 * unit, body_color);
+    painter.rect_filled(
+        egui::Rect::from_center_size(
+            head_center + egui::vec2(0.0, 18.0 * unit),
+            egui::vec2(18.0 * unit, 24.0 * unit),
+        ),
+        6.0 * unit,
+        body_color,
+    );
+    painter.rect_filled(
+        egui::Rect::from_center_size(
+            head_center + egui::vec2(8.0 * forward, 0.5 * unit),
+            egui::vec2(6.0 * unit, 3.0 * unit),
+        ),
+        unit,
+        egui::Color32::from_rgb(255, 170, 40),
+    );
+    painter.circle_filled(
+        head_center + egui::vec2(2.0 * forward, -3.0 * unit),
+        unit,
+        egui::Color32::BLACK,
+    );
+}
@@ -0,0 +1,145 @@
+use std::{
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context, Result};
+
+use crate::file_watcher::FileWatcher;
+
+/// How often the project folder is checked for changes.
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// A hat folder found in the project.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProjectHat {
+    pub path: PathBuf,
+    /// The name from its data.json, or the folder name if it has none.
+    pub name: String,
+}
+
+/// A folder of hat folders, e.g. a hat pack. Hats added or removed on disk show up on
+/// their own.
+pub struct Project {
+    root: PathBuf,
+    hats: Vec<ProjectHat>,
+    watcher: FileWatcher,
+    last_poll: Instant,
+}
+
+impl Project {
+    pub fn open(root: impl AsRef<Path>) -> Result<Self> {
+        let mut project = Self {
+            root: root.as_ref().to_path_buf(),
+            hats: vec![],
+            watcher: FileWatcher::new(),
+            last_poll: Instant::now(),
+        };
+        project.rescan()?;
+        Ok(project)
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn name(&self) -> String {
+        self.root
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_else(|| self.root.to_string_lossy().to_string())
+    }
+
+    pub fn hats(&self) -> &[ProjectHat] {
+        &self.hats
+    }
+
+    /// Rescans the project if anything in it changed since the last poll. Returns whether
+    /// it did.
+    pub fn poll(&mut self) -> Result<bool> {
+        if self.last_poll.elapsed() < POLL_INTERVAL {
+            return Ok(false);
+        }
+        self.last_poll = Instant::now();
+        if self.watcher.update().is_empty() {
+            return Ok(false);
+        }
+        self.rescan()?;
+        Ok(true)
+    }
+
+    /// Lists the hats again and watches the root along with every folder in it, so new
+    /// folders, data.json files showing up in them and edited data.json files are noticed.
+    fn rescan(&mut self) -> Result<()> {
+        self.hats = scan_hats(&self.root)?;
+        self.watcher.unwatch_all();
+        self.watcher.watch_file(&self.root)?;
+        let entries =
+            std::fs::read_dir(&self.root).context(format!("could not read {:?}", &self.root))?;
+        for path in entries.filter_map(|entry| entry.ok().map(|entry| entry.path())) {
+            if path.is_dir() {
+                self.watcher.watch_file(&path)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Folders directly inside `root` that have a data.json, sorted by name.
+pub fn scan_hats(root: &Path) -> Result<Vec<ProjectHat>> {
+    let entries = std::fs::read_dir(root).context(format!("could not read {:?}", root))?;
+    let mut hats: Vec<_> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.join("data.json").is_file())
+        .map(|path| ProjectHat {
+            name: hat_name(&path),
+            path,
+        })
+        .collect();
+    hats.sort_by_key(|hat| hat.name.to_lowercase());
+    Ok(hats)
+}
+
+fn hat_name(path: &Path) -> String {
+    std::fs::read_to_string(path.join("data.json"))
+        .ok()
+        .and_then(|json| serde_json::from_str::<serde_json::Value>(&json).ok())
+        .and_then(|data| data.get("name")?.as_str().map(str::to_string))
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| {
+            path.file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default()
+        })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::test_utils::TempDir;
+
+    use super::scan_hats;
+
+    #[test]
+    fn only_hat_folders_are_listed() {
+        let dir = TempDir::new().unwrap();
+        for (folder, data) in [
+            ("b_hat", Some(r#"{"name": "Alpha"}"#)),
+            ("a_hat", Some("{}")),
+            ("not_a_hat", None),
+        ] {
+            let path = dir.path().join(folder);
+            std::fs::create_dir(&path).unwrap();
+            if let Some(data) = data {
+                std::fs::write(path.join("data.json"), data).unwrap();
+            }
+        }
+        std::fs::write(dir.path().join("data.json"), "{}").unwrap();
+
+        let names: Vec<_> = scan_hats(dir.path())
+            .unwrap()
+            .into_iter()
+            .map(|hat| hat.name)
+            .collect();
+        assert_eq!(names, ["a_hat", "Alpha"]);
+    }
+}
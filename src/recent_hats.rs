@@ -0,0 +1,62 @@
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+pub const RECENT_HATS_KEY: &str = "recent_hats";
+/// How many hats the recent list keeps.
+const MAX_RECENT_HATS: usize = 10;
+
+/// Hats opened or exported lately, most recent first.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct RecentHats {
+    paths: Vec<PathBuf>,
+}
+
+impl RecentHats {
+    pub fn paths(&self) -> &[PathBuf] {
+        &self.paths
+    }
+
+    /// Moves `path` to the front, dropping the oldest entry if the list is full.
+    pub fn push(&mut self, path: impl AsRef<Path>) {
+        let path = path.as_ref();
+        self.remove(path);
+        self.paths.insert(0, path.to_path_buf());
+        self.paths.truncate(MAX_RECENT_HATS);
+    }
+
+    pub fn remove(&mut self, path: &Path) {
+        self.paths.retain(|recent| recent != path);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+
+    use super::{MAX_RECENT_HATS, RecentHats};
+
+    #[test]
+    fn reopened_hats_move_to_the_front() {
+        let mut recent_hats = RecentHats::default();
+        for i in 0..MAX_RECENT_HATS + 2 {
+            recent_hats.push(format!("hats/{}", i));
+        }
+        recent_hats.push("hats/5");
+
+        let paths = recent_hats.paths();
+        assert_eq!(paths.len(), MAX_RECENT_HATS);
+        assert_eq!(paths[0], Path::new("hats/5"));
+        assert_eq!(
+            paths[1],
+            Path::new(&format!("hats/{}", MAX_RECENT_HATS + 1))
+        );
+        assert_eq!(
+            paths
+                .iter()
+                .filter(|path| *path == Path::new("hats/5"))
+                .count(),
+            1
+        );
+    }
+}
@@ -0,0 +1,206 @@
+use std::time::Duration;
+
+use eframe::egui;
+use serde::{Deserialize, Serialize};
+use strum::IntoEnumIterator;
+
+use crate::{
+    animations::DEFAULT_ANIMATION_DELAY,
+    editor_app::ToastType,
+    hats_data::MAX_PETS,
+    image::AsepriteLayout,
+    keybinds::Keybinds,
+    ui_text::{Language, Translatable, UiText},
+};
+
+pub const SETTINGS_KEY: &str = "settings";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ToastAnchor {
+    #[default]
+    TopRight,
+    TopLeft,
+    BottomRight,
+    BottomLeft,
+}
+
+impl ToastAnchor {
+    pub fn anchor(&self) -> egui_notify::Anchor {
+        match self {
+            ToastAnchor::TopRight => egui_notify::Anchor::TopRight,
+            ToastAnchor::TopLeft => egui_notify::Anchor::TopLeft,
+            ToastAnchor::BottomRight => egui_notify::Anchor::BottomRight,
+            ToastAnchor::BottomLeft => egui_notify::Anchor::BottomLeft,
+        }
+    }
+}
+
+impl Translatable for ToastAnchor {
+    fn translate_key(&self) -> &str {
+        match self {
+            ToastAnchor::TopRight => "64",
+            ToastAnchor::TopLeft => "65",
+            ToastAnchor::BottomRight => "66",
+            ToastAnchor::BottomLeft => "67",
+        }
+    }
+}
+
+/// User preferences, persisted through eframe storage.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub language: Language,
+    pub toast_anchor: ToastAnchor,
+    pub success_toast_secs: f32,
+    pub info_toast_secs: f32,
+    pub warn_toast_secs: f32,
+    /// `None` keeps errors on screen until they are closed.
+    pub error_toast_secs: Option<f32>,
+    pub max_pets: usize,
+    /// `None` turns autosave off.
+    pub autosave_minutes: Option<f32>,
+    /// Exports animations with a single delay each, for game builds without per-frame delays.
+    pub flatten_frame_delays: bool,
+    /// Delay of animations added in the editor, in seconds.
+    pub default_animation_delay: f32,
+    /// How frames of imported aseprite files are tiled.
+    pub aseprite_layout: AsepriteLayout,
+    pub keybinds: Keybinds,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            language: Default::default(),
+            toast_anchor: Default::default(),
+            success_toast_secs: 2.0,
+            info_toast_secs: 3.0,
+            warn_toast_secs: 5.0,
+            error_toast_secs: None,
+            max_pets: MAX_PETS,
+            autosave_minutes: Some(5.0),
+            flatten_frame_delays: false,
+            default_animation_delay: DEFAULT_ANIMATION_DELAY,
+            aseprite_layout: Default::default(),
+            keybinds: Default::default(),
+        }
+    }
+}
+
+impl Settings {
+    pub fn load(storage: Option<&dyn eframe::Storage>) -> Self {
+        storage
+            .and_then(|storage| eframe::get_value(storage, SETTINGS_KEY))
+            .unwrap_or_default()
+    }
+
+    pub fn toast_duration(&self, toast_type: ToastType) -> Option<Duration> {
+        let secs = match toast_type {
+            ToastType::Success => Some(self.success_toast_secs),
+            ToastType::Info => Some(self.info_toast_secs),
+            ToastType::Warn => Some(self.warn_toast_secs),
+            ToastType::Error => self.error_toast_secs,
+        };
+        secs.map(Duration::from_secs_f32)
+    }
+
+    pub fn autosave_interval(&self) -> Option<Duration> {
+        self.autosave_minutes
+            .map(|minutes| Duration::from_secs_f32(minutes * 60.0))
+    }
+
+    pub fn ui(&mut self, ui: &mut egui::Ui, text: &UiText) {
+        ui.horizontal(|ui| {
+            ui.label(text.get("164"));
+            egui::ComboBox::from_id_salt("language")
+                .selected_text(self.language.native_name())
+                .show_ui(ui, |ui| {
+                    for language in Language::iter() {
+                        ui.selectable_value(&mut self.language, language, language.native_name());
+                    }
+                });
+        });
+        ui.horizontal(|ui| {
+            ui.label(text.get("74"));
+            ui.add(egui::DragValue::new(&mut self.max_pets).range(0..=100));
+        });
+        ui.checkbox(&mut self.flatten_frame_delays, text.get("133"));
+        ui.horizontal(|ui| {
+            ui.label(text.get("139"));
+            ui.add(
+                egui::DragValue::new(&mut self.default_animation_delay)
+                    .speed(0.01)
+                    .range(0.01..=10.0),
+            );
+        });
+        ui.horizontal(|ui| {
+            ui.label(text.get("170"));
+            egui::ComboBox::from_id_salt("aseprite_layout")
+                .selected_text(text.get(self.aseprite_layout.translate_key()))
+                .show_ui(ui, |ui| {
+                    for layout in AsepriteLayout::iter() {
+                        ui.selectable_value(
+                            &mut self.aseprite_layout,
+                            layout,
+                            text.get(layout.translate_key()),
+                        );
+                    }
+                });
+        });
+        ui.separator();
+        let mut autosave = self.autosave_minutes.is_some();
+        if ui.checkbox(&mut autosave, text.get("92")).changed() {
+            self.autosave_minutes = autosave.then_some(5.0);
+        }
+        if let Some(minutes) = &mut self.autosave_minutes {
+            ui.horizontal(|ui| {
+                ui.label(text.get("93"));
+                ui.add(egui::DragValue::new(minutes).speed(0.1).range(0.5..=120.0));
+            });
+        }
+        ui.separator();
+        ui.horizontal(|ui| {
+            ui.label(text.get("63"));
+            egui::ComboBox::from_id_salt("toast_anchor")
+                .selected_text(text.get(self.toast_anchor.translate_key()))
+                .show_ui(ui, |ui| {
+                    for anchor in [
+                        ToastAnchor::TopRight,
+                        ToastAnchor::TopLeft,
+                        ToastAnchor::BottomRight,
+                        ToastAnchor::BottomLeft,
+                    ] {
+                        ui.selectable_value(
+                            &mut self.toast_anchor,
+                            anchor,
+                            text.get(anchor.translate_key()),
+                        );
+                    }
+                });
+        });
+        for (key, secs) in [
+            ("68", &mut self.success_toast_secs),
+            ("69", &mut self.info_toast_secs),
+            ("70", &mut self.warn_toast_secs),
+        ] {
+            ui.horizontal(|ui| {
+                ui.label(text.get(key));
+                ui.add(egui::DragValue::new(secs).speed(0.1).range(0.5..=60.0));
+            });
+        }
+        let mut keep_errors = self.error_toast_secs.is_none();
+        if ui.checkbox(&mut keep_errors, text.get("71")).changed() {
+            self.error_toast_secs = (!keep_errors).then_some(5.0);
+        }
+        if let Some(secs) = &mut self.error_toast_secs {
+            ui.horizontal(|ui| {
+                ui.label(text.get("72"));
+                ui.add(egui::DragValue::new(secs).speed(0.1).range(0.5..=60.0));
+            });
+        }
+        ui.separator();
+        ui.label(text.get("144"));
+        self.keybinds.ui(ui, text);
+    }
+}
@@ -1,26 +1,168 @@
 use std::{
+    path::{Path, PathBuf},
     sync::mpsc::{Receiver, channel},
     thread::sleep,
     u32,
 };
 
+use anyhow::{Context, Result};
 use eframe::{
     egui::{self, SelectableLabel},
     epaint::text::layout,
     glow,
 };
-use egui_dock::{DockArea, DockState, NodeIndex, Style, SurfaceIndex, TabViewer};
+use egui_dock::{DockArea, DockState, NodeIndex, Style, SurfaceIndex, TabIndex, TabViewer};
+use log::{error, info, warn};
+use pixas::bitmap::Bitmap;
+use serde::{Deserialize, Serialize};
+use strum::IntoEnumIterator;
 
 use crate::{
+    animation_window,
+    animations::{AnimType, Animation, avalible_animations},
     console::Console,
-    hats::{Hat, HatElement, HatElementId, HatId, LoadHatElement, WearableHat, WingsHat, hat_id},
-    hats_data::HatType,
+    editor_app::ToastType,
+    file_dialogs::{FileDialogDirs, FileDialogKind},
+    hats::{Hat, HatElement, HatElementId, HatId, WingsHat, hat_id},
+    hats_data::{HatElementData, HatType, WingsData},
+    image::{
+        AsepriteLayout, ColorSwap, Image, TrimBounds, distinct_colors, swap_colors, trim_bounds,
+    },
+    preview::{self, PreviewState},
+    timeline,
     ui_text::{self, Translatable, UiText},
+    undo::UndoStack,
 };
 
 #[derive(Debug, Default, Clone)]
 pub struct HatTabState {
-    element_to_remove: Option<(String, HatElementId)>,
+    elements_to_remove: Vec<(String, HatElementId)>,
+    element_to_trim: Option<(HatElementId, TrimBounds)>,
+    pending_reimport: Option<PendingReimport>,
+    palette_swap: Option<PaletteSwap>,
+    selected_elements: Vec<HatElementId>,
+    preview: PreviewState,
+    element_filter: String,
+    external_animation_sources: Vec<ExternalAnimationSource>,
+    undo_stack: UndoStack,
+    /// The hat's path and what it resolves to, so looking tabs up by path doesn't hit the
+    /// file system every frame.
+    resolved_path: Option<(PathBuf, PathBuf)>,
+}
+
+/// Colors of an element being remapped. Nothing is written to the element until applied.
+#[derive(Debug, Clone)]
+struct PaletteSwap {
+    element_id: HatElementId,
+    swaps: Vec<ColorSwap>,
+    truncated: bool,
+    preview: Option<egui::TextureHandle>,
+}
+
+const PALETTE_SWAP_MAX_COLORS: usize = 64;
+const TRIM_MODAL_ID: &str = "trim_modal";
+const REIMPORT_MODAL_ID: &str = "reimport_modal";
+
+/// A re-imported image that doesn't fit the element's frames, waiting for the user to
+/// pick whether to keep the element's data anyway.
+#[derive(Debug, Clone)]
+struct PendingReimport {
+    element_id: HatElementId,
+    path: PathBuf,
+    conflicts: Vec<String>,
+}
+
+/// Quick actions on the selected element, offered by right-clicking the preview.
+#[derive(Debug, Clone, Copy)]
+enum PreviewAction {
+    ReplaceImage,
+    ReimportImage,
+    ExportImage,
+    Trim,
+    BakeToPng,
+    RevealSource,
+    ExportGif,
+}
+
+impl PaletteSwap {
+    fn new(element: &dyn HatElement) -> Self {
+        let mut colors = distinct_colors(element.bitmap());
+        let truncated = colors.len() > PALETTE_SWAP_MAX_COLORS;
+        colors.truncate(PALETTE_SWAP_MAX_COLORS);
+        Self {
+            element_id: element.id(),
+            swaps: colors.into_iter().map(|color| (color, color)).collect(),
+            truncated,
+            preview: None,
+        }
+    }
+
+    fn changed_swaps(&self) -> Vec<ColorSwap> {
+        self.swaps
+            .iter()
+            .copied()
+            .filter(|(old, new)| old != new)
+            .collect()
+    }
+
+    fn update_preview(&mut self, ctx: &egui::Context, element: &dyn HatElement) {
+        let bitmap = swap_colors(element.bitmap(), &self.changed_swaps());
+        let image = egui::ColorImage::from_rgba_unmultiplied(
+            [bitmap.width() as usize, bitmap.height() as usize],
+            bitmap.bytes(),
+        );
+        self.preview =
+            Some(ctx.load_texture("palette_swap_preview", image, egui::TextureOptions::NEAREST));
+    }
+}
+
+/// An element with animations in one of the open hats.
+#[derive(Debug, Clone)]
+pub struct AnimationSource {
+    pub hat_id: HatId,
+    pub element_id: HatElementId,
+    pub label: String,
+}
+
+/// Animations taken from a hat that isn't open, kept around so they can be imported.
+#[derive(Debug, Clone)]
+struct ExternalAnimationSource {
+    label: String,
+    frames_amount: u32,
+    animations: Vec<Animation>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum AnimationImportSource {
+    Open(HatId, HatElementId),
+    External(usize),
+    PickFile,
+    PickFolder,
+}
+
+/// Copies animations between elements of open hats. Handled once all tabs are drawn,
+/// since the two hats may live in different tabs.
+#[derive(Debug, Clone, Copy)]
+pub struct AnimationImport {
+    pub source_hat_id: HatId,
+    pub source_element_id: HatElementId,
+    pub target_hat_id: HatId,
+    pub target_element_id: HatElementId,
+}
+
+/// Copies an element into another open hat, handled once all tabs are drawn.
+#[derive(Debug, Clone, Copy)]
+pub struct ElementCopy {
+    pub source_hat_id: HatId,
+    pub element_id: HatElementId,
+    pub target_hat_id: HatId,
+}
+
+fn log_animation_import(result: Result<()>) {
+    match result {
+        Ok(()) => info!("animations imported"),
+        Err(err) => error!("while importing animations: {}", err.to_string()),
+    }
 }
 
 pub enum Tab {
@@ -41,6 +183,14 @@ pub enum Tab {
     },
 }
 
+/// What is kept of a tab between sessions. Hats are loaded again from their path.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum SavedTab {
+    Home,
+    Hat(PathBuf),
+    Console,
+}
+
 pub struct FrameData<'a> {
     pub ui_text: &'a UiText,
     pub clicked_rename_hat: bool,
@@ -49,6 +199,14 @@ pub struct FrameData<'a> {
     pub clicked_help_tab: bool,
     pub console: Option<Console>,
     pub gl: &'a glow::Context,
+    pub file_dialog_dirs: &'a mut FileDialogDirs,
+    pub animation_sources: Vec<AnimationSource>,
+    pub imported_animations: Option<AnimationImport>,
+    /// Ids and names of all open hats.
+    pub open_hats: Vec<(HatId, String)>,
+    pub copied_element: Option<ElementCopy>,
+    pub default_animation_delay: f32,
+    pub aseprite_layout: AsepriteLayout,
 }
 
 #[derive(Debug)]
@@ -58,6 +216,7 @@ pub struct FrameResult {
     pub clicked_open_hat: bool,
     pub clicked_help_tab: bool,
     pub console: Option<Console>,
+    pub toasts: Vec<(ToastType, String)>,
 }
 impl Tab {
     pub fn new_home_tab(title: String) -> Self {
@@ -92,8 +251,10 @@ impl TabViewer for MyTabViewer<'_, '_, '_> {
     type Tab = Tab;
 
     fn on_close(&mut self, tab: &mut Self::Tab) -> bool {
-        if let Tab::Console { console, .. } = tab {
-            self.frame_data.console = console.take();
+        match tab {
+            Tab::Console { console, .. } => self.frame_data.console = console.take(),
+            Tab::HatElement { hat, .. } => hat.unwatch_images(),
+            _ => {}
         }
         true
     }
@@ -108,14 +269,28 @@ impl TabViewer for MyTabViewer<'_, '_, '_> {
     }
 
     fn on_tab_button(&mut self, tab: &mut Self::Tab, response: &egui::Response) {
-        response
-            .clone()
-            .on_hover_text(self.frame_data.ui_text.get(match tab {
-                Tab::Home { .. } => "19",
-                Tab::Help { .. } => "20",
-                Tab::HatElement { .. } => "21",
-                Tab::Console { .. } => "40",
-            }));
+        let text = self.frame_data.ui_text;
+        if let Tab::HatElement { hat, .. } = tab {
+            let summary = hat.summary();
+            response.clone().on_hover_ui(|ui| {
+                ui.label(text.get("21"));
+                for hat_type in HatType::iter().filter(|t| summary.count(*t) > 0) {
+                    ui.label(format!(
+                        "{}: {}",
+                        text.get(hat_type.translate_key()),
+                        summary.count(hat_type)
+                    ));
+                }
+                ui.label(format!("{}: {}", text.get("140"), summary.total_frames));
+            });
+            return;
+        }
+        response.clone().on_hover_text(text.get(match tab {
+            Tab::Home { .. } => "19",
+            Tab::Help { .. } => "20",
+            Tab::HatElement { .. } => "21",
+            Tab::Console { .. } => "40",
+        }));
     }
 
     fn context_menu(
@@ -149,7 +324,7 @@ impl TabViewer for MyTabViewer<'_, '_, '_> {
                 console: Some(console),
                 ..
             } => {
-                console.update(ui);
+                console.update(ui, self.frame_data.ui_text);
             }
             _ => {}
         }
@@ -184,7 +359,7 @@ impl MyTabViewer<'_, '_, '_> {
         //     });
         // egui::CentralPanel::default().show_inside(ui, |ui| {
         let ui_text = &self.frame_data.ui_text;
-        let text = |text: &str| ui_text.get(text);
+        let text = |text: &'static str| ui_text.get(text);
         let label = |ui: &mut egui::Ui, text: &str| ui.label(ui_text.get(text));
         ui.heading(text("22"));
         ui.horizontal(|ui| {
@@ -232,31 +407,534 @@ impl MyTabViewer<'_, '_, '_> {
         if let Some(id) = left_panel_response.selected_hat_id {
             *selected_hat_id = Some(id);
         }
-        if let Some(remove_id) = left_panel_response.removed_hat_id {
+        for remove_id in left_panel_response.removed_hat_ids {
             hat.remove_element(remove_id);
+            state.selected_elements.retain(|id| *id != remove_id);
             if let Some(selected_id) = selected_hat_id
                 && *selected_id == remove_id
             {
                 *selected_hat_id = None;
             }
         }
-        if let Some(HatType::Wearable) = left_panel_response.added_hat_type
-            && let Some(path) = rfd::FileDialog::new()
-                .add_filter("Image", &["png", "aseprite"])
-                .pick_file()
+        if let Some((trim_id, bounds)) = left_panel_response.trimmed_hat {
+            match hat.trim_element(trim_id, bounds, self.frame_data.gl) {
+                Ok(()) => {
+                    info!(hat = hat.name(), element = trim_id.0; "element trimmed by {:?}", bounds)
+                }
+                Err(err) => {
+                    error!(hat = hat.name(), element = trim_id.0; "while trimming element: {}", err)
+                }
+            }
+        }
+        if let Some((reimport, keep_data)) = left_panel_response.reimported_hat {
+            self.finish_reimport(hat, reimport, keep_data);
+        }
+        if let Some((recolor_id, swaps)) = left_panel_response.recolored_hat {
+            match hat.recolor_element(recolor_id, &swaps, self.frame_data.gl) {
+                Ok(()) => {
+                    info!(
+                        hat = hat.name(), element = recolor_id.0;
+                        "{} element colors swapped", swaps.len()
+                    )
+                }
+                Err(err) => {
+                    error!(
+                        hat = hat.name(), element = recolor_id.0;
+                        "while swapping element colors: {}", err
+                    )
+                }
+            }
+        }
+        if let Some(id) = left_panel_response.attached_secondary
+            && let Err(err) = self.attach_secondary_image(hat, id)
         {
-            let wearable = WearableHat::load_from_path(&path, self.frame_data.gl).unwrap();
-            hat.add_element(wearable);
-        } else if let Some(HatType::Wings) = left_panel_response.added_hat_type
-            && let Some(path) = rfd::FileDialog::new()
+            error!(hat = hat.name(), element = id.0; "while attaching secondary image: {}", err);
+        }
+        if let Some(id) = left_panel_response.detached_secondary {
+            hat.detach_secondary_image(id);
+        }
+        if let Some((convert_id, hat_type)) = left_panel_response.converted_hat {
+            match hat.convert_element(convert_id, hat_type, self.frame_data.gl) {
+                Ok(new_id) => {
+                    if selected_hat_id.is_some_and(|id| id == convert_id) {
+                        *selected_hat_id = Some(new_id);
+                    }
+                }
+                Err(err) => {
+                    error!(
+                        hat = hat.name(), element = convert_id.0;
+                        "while converting element: {}", err
+                    )
+                }
+            }
+        }
+        if let Some(duplicate_id) = left_panel_response.duplicated_hat
+            && let Some(new_id) = hat.duplicate_element(duplicate_id, self.frame_data.gl)
+        {
+            info!(hat = hat.name(), element = new_id.0; "duplicated element {}", duplicate_id.0);
+            *selected_hat_id = Some(new_id);
+        }
+        if let Some((target_id, source)) = left_panel_response.imported_animations {
+            match source {
+                AnimationImportSource::Open(source_hat_id, source_element_id) => {
+                    self.frame_data.imported_animations = Some(AnimationImport {
+                        source_hat_id,
+                        source_element_id,
+                        target_hat_id: hat.id(),
+                        target_element_id: target_id,
+                    });
+                }
+                AnimationImportSource::External(index) => {
+                    if let Some(source) = state.external_animation_sources.get(index) {
+                        log_animation_import(hat.import_animations(
+                            target_id,
+                            &source.animations,
+                            source.frames_amount,
+                        ));
+                    }
+                }
+                AnimationImportSource::PickFile | AnimationImportSource::PickFolder => {
+                    let from_file = matches!(source, AnimationImportSource::PickFile);
+                    if let Err(err) = self.load_animation_sources(from_file, state) {
+                        error!(hat = hat.name(); "while loading animations: {}", err);
+                    }
+                }
+            }
+        }
+        if let Some((element_id, target_hat_id)) = left_panel_response.copied_hat {
+            self.frame_data.copied_element = Some(ElementCopy {
+                source_hat_id: hat.id(),
+                element_id,
+                target_hat_id,
+            });
+        }
+        if let Some(export_id) = left_panel_response.exported_hat_id
+            && let Some(element) = hat.element(export_id)
+            && let Err(err) = Self::export_element_image(element, self.frame_data.file_dialog_dirs)
+        {
+            error!(
+                hat = hat.name(), element = export_id.0;
+                "while exporting element image: {}", err
+            );
+        }
+        let file_dialog_dirs = &mut self.frame_data.file_dialog_dirs;
+        if let Some(hat_type @ (HatType::Wearable | HatType::Wings)) =
+            left_panel_response.added_hat_type
+            && let Some(path) = file_dialog_dirs
+                .dialog(FileDialogKind::ImportImage)
                 .add_filter("Image", &["png", "aseprite"])
                 .pick_file()
         {
-            let wings = WingsHat::load_from_path(&path, self.frame_data.gl).unwrap();
-            hat.add_element(wings);
+            file_dialog_dirs.remember(FileDialogKind::ImportImage, &path);
+            let layout = self.frame_data.aseprite_layout;
+            let animation_delay = self.frame_data.default_animation_delay;
+            let result = Image::new(&path)
+                .context(format!("could not load image at {:?}", &path))
+                .and_then(|image| {
+                    let element = HatElementData::default_for(hat_type)
+                        .context(format!("{:?} has no default data", hat_type))?;
+                    let image = image
+                        .with_aseprite_layout(layout)
+                        .with_animation_delay(animation_delay);
+                    hat.add_element_data(element, image, self.frame_data.gl)
+                });
+            if let Err(err) = result {
+                error!(hat = hat.name(); "while adding {:?}: {}", hat_type, err);
+            }
         }
 
-        self.draw_hat_ui(ui, hat, selected_hat_id);
+        if let Some(action) = self.draw_hat_ui(ui, hat, selected_hat_id, state)
+            && let Some(id) = *selected_hat_id
+        {
+            self.apply_preview_action(ui.ctx(), hat, id, action, state);
+        }
+        // Drags and text edits become a single undo step once they are finished. Edits are
+        // only ever committed by a click or a key press, so other frames aren't looked at.
+        let editing = ui.input(|i| i.pointer.any_down()) || ui.ctx().wants_keyboard_input();
+        let committed = ui.input(|i| {
+            i.pointer.any_released()
+                || i.events
+                    .iter()
+                    .any(|event| matches!(event, egui::Event::Key { pressed: true, .. }))
+        });
+        if committed && !editing {
+            state.undo_stack.record(hat);
+        }
+    }
+
+    /// Asks for confirmation before trimming the element, unless there is nothing to trim.
+    fn request_trim(
+        ctx: &egui::Context,
+        element: &dyn HatElement,
+        element_to_trim: &mut Option<(HatElementId, TrimBounds)>,
+    ) {
+        match trim_bounds(element.bitmap(), element.base().frame_size) {
+            Some(bounds) if bounds.is_empty() => {
+                info!(element = element.id().0; "element has no transparent borders to trim")
+            }
+            Some(bounds) => {
+                *element_to_trim = Some((element.id(), bounds));
+                egui_modal::Modal::new(ctx, TRIM_MODAL_ID).open();
+            }
+            None => warn!(element = element.id().0; "element image is fully transparent"),
+        }
+    }
+
+    fn draw_preview_menu(
+        ui: &mut egui::Ui,
+        text: &UiText,
+        hat: &Hat,
+        element: &dyn HatElement,
+    ) -> Option<PreviewAction> {
+        let in_folder = !hat.is_unsaved() && !hat.is_file_backed();
+        let has_source = element.bitmap().path().is_some();
+        Self::set_width(ui, text.get("136"));
+        let mut clicked = None;
+        for (action, key, enabled) in [
+            (PreviewAction::ReplaceImage, "134", true),
+            (PreviewAction::ReimportImage, "151", true),
+            (PreviewAction::ExportImage, "57", true),
+            (PreviewAction::Trim, "106", true),
+            (PreviewAction::BakeToPng, "135", in_folder),
+            (PreviewAction::RevealSource, "136", has_source),
+            (
+                PreviewAction::ExportGif,
+                "169",
+                element.animations().is_some_and(|a| !a.is_empty()),
+            ),
+        ] {
+            if ui
+                .add_enabled(enabled, egui::Button::new(text.get(key)))
+                .clicked()
+            {
+                ui.close_menu();
+                clicked = Some(action);
+            }
+        }
+        clicked
+    }
+
+    fn apply_preview_action(
+        &mut self,
+        ctx: &egui::Context,
+        hat: &mut Hat,
+        id: HatElementId,
+        action: PreviewAction,
+        state: &mut HatTabState,
+    ) {
+        let result = match action {
+            PreviewAction::ReplaceImage => self.replace_element_image(hat, id),
+            PreviewAction::ReimportImage => self.reimport_element_image(ctx, hat, id, state),
+            PreviewAction::ExportImage => match hat.element(id) {
+                Some(element) => {
+                    Self::export_element_image(element, self.frame_data.file_dialog_dirs)
+                }
+                None => Ok(()),
+            },
+            PreviewAction::Trim => {
+                if let Some(element) = hat.element(id) {
+                    Self::request_trim(ctx, element, &mut state.element_to_trim);
+                }
+                Ok(())
+            }
+            PreviewAction::BakeToPng => hat.bake_element_image(id).map(|path| match path {
+                Some(path) => {
+                    info!(hat = hat.name(), element = id.0; "element image baked to {:?}", path)
+                }
+                None => info!(
+                    hat = hat.name(), element = id.0;
+                    "element image already is a file in the hat folder"
+                ),
+            }),
+            PreviewAction::RevealSource => {
+                match hat.element(id).and_then(|e| e.bitmap().path()?.parent()) {
+                    Some(folder) => {
+                        open::that(folder).context(format!("could not open {:?}", folder))
+                    }
+                    None => Ok(()),
+                }
+            }
+            PreviewAction::ExportGif => Self::export_element_gif(
+                hat,
+                id,
+                state.preview.played_animation,
+                self.frame_data.file_dialog_dirs,
+            ),
+        };
+        if let Err(err) = result {
+            error!(
+                hat = hat.name(), element = id.0;
+                "while running {:?} on element: {}", action, err
+            );
+        }
+    }
+
+    fn replace_element_image(&mut self, hat: &mut Hat, id: HatElementId) -> Result<()> {
+        let file_dialog_dirs = &mut self.frame_data.file_dialog_dirs;
+        let Some(path) = file_dialog_dirs
+            .dialog(FileDialogKind::ImportImage)
+            .add_filter("Image", &["png", "aseprite"])
+            .pick_file()
+        else {
+            return Ok(());
+        };
+        file_dialog_dirs.remember(FileDialogKind::ImportImage, &path);
+        let image = Image::new(&path)
+            .context(format!("could not load image at {:?}", &path))?
+            .with_aseprite_layout(self.frame_data.aseprite_layout)
+            .with_animation_delay(self.frame_data.default_animation_delay);
+        hat.replace_element_image(id, image, self.frame_data.gl)?;
+        info!(hat = hat.name(), element = id.0; "element image replaced with {:?}", &path);
+        Ok(())
+    }
+
+    /// Re-imports right away if the new image has the same frames, otherwise asks first.
+    fn reimport_element_image(
+        &mut self,
+        ctx: &egui::Context,
+        hat: &mut Hat,
+        id: HatElementId,
+        state: &mut HatTabState,
+    ) -> Result<()> {
+        let file_dialog_dirs = &mut self.frame_data.file_dialog_dirs;
+        let Some(path) = file_dialog_dirs
+            .dialog(FileDialogKind::ImportImage)
+            .add_filter("Image", &["png", "aseprite"])
+            .pick_file()
+        else {
+            return Ok(());
+        };
+        file_dialog_dirs.remember(FileDialogKind::ImportImage, &path);
+        let image = Image::new(&path)
+            .context(format!("could not load image at {:?}", &path))?
+            .with_aseprite_layout(self.frame_data.aseprite_layout)
+            .with_animation_delay(self.frame_data.default_animation_delay);
+        let conflicts = hat.reimport_conflicts(id, &image)?;
+        if conflicts.is_empty() {
+            hat.reimport_element_image(id, image, self.frame_data.gl)?;
+            info!(
+                hat = hat.name(), element = id.0;
+                "element image re-imported from {:?}", &path
+            );
+        } else {
+            state.pending_reimport = Some(PendingReimport {
+                element_id: id,
+                path,
+                conflicts,
+            });
+            egui_modal::Modal::new(ctx, REIMPORT_MODAL_ID).open();
+        }
+        Ok(())
+    }
+
+    /// Finishes a re-import the user was asked about, either keeping the element's data or
+    /// taking everything from the file like replacing the image does.
+    fn finish_reimport(&mut self, hat: &mut Hat, reimport: PendingReimport, keep_data: bool) {
+        let PendingReimport {
+            element_id: id,
+            path,
+            ..
+        } = reimport;
+        let layout = self.frame_data.aseprite_layout;
+        let animation_delay = self.frame_data.default_animation_delay;
+        let result = Image::new(&path)
+            .context(format!("could not load image at {:?}", &path))
+            .map(|image| {
+                image
+                    .with_aseprite_layout(layout)
+                    .with_animation_delay(animation_delay)
+            })
+            .and_then(|image| {
+                if keep_data {
+                    hat.reimport_element_image(id, image, self.frame_data.gl)?;
+                    info!(
+                        hat = hat.name(), element = id.0;
+                        "element image re-imported from {:?}", &path
+                    );
+                } else {
+                    hat.replace_element_image(id, image, self.frame_data.gl)?;
+                    info!(
+                        hat = hat.name(), element = id.0;
+                        "element image replaced with {:?}", &path
+                    );
+                }
+                Ok(())
+            });
+        if let Err(err) = result {
+            error!(hat = hat.name(), element = id.0; "while re-importing image: {}", err);
+        }
+    }
+
+    fn attach_secondary_image(&mut self, hat: &mut Hat, id: HatElementId) -> Result<()> {
+        let file_dialog_dirs = &mut self.frame_data.file_dialog_dirs;
+        let Some(path) = file_dialog_dirs
+            .dialog(FileDialogKind::ImportImage)
+            .add_filter("Image", &["png"])
+            .pick_file()
+        else {
+            return Ok(());
+        };
+        file_dialog_dirs.remember(FileDialogKind::ImportImage, &path);
+        let bitmap =
+            Bitmap::from_path(&path).context(format!("could not load image at {:?}", &path))?;
+        hat.attach_secondary_image(id, bitmap, self.frame_data.gl)?;
+        info!(hat = hat.name(), element = id.0; "secondary image attached from {:?}", &path);
+        Ok(())
+    }
+
+    /// Loads another hat just to keep copies of its animations, so they can be imported.
+    fn load_animation_sources(&mut self, from_file: bool, state: &mut HatTabState) -> Result<()> {
+        let dialog = self
+            .frame_data
+            .file_dialog_dirs
+            .dialog(FileDialogKind::OpenHat);
+        let path = if from_file {
+            dialog.add_filter(".hatspp", &["hatspp"]).pick_file()
+        } else {
+            dialog.pick_folder()
+        };
+        let Some(path) = path else {
+            return Ok(());
+        };
+        self.frame_data
+            .file_dialog_dirs
+            .remember(FileDialogKind::OpenHat, &path);
+        let gl = self.frame_data.gl;
+        let text = self.frame_data.ui_text;
+        let hat = if from_file {
+            Hat::load_from_file(&path, gl)?
+        } else {
+            Hat::load(&path, gl)?
+        };
+        let mut loaded = 0;
+        for element in hat.elements() {
+            if let Some(animations) = element.animations()
+                && !animations.is_empty()
+            {
+                state
+                    .external_animation_sources
+                    .push(ExternalAnimationSource {
+                        label: format!(
+                            "{}: {}",
+                            hat.name(),
+                            text.get(element.base().hat_type.translate_key())
+                        ),
+                        frames_amount: element.frames_amount(),
+                        animations: animations.clone(),
+                    });
+                loaded += 1;
+            }
+        }
+        info!(
+            "loaded animations of {} elements from {:?}, pick one to import from the menu",
+            loaded, &path
+        );
+        Ok(())
+    }
+
+    fn draw_wings_settings(
+        ui: &mut egui::Ui,
+        text: &UiText,
+        wings_data: &mut WingsData,
+        frames_amount: u32,
+    ) {
+        //frames past the end crash the game
+        let last_frame = frames_amount.saturating_sub(1) as i32;
+        for (key, frame) in [
+            ("101", &mut wings_data.glide_frame),
+            ("102", &mut wings_data.idle_frame),
+        ] {
+            ui.horizontal(|ui| {
+                ui.label(text.get(key));
+                ui.add(egui::DragValue::new(frame).range(0..=last_frame));
+            });
+        }
+    }
+
+    /// Exports the animation playing in the preview, or else the idle one, as a GIF.
+    fn export_element_gif(
+        hat: &mut Hat,
+        id: HatElementId,
+        played_animation: Option<AnimType>,
+        file_dialog_dirs: &mut FileDialogDirs,
+    ) -> Result<()> {
+        let element = hat.element(id).context("element does not exist")?;
+        let animations = element.animations().map(Vec::as_slice).unwrap_or_default();
+        let Some(animation) = [played_animation, Some(AnimType::OnDefault)]
+            .into_iter()
+            .flatten()
+            .find_map(|anim_type| animations.iter().find(|a| a.anim_type == anim_type))
+            .or(animations.first())
+        else {
+            return Ok(());
+        };
+        let Some(path) = file_dialog_dirs
+            .dialog(FileDialogKind::ExportFile)
+            .add_filter("GIF", &["gif"])
+            .set_file_name(format!("{:?}.gif", animation.anim_type))
+            .save_file()
+        else {
+            return Ok(());
+        };
+        file_dialog_dirs.remember(FileDialogKind::ExportFile, &path);
+        let animation = animation.clone();
+        hat.export_element_gif(id, &animation, &path)?;
+        info!(
+            hat = hat.name(), element = id.0;
+            "{:?} animation exported to {:?}", animation.anim_type, &path
+        );
+        Ok(())
+    }
+
+    fn export_element_image(
+        element: &dyn HatElement,
+        file_dialog_dirs: &mut FileDialogDirs,
+    ) -> Result<()> {
+        let file_name = element
+            .bitmap()
+            .path()
+            .and_then(|path| path.file_stem())
+            .map(|stem| format!("{}.png", stem.to_string_lossy()))
+            .unwrap_or_else(|| format!("{}.png", element.id().0));
+        let Some(path) = file_dialog_dirs
+            .dialog(FileDialogKind::ExportFile)
+            .add_filter("Image", &["png"])
+            .set_file_name(file_name)
+            .save_file()
+        else {
+            return Ok(());
+        };
+        file_dialog_dirs.remember(FileDialogKind::ExportFile, &path);
+        element
+            .bitmap()
+            .save(&path)
+            .context(format!("could not save image to {:?}", &path))?;
+        info!(element = element.id().0; "element image exported to {:?}", &path);
+        Ok(())
+    }
+
+    fn draw_element_tooltip(ui: &mut egui::Ui, text: &UiText, element: &dyn HatElement) {
+        match element.bitmap().path() {
+            Some(path) => ui.label(path.to_string_lossy()),
+            None => ui.label(text.get("89")),
+        };
+        ui.label(format!(
+            "{}x{}",
+            element.texture().width(),
+            element.texture().height()
+        ));
+        if element.animations().is_some() {
+            let available = avalible_animations(element.base().hat_type)
+                .unwrap_or_default()
+                .len();
+            let missing = element.missing_anim_types().len();
+            ui.label(format!(
+                "{}: {}/{}",
+                text.get("109"),
+                available - missing,
+                available
+            ));
+        }
     }
 
     fn set_width(ui: &mut egui::Ui, text: &str) {
@@ -276,6 +954,13 @@ impl MyTabViewer<'_, '_, '_> {
         state: &mut HatTabState,
     ) -> HatLeftPanelResponse {
         let text = self.frame_data.ui_text;
+        let animation_sources = &self.frame_data.animation_sources;
+        let other_hats: Vec<_> = self
+            .frame_data
+            .open_hats
+            .iter()
+            .filter(|(id, _)| *id != hat.id())
+            .collect();
         egui::SidePanel::left(egui::Id::new(format!("left_panel{}", hat.id().0)))
             .max_width(300.0)
             .show_inside(ui, |ui| {
@@ -305,25 +990,181 @@ impl MyTabViewer<'_, '_, '_> {
                 });
                 let remove_modal = egui_modal::Modal::new(ui.ctx(), "remove_model");
                 remove_modal.show(|ui| {
-                    let elem_to_remove = state.element_to_remove.as_ref().unwrap().clone();
                     remove_modal.frame(ui, |ui| {
-                        let remove_label = format!(r#"{} "{}"?"#, text.get("46"), elem_to_remove.0);
-                        ui.label(remove_label);
+                        if let [(name, id)] = &state.elements_to_remove[..] {
+                            let remove_label = format!(r#"{} "{}"?"#, text.get("46"), name);
+                            ui.label(remove_label);
+                            if let Some(element) = hat.element(*id) {
+                                preview::draw_thumbnail(ui, element);
+                            }
+                        } else {
+                            ui.label(format!("{}:", text.get("104")));
+                            egui::ScrollArea::vertical()
+                                .max_height(300.0)
+                                .show(ui, |ui| {
+                                    for (name, id) in &state.elements_to_remove {
+                                        ui.horizontal(|ui| {
+                                            if let Some(element) = hat.element(*id) {
+                                                preview::draw_thumbnail(ui, element);
+                                            }
+                                            ui.label(name);
+                                        });
+                                    }
+                                });
+                        }
                     });
                     remove_modal.buttons(ui, |ui| {
                         if remove_modal.caution_button(ui, text.get("47")).clicked() {
                             remove_modal.close();
-                            state.element_to_remove = None;
-                            response.removed_hat_id = Some(elem_to_remove.1);
+                            response.removed_hat_ids =
+                                std::mem::take(&mut state.elements_to_remove)
+                                    .into_iter()
+                                    .map(|(_, id)| id)
+                                    .collect();
                         } else if remove_modal.button(ui, text.get("48")).clicked() {
                             remove_modal.close();
-                            state.element_to_remove = None;
+                            state.elements_to_remove.clear();
+                        }
+                    });
+                });
+
+                let trim_modal = egui_modal::Modal::new(ui.ctx(), TRIM_MODAL_ID);
+                trim_modal.show(|ui| {
+                    trim_modal.frame(ui, |ui| {
+                        ui.label(text.get("107"));
+                        if let Some((id, bounds)) = state.element_to_trim
+                            && let Some(element) = hat.element(id)
+                        {
+                            let frame_size = element.base().frame_size;
+                            let new_size = bounds.trimmed_size(frame_size);
+                            ui.label(format!(
+                                "← {} px, ↑ {} px, → {} px, ↓ {} px",
+                                bounds.left, bounds.top, bounds.right, bounds.bottom
+                            ));
+                            ui.label(format!(
+                                "{}: {}x{} → {}x{}",
+                                text.get("108"),
+                                frame_size.x,
+                                frame_size.y,
+                                new_size.x,
+                                new_size.y
+                            ));
+                        }
+                    });
+                    trim_modal.buttons(ui, |ui| {
+                        if trim_modal.suggested_button(ui, text.get("47")).clicked() {
+                            trim_modal.close();
+                            response.trimmed_hat = state.element_to_trim.take();
+                        } else if trim_modal.button(ui, text.get("48")).clicked() {
+                            trim_modal.close();
+                            state.element_to_trim = None;
                         }
                     });
                 });
 
+                let reimport_modal = egui_modal::Modal::new(ui.ctx(), REIMPORT_MODAL_ID);
+                reimport_modal.show(|ui| {
+                    reimport_modal.title(ui, text.get("152"));
+                    reimport_modal.frame(ui, |ui| {
+                        if let Some(reimport) = &state.pending_reimport {
+                            for conflict in &reimport.conflicts {
+                                ui.label(format!("• {}", conflict));
+                            }
+                        }
+                    });
+                    reimport_modal.buttons(ui, |ui| {
+                        if reimport_modal
+                            .suggested_button(ui, text.get("153"))
+                            .clicked()
+                        {
+                            reimport_modal.close();
+                            response.reimported_hat = state
+                                .pending_reimport
+                                .take()
+                                .map(|reimport| (reimport, true));
+                        } else if reimport_modal.caution_button(ui, text.get("154")).clicked() {
+                            reimport_modal.close();
+                            response.reimported_hat = state
+                                .pending_reimport
+                                .take()
+                                .map(|reimport| (reimport, false));
+                        } else if reimport_modal.button(ui, text.get("43")).clicked() {
+                            reimport_modal.close();
+                            state.pending_reimport = None;
+                        }
+                    });
+                });
+
+                let palette_modal = egui_modal::Modal::new(ui.ctx(), "palette_swap_modal");
+                palette_modal.show(|ui| {
+                    palette_modal.title(ui, text.get("122"));
+                    palette_modal.frame(ui, |ui| {
+                        if let Some(swap) = &mut state.palette_swap
+                            && let Some(element) = hat.element(swap.element_id)
+                        {
+                            let mut changed = swap.preview.is_none();
+                            ui.horizontal_top(|ui| {
+                                egui::ScrollArea::vertical()
+                                    .max_height(300.0)
+                                    .show(ui, |ui| {
+                                        egui::Grid::new("palette_swap_grid").show(ui, |ui| {
+                                            for (old, new) in &mut swap.swaps {
+                                                let mut original = *old;
+                                                ui.add_enabled_ui(false, |ui| {
+                                                    ui.color_edit_button_srgba_unmultiplied(
+                                                        &mut original,
+                                                    );
+                                                });
+                                                ui.label("→");
+                                                changed |= ui
+                                                    .color_edit_button_srgba_unmultiplied(new)
+                                                    .changed();
+                                                ui.end_row();
+                                            }
+                                        });
+                                    });
+                                if changed {
+                                    swap.update_preview(ui.ctx(), element);
+                                }
+                                if let Some(preview) = &swap.preview {
+                                    ui.add(
+                                        egui::Image::new(preview)
+                                            .fit_to_exact_size(egui::vec2(256.0, 256.0)),
+                                    );
+                                }
+                            });
+                            if swap.truncated {
+                                ui.label(text.get("124"));
+                            }
+                        }
+                    });
+                    palette_modal.buttons(ui, |ui| {
+                        if palette_modal
+                            .suggested_button(ui, text.get("123"))
+                            .clicked()
+                        {
+                            palette_modal.close();
+                            response.recolored_hat = state
+                                .palette_swap
+                                .take()
+                                .map(|swap| (swap.element_id, swap.changed_swaps()))
+                                .filter(|(_, swaps)| !swaps.is_empty());
+                        } else if palette_modal.button(ui, text.get("43")).clicked() {
+                            palette_modal.close();
+                            state.palette_swap = None;
+                        }
+                    });
+                });
+
+                ui.add(
+                    egui::TextEdit::singleline(&mut state.element_filter)
+                        .hint_text(text.get("73")),
+                );
+                let filter = state.element_filter.to_lowercase();
+
                 let id = ui.make_persistent_id(egui::Id::new(format!("tree_view{}", hat.id().0)));
-                let tree_response = egui_ltreeview::TreeView::new(id).show(ui, |builder| {
+                let tree_view = egui_ltreeview::TreeView::new(id).allow_multi_selection(true);
+                let tree_response = tree_view.show(ui, |builder| {
                     builder.node(
                         egui_ltreeview::NodeBuilder::dir(0)
                             .label(text.get("Elements"))
@@ -335,19 +1176,156 @@ impl MyTabViewer<'_, '_, '_> {
                                 }
                             }),
                     );
-                    for elem in hat.elements() {
+                    for elem in hat.elements().filter(|e| {
+                        text.get(e.base().hat_type.translate_key())
+                            .to_lowercase()
+                            .contains(&filter)
+                    }) {
                         builder.node(
                             egui_ltreeview::NodeBuilder::leaf(elem.id().0)
-                                .label(text.get(elem.base().hat_type.translate_key()).to_string())
+                                .label_ui(|ui| {
+                                    ui.label(text.get(elem.base().hat_type.translate_key()))
+                                        .on_hover_ui(|ui| {
+                                            Self::draw_element_tooltip(ui, text, elem)
+                                        });
+                                })
                                 .context_menu(|ui| {
-                                Self::set_width(ui, text.get("45"));
+                                Self::set_width(ui, text.get("57"));
+                                    if ui.button(text.get("57")).clicked() {
+                                        response.exported_hat_id = Some(elem.id());
+                                        ui.close_menu();
+                                    }
+                                    if elem.animations().is_some() {
+                                        ui.menu_button(text.get("98"), |ui| {
+                                            let open_sources = animation_sources
+                                                .iter()
+                                                .filter(|source| source.element_id != elem.id())
+                                                .map(|source| {
+                                                    (
+                                                        &source.label,
+                                                        AnimationImportSource::Open(
+                                                            source.hat_id,
+                                                            source.element_id,
+                                                        ),
+                                                    )
+                                                });
+                                            let external_sources = state
+                                                .external_animation_sources
+                                                .iter()
+                                                .enumerate()
+                                                .map(|(index, source)| {
+                                                    (
+                                                        &source.label,
+                                                        AnimationImportSource::External(index),
+                                                    )
+                                                });
+                                            for (label, source) in
+                                                open_sources.chain(external_sources)
+                                            {
+                                                if ui.button(label).clicked() {
+                                                    response.imported_animations =
+                                                        Some((elem.id(), source));
+                                                    ui.close_menu();
+                                                }
+                                            }
+                                            ui.separator();
+                                            if ui.button(text.get("99")).clicked() {
+                                                response.imported_animations = Some((
+                                                    elem.id(),
+                                                    AnimationImportSource::PickFile,
+                                                ));
+                                                ui.close_menu();
+                                            }
+                                            if ui.button(text.get("100")).clicked() {
+                                                response.imported_animations = Some((
+                                                    elem.id(),
+                                                    AnimationImportSource::PickFolder,
+                                                ));
+                                                ui.close_menu();
+                                            }
+                                        });
+                                    }
+                                    ui.menu_button(text.get("88"), |ui| {
+                                        for hat_type in HatType::iter() {
+                                            if ui
+                                                .add_enabled(
+                                                    hat.can_convert_element(elem.id(), hat_type),
+                                                    egui::Button::new(
+                                                        text.get(hat_type.translate_key()),
+                                                    ),
+                                                )
+                                                .clicked()
+                                            {
+                                                response.converted_hat =
+                                                    Some((elem.id(), hat_type));
+                                                ui.close_menu();
+                                            }
+                                        }
+                                    });
+                                    let rejection = hat.rejection_reason(elem.base().hat_type);
+                                    let duplicate_button = ui.add_enabled(
+                                        rejection.is_none(),
+                                        egui::Button::new(text.get("168")),
+                                    );
+                                    if let Some(reason) = rejection {
+                                        duplicate_button.on_disabled_hover_text(reason);
+                                    } else if duplicate_button.clicked() {
+                                        response.duplicated_hat = Some(elem.id());
+                                        ui.close_menu();
+                                    }
+                                    ui.menu_button(text.get("114"), |ui| {
+                                        if other_hats.is_empty() {
+                                            ui.label(text.get("115"));
+                                        }
+                                        for (target_id, name) in &other_hats {
+                                            if ui.button(name).clicked() {
+                                                response.copied_hat = Some((elem.id(), *target_id));
+                                                ui.close_menu();
+                                            }
+                                        }
+                                    });
+                                    if ui.button(text.get("106")).clicked() {
+                                        ui.close_menu();
+                                        Self::request_trim(
+                                            ui.ctx(),
+                                            elem,
+                                            &mut state.element_to_trim,
+                                        );
+                                    }
+                                    if ui.button(text.get("121")).clicked() {
+                                        ui.close_menu();
+                                        state.palette_swap = Some(PaletteSwap::new(elem));
+                                        palette_modal.open();
+                                    }
+                                    if ui.button(text.get("141")).clicked() {
+                                        response.attached_secondary = Some(elem.id());
+                                        ui.close_menu();
+                                    }
+                                    if elem.secondary().is_some()
+                                        && ui.button(text.get("142")).clicked()
+                                    {
+                                        response.detached_secondary = Some(elem.id());
+                                        ui.close_menu();
+                                    }
                                     if ui.button(text.get("45")).clicked() {
                                         remove_modal.open();
-                                        state.element_to_remove = Some((
-                                            text.get(elem.base().hat_type.translate_key())
-                                                .to_string(),
-                                            elem.id(),
-                                        ));
+                                        //removing a selected element removes the whole selection
+                                        let ids = if state.selected_elements.contains(&elem.id()) {
+                                            state.selected_elements.clone()
+                                        } else {
+                                            vec![elem.id()]
+                                        };
+                                        state.elements_to_remove = ids
+                                            .into_iter()
+                                            .filter_map(|id| hat.element(id))
+                                            .map(|e| {
+                                                (
+                                                    text.get(e.base().hat_type.translate_key())
+                                                        .to_string(),
+                                                    e.id(),
+                                                )
+                                            })
+                                            .collect();
                                     }
                                 }),
                         );
@@ -356,18 +1334,23 @@ impl MyTabViewer<'_, '_, '_> {
                 });
 
                 for action in tree_response.1 {
-                    if let egui_ltreeview::Action::SetSelected(ids) = action
-                        && !ids.is_empty()
-                        && !hat.is_empty()
-                    {
-                        let id = HatElementId(ids[0]);
-                        if hat.has_element_with_id(id) {
-                            response.selected_hat_id = Some(id);
-                            break;
+                    if let egui_ltreeview::Action::SetSelected(ids) = action {
+                        //the "Elements" folder can be selected too
+                        state.selected_elements = ids
+                            .into_iter()
+                            .map(HatElementId)
+                            .filter(|id| hat.has_element_with_id(*id))
+                            .collect();
+                        if let Some(id) = state.selected_elements.first() {
+                            response.selected_hat_id = Some(*id);
                         }
                     }
                 }
 
+                ui.collapsing(text.get("182"), |ui| {
+                    hat.image_watcher().draw_debug(ui, text)
+                });
+
                 response
             })
             .inner
@@ -378,14 +1361,15 @@ impl MyTabViewer<'_, '_, '_> {
         ui: &mut egui::Ui,
         hat: &mut Hat,
         selected_hat_id: &mut Option<HatElementId>,
-    ) {
+        state: &mut HatTabState,
+    ) -> Option<PreviewAction> {
         let text = self.frame_data.ui_text;
         let hat_element_id = match selected_hat_id {
             Some(id) => *id,
             None => {
                 if hat.is_empty() {
                     ui.label(text.get("37"));
-                    return;
+                    return None;
                 }
 
                 let selected_hat = hat.elements().next().map(|h| h.id()).unwrap();
@@ -393,18 +1377,91 @@ impl MyTabViewer<'_, '_, '_> {
                 selected_hat
             }
         };
+        let mut preview_action = None;
         egui::CentralPanel::default().show_inside(ui, |ui| {
+            let animations = hat.element(hat_element_id).and_then(|e| e.animations());
+            preview::draw_preview_toolbar(
+                ui,
+                text,
+                &mut state.preview,
+                animations.map(Vec::as_slice),
+            );
+            let preview_response =
+                preview::draw_preview(ui, text, hat, hat_element_id, &mut state.preview);
+            if let Some(element) = hat.element(hat_element_id) {
+                preview_response.context_menu(|ui| {
+                    preview_action = Self::draw_preview_menu(ui, text, hat, element);
+                });
+            }
+            if state.preview.timeline
+                && let Some(animations) = hat.element(hat_element_id).and_then(|e| e.animations())
+            {
+                if state.preview.playing {
+                    let elapsed = ui.input(|i| i.stable_dt) * state.preview.playback_speed;
+                    timeline::advance_playhead(animations, &mut state.preview.playhead, elapsed);
+                    ui.ctx().request_repaint();
+                }
+                timeline::draw_timeline(ui, text, animations, &mut state.preview.playhead);
+                timeline::draw_frame_step(ui, text, animations, &mut state.preview.playhead);
+            } else if let Some(anim_type) = state.preview.played_animation
+                && let Some(element) = hat.element(hat_element_id)
+            {
+                let animation = element
+                    .animations()
+                    .and_then(|animations| animations.iter().find(|a| a.anim_type == anim_type));
+                match animation {
+                    Some(animation) => {
+                        let elapsed = ui.input(|i| i.stable_dt) * state.preview.playback_speed;
+                        let frames_amount = element.frames_amount();
+                        state
+                            .preview
+                            .player
+                            .advance(animation, frames_amount, elapsed);
+                        ui.ctx().request_repaint();
+                    }
+                    None => state.preview.player.restart(),
+                }
+            }
             let selected_hat = hat.element_mut(hat_element_id).unwrap();
-            ui.label(selected_hat.base().hat_type.translate_key().to_string());
+            ui.label(text.get(selected_hat.base().hat_type.translate_key()));
+            let hat_type = selected_hat.base().hat_type;
+            let frames_amount = selected_hat.frames_amount();
+            if let Some(wings) = selected_hat.downcast_mut::<WingsHat>() {
+                ui.separator();
+                Self::draw_wings_settings(ui, text, wings.data_mut(), frames_amount);
+            }
+            if let Some(animations) = selected_hat.animations_mut() {
+                ui.separator();
+                animation_window::draw_animations(
+                    ui,
+                    text,
+                    hat_type,
+                    frames_amount,
+                    self.frame_data.default_animation_delay,
+                    animations,
+                );
+            }
         });
+        preview_action
     }
 }
 
-#[derive(Debug, Default, Clone, Copy)]
+#[derive(Debug, Default, Clone)]
 struct HatLeftPanelResponse {
     selected_hat_id: Option<HatElementId>,
     added_hat_type: Option<HatType>,
-    removed_hat_id: Option<HatElementId>,
+    removed_hat_ids: Vec<HatElementId>,
+    trimmed_hat: Option<(HatElementId, TrimBounds)>,
+    /// A pending re-import and whether to keep the element's data.
+    reimported_hat: Option<(PendingReimport, bool)>,
+    recolored_hat: Option<(HatElementId, Vec<ColorSwap>)>,
+    attached_secondary: Option<HatElementId>,
+    detached_secondary: Option<HatElementId>,
+    exported_hat_id: Option<HatElementId>,
+    converted_hat: Option<(HatElementId, HatType)>,
+    duplicated_hat: Option<HatElementId>,
+    imported_animations: Option<(HatElementId, AnimationImportSource)>,
+    copied_hat: Option<(HatElementId, HatId)>,
 }
 
 pub struct Tabs {
@@ -437,6 +1494,29 @@ impl Tabs {
             .push_to_focused_leaf(Tab::new_console_tab(ui_text.get("38").to_string(), console));
     }
 
+    /// The dock layout with hats replaced by their paths. Unsaved hats are left out.
+    pub fn saved_layout(&self) -> DockState<SavedTab> {
+        self.dock_state.filter_map_tabs(|tab| match tab {
+            Tab::Home { .. } => Some(SavedTab::Home),
+            Tab::Help { .. } => None,
+            Tab::HatElement { hat, .. } => {
+                (!hat.is_unsaved()).then(|| SavedTab::Hat(hat.path().to_path_buf()))
+            }
+            Tab::Console { .. } => Some(SavedTab::Console),
+        })
+    }
+
+    /// Replaces the open tabs, keeping the dock's translations.
+    pub fn set_layout(&mut self, mut dock_state: DockState<Tab>) {
+        dock_state.translations = self.dock_state.translations.clone();
+        self.hat_tabs_count = dock_state
+            .iter_all_tabs()
+            .filter(|(_, tab)| matches!(tab, Tab::HatElement { .. }))
+            .count()
+            .max(1);
+        self.dock_state = dock_state;
+    }
+
     pub fn last_interacted_tab(&mut self) -> Option<&Tab> {
         self.dock_state.find_active_focused().map(|(_, tab)| &*tab)
     }
@@ -471,7 +1551,49 @@ impl Tabs {
         self.last_interacted_tab_hat().map(|h| h.name())
     }
 
+    pub fn last_interacted_undo_stack(&mut self) -> Option<&UndoStack> {
+        self.last_interacted_tab().and_then(|t| {
+            if let Tab::HatElement { state, .. } = t {
+                Some(&state.undo_stack)
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Undoes (or redoes) the last change to the focused hat. Returns whether there was
+    /// one.
+    pub fn undo_last_interacted(&mut self, gl: &glow::Context, redo: bool) -> Result<bool> {
+        let Some(Tab::HatElement {
+            hat,
+            selected_hat_id,
+            state,
+        }) = self.last_interacted_tab_mut()
+        else {
+            return Ok(false);
+        };
+        let changed = if redo {
+            state.undo_stack.redo(hat, gl)?
+        } else {
+            state.undo_stack.undo(hat, gl)?
+        };
+        if selected_hat_id.is_some_and(|id| !hat.element_exists(id)) {
+            *selected_hat_id = None;
+        }
+        state.selected_elements.retain(|id| hat.element_exists(*id));
+        Ok(changed)
+    }
+
     pub fn ui(&mut self, ui: &mut egui::Ui, mut frame_data: FrameData) -> FrameResult {
+        frame_data.animation_sources = self.animation_sources(frame_data.ui_text);
+        frame_data.open_hats = self
+            .dock_state
+            .iter_all_tabs()
+            .filter_map(|(_, tab)| match tab {
+                Tab::HatElement { hat, .. } => Some((hat.id(), hat.name().to_string())),
+                _ => None,
+            })
+            .collect();
         let mut added_nodes = vec![];
         let mut tab_viewer = MyTabViewer {
             added_nodes: &mut added_nodes,
@@ -496,18 +1618,153 @@ impl Tabs {
         //         .set_focused_node_and_surface((surface, node));
         //     self.dock_state.push_to_focused_leaf(tab);
         // }
+        if let Some(import) = frame_data.imported_animations.take() {
+            log_animation_import(self.import_animations(import));
+        }
+        let mut toasts = vec![];
+        for (_, tab) in self.dock_state.iter_all_tabs_mut() {
+            if let Tab::HatElement { hat, .. } = tab {
+                toasts.extend(
+                    hat.take_load_warnings()
+                        .into_iter()
+                        .map(|warning| (ToastType::Warn, warning.to_text(frame_data.ui_text))),
+                );
+            }
+        }
+        if let Some(copy) = frame_data.copied_element.take() {
+            match self.copy_element(copy, frame_data.gl) {
+                Ok(target_name) => {
+                    info!(element = copy.element_id.0; "element copied to {}", &target_name);
+                    toasts.push((
+                        ToastType::Success,
+                        format!(r#"{} "{}""#, frame_data.ui_text.get("197"), target_name),
+                    ));
+                }
+                Err(err) => {
+                    error!(element = copy.element_id.0; "while copying element: {}", err);
+                    let message = format!("{}: {}", frame_data.ui_text.get("198"), err);
+                    toasts.push((ToastType::Error, message));
+                }
+            }
+        }
         FrameResult {
             clicked_rename_hat: frame_data.clicked_rename_hat,
             cliked_new_hat: frame_data.clicked_new_hat,
             clicked_open_hat: frame_data.clicked_open_hat,
             clicked_help_tab: frame_data.clicked_help_tab,
             console: frame_data.console,
+            toasts,
         }
     }
 
+    fn animation_sources(&self, ui_text: &UiText) -> Vec<AnimationSource> {
+        let mut sources = vec![];
+        for (_, tab) in self.dock_state.iter_all_tabs() {
+            let Tab::HatElement { hat, .. } = tab else {
+                continue;
+            };
+            for element in hat.elements() {
+                if element.animations().is_some_and(|a| !a.is_empty()) {
+                    sources.push(AnimationSource {
+                        hat_id: hat.id(),
+                        element_id: element.id(),
+                        label: format!(
+                            "{}: {}",
+                            hat.name(),
+                            ui_text.get(element.base().hat_type.translate_key())
+                        ),
+                    });
+                }
+            }
+        }
+        sources
+    }
+
+    fn hat_mut(&mut self, hat_id: HatId) -> Option<&mut Hat> {
+        self.dock_state
+            .iter_all_tabs_mut()
+            .find_map(|(_, tab)| match tab {
+                Tab::HatElement { hat, .. } if hat.id() == hat_id => Some(hat),
+                _ => None,
+            })
+    }
+
+    fn import_animations(&mut self, import: AnimationImport) -> Result<()> {
+        let (animations, frames_amount) = self
+            .hat_mut(import.source_hat_id)
+            .and_then(|hat| hat.element(import.source_element_id))
+            .and_then(|e| Some((e.animations()?.clone(), e.frames_amount())))
+            .context("source element no longer exists")?;
+        self.hat_mut(import.target_hat_id)
+            .context("target hat is no longer open")?
+            .import_animations(import.target_element_id, &animations, frames_amount)
+    }
+
+    /// Returns the name of the hat the element was copied to.
+    fn copy_element(&mut self, copy: ElementCopy, gl: &glow::Context) -> Result<String> {
+        let (data, bitmap) = self
+            .hat_mut(copy.source_hat_id)
+            .and_then(|hat| hat.element_copy(copy.element_id))
+            .context("source element no longer exists")?;
+        let target = self
+            .hat_mut(copy.target_hat_id)
+            .context("target hat is no longer open")?;
+        target.add_element_copy(data, bitmap, gl)?;
+        Ok(target.name().to_string())
+    }
+
+    /// Where the tab editing the hat in `path` is. Paths are resolved first, so different
+    /// spellings of the same folder match. Unsaved hats have no folder and never match.
+    fn find_hat_tab(&mut self, path: &Path) -> Option<(SurfaceIndex, NodeIndex, TabIndex)> {
+        for (_, tab) in self.dock_state.iter_all_tabs_mut() {
+            if let Tab::HatElement { hat, state, .. } = tab
+                && state
+                    .resolved_path
+                    .as_ref()
+                    .is_none_or(|(hat_path, _)| hat_path != hat.path())
+            {
+                state.resolved_path = Some((hat.path().to_path_buf(), resolve_path(hat.path())));
+            }
+        }
+        let path = resolve_path(path);
+        self.dock_state.find_tab_from(|tab| {
+            matches!(tab, Tab::HatElement { hat, state, .. }
+                if !hat.is_unsaved()
+                    && state.resolved_path.as_ref().is_some_and(|(_, resolved)| *resolved == path))
+        })
+    }
+
+    /// Id of the hat open in `path`, if there is one.
+    pub fn open_hat_id(&mut self, path: &Path) -> Option<HatId> {
+        let (surface, node, tab) = self.find_hat_tab(path)?;
+        match &self.dock_state[surface][node].tabs()?[tab.0] {
+            Tab::HatElement { hat, .. } => Some(hat.id()),
+            _ => None,
+        }
+    }
+
+    pub fn is_hat_open(&mut self, path: &Path) -> bool {
+        self.find_hat_tab(path).is_some()
+    }
+
+    /// Switches to the tab editing the hat in `path`. Returns whether there was one.
+    pub fn focus_hat_tab(&mut self, path: &Path) -> bool {
+        let Some((surface, node, tab)) = self.find_hat_tab(path) else {
+            return false;
+        };
+        self.dock_state.set_active_tab((surface, node, tab));
+        self.dock_state
+            .set_focused_node_and_surface((surface, node));
+        true
+    }
+
     pub fn new_hat_tab_name(&mut self, ui_text: &UiText) -> String {
         let name = format!("{} {}", ui_text.get("Hat"), self.hat_tabs_count);
         self.hat_tabs_count += 1;
         name
     }
 }
+
+fn resolve_path(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
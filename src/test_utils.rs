@@ -0,0 +1,76 @@
+//! Helpers for tests that need a GL context, e.g. to load hats or create textures.
+
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use anyhow::{Context as _, Result};
+use eframe::glow;
+use glutin::{
+    api::egl::{context::PossiblyCurrentContext, device::Device, display::Display},
+    config::{ConfigSurfaceTypes, ConfigTemplateBuilder},
+    context::ContextAttributesBuilder,
+    prelude::*,
+};
+use uuid::Uuid;
+
+/// A surfaceless EGL context, current on the thread that created it. Works without a
+/// window system, including with mesa's software rasterizer.
+pub struct HeadlessGl {
+    pub gl: Arc<glow::Context>,
+    _context: PossiblyCurrentContext,
+    _display: Display,
+}
+
+impl HeadlessGl {
+    pub fn new() -> Result<Self> {
+        let device = Device::query_devices()
+            .context("could not query EGL devices")?
+            .next()
+            .context("no EGL devices available")?;
+        let display =
+            unsafe { Display::with_device(&device, None) }.context("could not create display")?;
+        let template = ConfigTemplateBuilder::new()
+            .with_surface_type(ConfigSurfaceTypes::empty())
+            .build();
+        let config = unsafe { display.find_configs(template) }
+            .context("could not query configs")?
+            .next()
+            .context("no suitable config")?;
+        let attributes = ContextAttributesBuilder::new().build(None);
+        let context = unsafe { display.create_context(&config, &attributes) }
+            .context("could not create context")?
+            .make_current_surfaceless()
+            .context("could not make context current")?;
+        let gl = unsafe {
+            glow::Context::from_loader_function_cstr(|name| display.get_proc_address(name))
+        };
+        Ok(Self {
+            gl: Arc::new(gl),
+            _context: context,
+            _display: display,
+        })
+    }
+}
+
+/// A uniquely named directory in the system temp dir, removed on drop.
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    pub fn new() -> Result<Self> {
+        let path = std::env::temp_dir().join(format!("hpp-editor-test-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&path).context(format!("could not create {:?}", &path))?;
+        Ok(Self(path))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
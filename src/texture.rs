@@ -6,7 +6,10 @@ use std::{
 
 use anyhow::{Context, Result, anyhow, bail};
 use bevy_math::IVec2;
-use eframe::glow::{self, HasContext, NativeTexture};
+use eframe::{
+    egui,
+    glow::{self, HasContext, NativeTexture},
+};
 use pixas::bitmap::Bitmap;
 
 use crate::image::{Image, bitmap_from_ase};
@@ -16,6 +19,74 @@ pub struct Inner {
     pub native: NativeTexture,
     pub width: i32,
     pub height: i32,
+    /// Set once the texture is registered with egui's painter.
+    pub egui_id: Option<egui::TextureId>,
+    /// Once set, `native` names a freed texture and must never reach GL or egui again:
+    /// [`Texture::native`] and [`Texture::egui_id`] return `None` from then on.
+    pub deleted: bool,
+}
+
+thread_local! {
+    /// Textures dropped since the last frame. Freeing them needs the GL context, which
+    /// only [`delete_pending_textures`] gets to see.
+    static PENDING_DELETIONS: RefCell<Vec<(NativeTexture, Option<egui::TextureId>)>> =
+        const { RefCell::new(Vec::new()) };
+    /// Freed textures that are still registered with egui. eframe can register native
+    /// textures but not unregister them, so new textures take these over instead of adding
+    /// registrations.
+    static RECYCLED: RefCell<Vec<(NativeTexture, egui::TextureId)>> =
+        const { RefCell::new(Vec::new()) };
+}
+
+/// Frees the GL textures of every [`Texture`] dropped since the last call.
+pub fn delete_pending_textures(gl: &glow::Context) {
+    PENDING_DELETIONS.with_borrow_mut(|pending| {
+        for (texture, egui_id) in pending.drain(..) {
+            free_native(gl, texture, egui_id);
+        }
+    });
+}
+
+/// Forgets the textures kept for reuse without touching GL, for when the context they
+/// belong to may be gone.
+pub fn forget_recycled_textures() {
+    RECYCLED.with_borrow_mut(Vec::clear);
+}
+
+/// Textures registered with egui are shrunk to a single pixel and kept for reuse, the rest
+/// are deleted.
+fn free_native(gl: &glow::Context, texture: NativeTexture, egui_id: Option<egui::TextureId>) {
+    let Some(egui_id) = egui_id else {
+        unsafe { gl.delete_texture(texture) };
+        return;
+    };
+    unsafe {
+        gl.bind_texture(glow::TEXTURE_2D, Some(texture));
+        gl.tex_image_2d(
+            glow::TEXTURE_2D,
+            0,
+            glow::RGBA as i32,
+            1,
+            1,
+            0,
+            glow::RGBA,
+            glow::UNSIGNED_BYTE,
+            glow::PixelUnpackData::Slice(None),
+        );
+    }
+    RECYCLED.with_borrow_mut(|recycled| recycled.push((texture, egui_id)));
+}
+
+/// A recycled texture along with its egui registration if there is one, otherwise a new
+/// texture.
+fn create_native(gl: &glow::Context) -> Result<(NativeTexture, Option<egui::TextureId>)> {
+    if let Some((texture, egui_id)) = RECYCLED.with_borrow_mut(Vec::pop) {
+        return Ok((texture, Some(egui_id)));
+    }
+    let texture = unsafe { gl.create_texture() }
+        .map_err(|e| anyhow!(e))
+        .context("could not create texture")?;
+    Ok((texture, None))
 }
 
 #[derive(Debug, Clone)]
@@ -25,20 +96,27 @@ pub struct Texture {
 }
 
 impl Texture {
-    //TODO: handle deleted textures
+    /// Frees the texture right away instead of waiting for it to be dropped. Deleting it
+    /// again does nothing.
     pub fn delete(&self, gl: &eframe::glow::Context) {
-        unsafe { gl.delete_texture(NativeTexture(self.inner.borrow().native.0)) };
+        let mut inner = self.inner.borrow_mut();
+        if !inner.deleted {
+            free_native(gl, inner.native, inner.egui_id);
+            inner.deleted = true;
+            inner.egui_id = None;
+        }
+    }
+
+    pub fn is_deleted(&self) -> bool {
+        self.inner.borrow().deleted
     }
     #[allow(dead_code)]
     pub fn with_size(gl: &glow::Context, size: IVec2) -> Result<Self> {
         if size.x == 0 || size.y == 0 {
             bail!("attempt to create empty texture with size {0}", size);
         }
+        let (texture, egui_id) = create_native(gl)?;
         unsafe {
-            let texture = match gl.create_texture() {
-                Ok(texture) => texture,
-                Err(err) => bail!("could not create texture: {}", err),
-            };
             gl.bind_texture(glow::TEXTURE_2D, Some(texture));
             gl.tex_image_2d(
                 glow::TEXTURE_2D,
@@ -56,6 +134,8 @@ impl Texture {
                     width: size.x,
                     height: size.y,
                     native: texture,
+                    egui_id,
+                    deleted: false,
                 })),
                 path: None,
             })
@@ -64,11 +144,8 @@ impl Texture {
 
     pub fn from_bitmap(gl: &glow::Context, bitmap: &Bitmap) -> Result<Self> {
         let data = bitmap.bytes();
+        let (texture, egui_id) = create_native(gl)?;
         unsafe {
-            let texture = gl
-                .create_texture()
-                .map_err(|e| anyhow!(e))
-                .context("could not create texture")?;
             gl.bind_texture(glow::TEXTURE_2D, Some(texture));
             gl.tex_image_2d(
                 glow::TEXTURE_2D,
@@ -90,6 +167,8 @@ impl Texture {
                     width: bitmap.width(),
                     height: bitmap.height(),
                     native: texture,
+                    egui_id,
+                    deleted: false,
                 })),
                 path: bitmap.path().map(|p| p.to_path_buf()),
             })
@@ -105,11 +184,8 @@ impl Texture {
             );
         }
         let data = bitmap.bytes();
+        let (texture, egui_id) = create_native(gl)?;
         unsafe {
-            let texture = match gl.create_texture() {
-                Ok(texture) => texture,
-                Err(err) => bail!("coud not create texture: {}", err),
-            };
             gl.bind_texture(glow::TEXTURE_2D, Some(texture));
             gl.tex_image_2d(
                 glow::TEXTURE_2D,
@@ -131,25 +207,95 @@ impl Texture {
                     width: bitmap.width(),
                     height: bitmap.height(),
                     native: texture,
+                    egui_id,
+                    deleted: false,
                 })),
                 path: Some(path.as_ref().to_owned()),
             })
         }
     }
 
-    // pub fn reload(&mut self, gl: &Context, path: impl AsRef<Path>) -> Option<()> {
-    //     self.delete(gl);
-    //     {
-    //         let new_texture = Texture::from_path(gl, path).ok()?;
-    //         let binding = self.inner_rc().clone();
-    //         let current_texture = &mut *binding.borrow_mut();
-    //         current_texture.native = new_texture.native();
-    //         current_texture.width = new_texture.width();
-    //         current_texture.height = new_texture.height();
-    //         self.path = new_texture.path.clone();
-    //     }
-    //     Some(())
-    // }
+    /// Replaces the GL texture with a new one made from `bitmap`, e.g. after the GL context
+    /// was lost. Clones follow along, and the texture may have to be registered with egui
+    /// again.
+    /// The old texture isn't freed: after a context loss its handle means nothing, or worse,
+    /// names an unrelated texture of the new context.
+    pub fn recreate(&self, gl: &glow::Context, bitmap: &Bitmap) -> Result<()> {
+        let new_texture = Texture::from_bitmap(gl, bitmap)?;
+        let new_inner = new_texture.inner();
+        //the shared handle now belongs to this texture, so the new one mustn't free it on drop
+        new_texture.inner.borrow_mut().deleted = true;
+        *self.inner.borrow_mut() = new_inner;
+        Ok(())
+    }
+
+    /// Uploads RGBA `data` over the `width`x`height` rectangle at `x`, `y`, leaving the rest
+    /// of the texture as is. Much cheaper than recreating it after small edits, and it stays
+    /// registered with egui.
+    pub fn update_region(
+        &self,
+        gl: &glow::Context,
+        x: i32,
+        y: i32,
+        width: i32,
+        height: i32,
+        data: &[u8],
+    ) -> Result<()> {
+        let inner = self.inner();
+        if inner.deleted {
+            bail!("attempt to update a deleted texture");
+        }
+        if x < 0 || y < 0 || width <= 0 || height <= 0 {
+            bail!("invalid region {}x{} at {}, {}", width, height, x, y);
+        }
+        if x + width > inner.width || y + height > inner.height {
+            bail!(
+                "region {}x{} at {}, {} doesn't fit the {}x{} texture",
+                width,
+                height,
+                x,
+                y,
+                inner.width,
+                inner.height
+            );
+        }
+        if data.len() != (width * height * 4) as usize {
+            bail!(
+                "expected {} bytes for the region, got {}",
+                width * height * 4,
+                data.len()
+            );
+        }
+        unsafe {
+            gl.bind_texture(glow::TEXTURE_2D, Some(inner.native));
+            gl.tex_sub_image_2d(
+                glow::TEXTURE_2D,
+                0,
+                x,
+                y,
+                width,
+                height,
+                glow::RGBA,
+                glow::UNSIGNED_BYTE,
+                glow::PixelUnpackData::Slice(Some(data)),
+            );
+        }
+        Ok(())
+    }
+
+    /// Loads the image at `path` into the texture in place, so clones see it too. The old GL
+    /// texture is freed, and the new one may have to be registered with egui again. Nothing
+    /// changes if the image can't be loaded.
+    pub fn reload(&mut self, gl: &glow::Context, path: impl AsRef<Path>) -> Result<()> {
+        let new_texture = Texture::from_path(gl, path.as_ref())?;
+        let new_inner = new_texture.inner();
+        //the shared handle now belongs to this texture, so the new one mustn't free it on drop
+        new_texture.inner.borrow_mut().deleted = true;
+        self.delete(gl);
+        *self.inner.borrow_mut() = new_inner;
+        self.path = Some(path.as_ref().to_path_buf());
+        Ok(())
+    }
 
     pub fn width(&self) -> i32 {
         self.inner.borrow().width
@@ -167,11 +313,89 @@ impl Texture {
         *self.inner.borrow()
     }
 
-    pub fn native(&self) -> NativeTexture {
-        NativeTexture(self.inner.borrow().native.0)
+    /// `None` once the texture was deleted.
+    pub fn native(&self) -> Option<NativeTexture> {
+        let inner = self.inner.borrow();
+        (!inner.deleted).then(|| NativeTexture(inner.native.0))
+    }
+
+    /// `None` until the texture is registered with egui, and once it was deleted.
+    pub fn egui_id(&self) -> Option<egui::TextureId> {
+        let inner = self.inner.borrow();
+        inner.egui_id.filter(|_| !inner.deleted)
+    }
+
+    pub fn set_egui_id(&self, id: egui::TextureId) {
+        self.inner.borrow_mut().egui_id = Some(id);
     }
 
     pub fn path(&self) -> Option<&PathBuf> {
         self.path.as_ref()
     }
 }
+
+impl Drop for Texture {
+    fn drop(&mut self) {
+        //clones share the GL texture, so only the last one frees it
+        if Rc::strong_count(&self.inner) == 1 {
+            let inner = self.inner.borrow();
+            if !inner.deleted {
+                PENDING_DELETIONS
+                    .with_borrow_mut(|pending| pending.push((inner.native, inner.egui_id)));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use eframe::egui;
+    use pixas::bitmap::Bitmap;
+
+    use super::{Texture, delete_pending_textures, forget_recycled_textures};
+    use crate::test_utils::{HeadlessGl, TempDir};
+
+    #[test]
+    fn deleted_textures_stay_deleted_until_reloaded() {
+        let headless = HeadlessGl::new().unwrap();
+        let gl = &headless.gl;
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("image.png");
+        Bitmap::empty(16, 8).save(&path).unwrap();
+
+        let mut texture = Texture::from_bitmap(gl, &Bitmap::empty(4, 4)).unwrap();
+        texture.delete(gl);
+        texture.delete(gl);
+        assert!(texture.is_deleted());
+        assert!(texture.native().is_none());
+        assert!(texture.egui_id().is_none());
+
+        texture.reload(gl, &path).unwrap();
+        assert!(!texture.is_deleted());
+        assert!(texture.native().is_some());
+        assert_eq!((texture.width(), texture.height()), (16, 8));
+        assert_eq!(texture.path(), Some(&path));
+    }
+
+    #[test]
+    fn textures_registered_with_egui_are_recycled() {
+        let headless = HeadlessGl::new().unwrap();
+        let gl = &headless.gl;
+
+        let texture = Texture::from_bitmap(gl, &Bitmap::empty(4, 4)).unwrap();
+        let native = texture.native();
+        texture.set_egui_id(egui::TextureId::User(7));
+        drop(texture);
+        delete_pending_textures(gl);
+
+        let texture = Texture::from_bitmap(gl, &Bitmap::empty(16, 8)).unwrap();
+        assert_eq!(texture.native(), native);
+        assert_eq!(texture.egui_id(), Some(egui::TextureId::User(7)));
+        assert_eq!((texture.width(), texture.height()), (16, 8));
+
+        texture.delete(gl);
+        forget_recycled_textures();
+        let texture = Texture::from_bitmap(gl, &Bitmap::empty(4, 4)).unwrap();
+        assert!(texture.egui_id().is_none());
+    }
+}
@@ -0,0 +1,207 @@
+use eframe::egui;
+
+use crate::{
+    animations::Animation,
+    ui_text::{Translatable, UiText},
+};
+
+const TRACK_HEIGHT: f32 = 18.0;
+const LABEL_WIDTH: f32 = 120.0;
+
+/// Length of the timeline, which plays the animations back to back.
+pub fn timeline_duration(animations: &[Animation]) -> f32 {
+    animations.iter().map(Animation::total_duration).sum()
+}
+
+/// Moves the playhead `elapsed` seconds forward, starting over once it passes the end of
+/// the timeline.
+pub fn advance_playhead(animations: &[Animation], playhead: &mut f32, elapsed: f32) {
+    let duration = timeline_duration(animations);
+    if duration <= 0.0 {
+        return;
+    }
+    *playhead = (*playhead + elapsed) % duration;
+}
+
+/// The index of the animation active at `time` and the position of the shown frame in
+/// that animation's frame list. Past the end, the last frame is held.
+pub fn frame_position(animations: &[Animation], time: f32) -> Option<(usize, usize)> {
+    let mut start = 0.0;
+    for (index, animation) in animations.iter().enumerate() {
+        for (frame_index, frame) in animation.frames.iter().enumerate() {
+            let end = start + animation.frame_delay(frame);
+            if time < end {
+                return Some((index, frame_index));
+            }
+            start = end;
+        }
+    }
+    animations
+        .iter()
+        .enumerate()
+        .rev()
+        .find_map(|(index, animation)| {
+            animation
+                .frames
+                .len()
+                .checked_sub(1)
+                .map(|frame_index| (index, frame_index))
+        })
+}
+
+/// The index of the animation active at `time` and the atlas frame it shows.
+pub fn frame_at(animations: &[Animation], time: f32) -> Option<(usize, u32)> {
+    frame_position(animations, time)
+        .map(|(index, frame_index)| (index, animations[index].frames[frame_index].value))
+}
+
+/// When the given frame of the given animation starts on the timeline.
+pub fn frame_start(animations: &[Animation], index: usize, frame_index: usize) -> f32 {
+    let animation = &animations[index];
+    let previous_animations: f32 = animations[..index]
+        .iter()
+        .map(Animation::total_duration)
+        .sum();
+    let previous_frames: f32 = animation.frames[..frame_index]
+        .iter()
+        .map(|frame| animation.frame_delay(frame))
+        .sum();
+    previous_animations + previous_frames
+}
+
+/// Moves the playhead to the start of the next (or previous) frame of the animation under
+/// it, wrapping around within that animation.
+pub fn step_frame(animations: &[Animation], playhead: &mut f32, forward: bool) {
+    let Some((index, frame_index)) = frame_position(animations, *playhead) else {
+        return;
+    };
+    let frames_amount = animations[index].frames.len();
+    let frame_index = if forward {
+        (frame_index + 1) % frames_amount
+    } else {
+        (frame_index + frames_amount - 1) % frames_amount
+    };
+    *playhead = frame_start(animations, index, frame_index);
+}
+
+/// Buttons (and the arrow keys, while nothing has keyboard focus) stepping through the
+/// frames of the animation under the playhead, along with which frame is shown.
+pub fn draw_frame_step(
+    ui: &mut egui::Ui,
+    text: &UiText,
+    animations: &[Animation],
+    playhead: &mut f32,
+) {
+    let Some((index, frame_index)) = frame_position(animations, *playhead) else {
+        return;
+    };
+    let animation = &animations[index];
+    let (mut back, mut forward) = if ui.memory(|memory| memory.focused().is_none()) {
+        ui.input(|input| {
+            (
+                input.key_pressed(egui::Key::ArrowLeft),
+                input.key_pressed(egui::Key::ArrowRight),
+            )
+        })
+    } else {
+        (false, false)
+    };
+    ui.horizontal(|ui| {
+        back |= ui.button("⏴").on_hover_text(text.get("110")).clicked();
+        forward |= ui.button("⏵").on_hover_text(text.get("111")).clicked();
+        ui.label(format!(
+            "{} {}/{}",
+            text.get("112"),
+            frame_index + 1,
+            animation.frames.len()
+        ));
+        ui.label(format!(
+            "{}: {}",
+            text.get("113"),
+            animation.frames[frame_index].value
+        ));
+    });
+    if back != forward {
+        step_frame(animations, playhead, forward);
+    }
+}
+
+/// One track per animation, with each frame as wide as it lasts. Clicking or dragging
+/// moves the playhead.
+pub fn draw_timeline(
+    ui: &mut egui::Ui,
+    text: &UiText,
+    animations: &[Animation],
+    playhead: &mut f32,
+) {
+    let duration = timeline_duration(animations);
+    if duration <= 0.0 {
+        ui.label(text.get("96"));
+        return;
+    }
+    *playhead = playhead.clamp(0.0, duration);
+    ui.horizontal(|ui| {
+        ui.label(text.get("97"));
+        ui.add(
+            egui::DragValue::new(playhead)
+                .speed(0.01)
+                .range(0.0..=duration),
+        );
+    });
+
+    let (rect, response) = ui.allocate_exact_size(
+        egui::vec2(ui.available_width(), TRACK_HEIGHT * animations.len() as f32),
+        egui::Sense::click_and_drag(),
+    );
+    let tracks_rect =
+        egui::Rect::from_min_max(egui::pos2(rect.left() + LABEL_WIDTH, rect.top()), rect.max);
+    if let Some(pointer) = response.interact_pointer_pos() {
+        *playhead = ((pointer.x - tracks_rect.left()) / tracks_rect.width() * duration)
+            .clamp(0.0, duration);
+    }
+    let time_to_x = |time: f32| tracks_rect.left() + time / duration * tracks_rect.width();
+    let active = frame_at(animations, *playhead).map(|(index, _)| index);
+
+    let painter = ui.painter_at(rect);
+    let visuals = ui.visuals();
+    let mut start = 0.0;
+    for (index, animation) in animations.iter().enumerate() {
+        let top = rect.top() + index as f32 * TRACK_HEIGHT;
+        let fill = if active == Some(index) {
+            visuals.selection.bg_fill
+        } else {
+            visuals.widgets.inactive.bg_fill
+        };
+        painter.text(
+            egui::pos2(rect.left() + 4.0, top + TRACK_HEIGHT / 2.0),
+            egui::Align2::LEFT_CENTER,
+            text.get(animation.anim_type.translate_key()),
+            egui::FontId::proportional(12.0),
+            visuals.text_color(),
+        );
+        for frame in &animation.frames {
+            let end = start + animation.frame_delay(frame);
+            let frame_rect = egui::Rect::from_min_max(
+                egui::pos2(time_to_x(start), top + 1.0),
+                egui::pos2(time_to_x(end), top + TRACK_HEIGHT - 1.0),
+            );
+            painter.rect_filled(frame_rect.shrink2(egui::vec2(0.5, 0.0)), 2.0, fill);
+            if frame_rect.width() > 14.0 {
+                painter.text(
+                    frame_rect.center(),
+                    egui::Align2::CENTER_CENTER,
+                    frame.value.to_string(),
+                    egui::FontId::monospace(10.0),
+                    visuals.strong_text_color(),
+                );
+            }
+            start = end;
+        }
+    }
+
+    let x = time_to_x(*playhead);
+    painter.line_segment(
+        [egui::pos2(x, rect.top()), egui::pos2(x, rect.bottom())],
+        egui::Stroke::new(2.0, egui::Color32::from_rgb(240, 80, 80)),
+    );
+}
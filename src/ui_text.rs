@@ -1,13 +1,32 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, path::Path};
 
+use anyhow::{Context, Result, bail};
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Default, strum::EnumIter)]
 pub enum Language {
+    #[default]
     English,
     Russian,
 }
 
+impl Language {
+    pub fn code(&self) -> &'static str {
+        match self {
+            Language::English => "en",
+            Language::Russian => "ru",
+        }
+    }
+
+    /// The language's name in itself, so it can be found whatever the current language.
+    pub fn native_name(&self) -> &'static str {
+        match self {
+            Language::English => "English",
+            Language::Russian => "Русский",
+        }
+    }
+}
+
 pub trait Translatable {
     fn translate_key(&self) -> &str;
 }
@@ -31,26 +50,77 @@ pub struct UiText {
 }
 
 impl UiText {
-    pub fn new(language: Language, json: &str) -> Self {
-        let json = serde_json::from_str(json).unwrap();
-        Self {
-            language,
-            data: json,
+    pub fn new(language: Language, json: &str) -> Result<Self> {
+        let data: HashMap<String, HashMap<String, String>> =
+            serde_json::from_str(json).context("could not parse translations")?;
+        if !data.contains_key(Language::English.code()) {
+            bail!("translations have no english table");
         }
+        Ok(Self { language, data })
+    }
+
+    pub fn from_path(language: Language, path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let json = std::fs::read_to_string(path).context(format!("could not read {:?}", path))?;
+        Self::new(language, &json).context(format!("invalid translations file {:?}", path))
     }
-    pub fn get<T: Translatable + ?Sized>(&self, translatable: &T) -> &str {
+
+    /// Text for the key in the current language, falling back to English and then to the
+    /// key itself.
+    pub fn get<'a, T: Translatable + ?Sized>(&'a self, translatable: &'a T) -> &'a str {
         let key = translatable.translate_key();
-        match self.language {
-            Language::English => self.data["en"].get(key).unwrap_or_else(|| {
-                panic!("could not find a key for: en, {0}", key);
-            }),
-            Language::Russian => self.data["ru"].get(key).unwrap_or_else(|| {
-                panic!("could not find a key for: ru, {0}", key);
-            }),
-        }
+        [self.language, Language::English]
+            .into_iter()
+            .find_map(|language| self.data.get(language.code())?.get(key))
+            .map(String::as_str)
+            .unwrap_or(key)
     }
 
     pub fn language(&self) -> Language {
         self.language
     }
+
+    /// Takes effect right away, since all text is looked up every frame.
+    pub fn set_language(&mut self, language: Language) {
+        self.language = language;
+    }
+
+    /// Keys present in English but absent in `language`, sorted.
+    pub fn missing_keys(&self, language: Language) -> Vec<String> {
+        let Some(english) = self.data.get(Language::English.code()) else {
+            return vec![];
+        };
+        let target = self.data.get(language.code());
+        let mut keys: Vec<String> = english
+            .keys()
+            .filter(|key| target.is_none_or(|t| !t.contains_key(*key)))
+            .cloned()
+            .collect();
+        keys.sort();
+        keys
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Language, UiText};
+
+    #[test]
+    fn malformed_translations() {
+        assert!(UiText::new(Language::English, r#"{"en": {"Hat": "Hat""#).is_err());
+        assert!(UiText::new(Language::English, r#"{"ru": {"Hat": "Шапка"}}"#).is_err());
+        assert!(UiText::new(Language::English, r#"{"en": {"Hat": "Hat"}}"#).is_ok());
+    }
+
+    #[test]
+    fn missing_keys_fall_back_to_english() {
+        let ui_text = UiText::new(
+            Language::Russian,
+            r#"{"en": {"Hat": "Hat", "Save": "Save"}, "ru": {"Hat": "Шапка"}}"#,
+        )
+        .unwrap();
+        assert_eq!(ui_text.get("Hat"), "Шапка");
+        assert_eq!(ui_text.get("Save"), "Save");
+        assert_eq!(ui_text.get("Nonexistent"), "Nonexistent");
+    }
 }
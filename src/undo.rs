@@ -0,0 +1,119 @@
+use std::collections::VecDeque;
+
+use anyhow::Result;
+use eframe::glow;
+
+use crate::hats::{Hat, HatSnapshot};
+
+/// How many changes can be undone.
+const MAX_UNDO_STEPS: usize = 50;
+
+/// Snapshots of a hat taken whenever it changed, to step back and forth through.
+#[derive(Debug, Clone, Default)]
+pub struct UndoStack {
+    undo: VecDeque<HatSnapshot>,
+    redo: Vec<HatSnapshot>,
+    /// The hat as of the last recorded change.
+    current: Option<HatSnapshot>,
+}
+
+impl UndoStack {
+    /// Records the hat if it changed since the last call. The first call only sets the
+    /// state to go back to.
+    pub fn record(&mut self, hat: &Hat) {
+        if self
+            .current
+            .as_ref()
+            .is_some_and(|current| current.key() == hat.snapshot_key())
+        {
+            return;
+        }
+        if let Some(previous) = self.current.replace(hat.snapshot()) {
+            self.push_undo(previous);
+            self.redo.clear();
+        }
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    /// Returns whether there was anything to undo. Changes not recorded yet are recorded
+    /// first, so they are what gets undone.
+    pub fn undo(&mut self, hat: &mut Hat, gl: &glow::Context) -> Result<bool> {
+        self.record(hat);
+        let Some(snapshot) = self.undo.pop_back() else {
+            return Ok(false);
+        };
+        if let Err(err) = hat.restore_snapshot(&snapshot, gl) {
+            self.undo.push_back(snapshot);
+            return Err(err);
+        }
+        if let Some(current) = self.current.replace(snapshot) {
+            self.redo.push(current);
+        }
+        Ok(true)
+    }
+
+    /// Returns whether there was anything to redo.
+    pub fn redo(&mut self, hat: &mut Hat, gl: &glow::Context) -> Result<bool> {
+        self.record(hat);
+        let Some(snapshot) = self.redo.pop() else {
+            return Ok(false);
+        };
+        if let Err(err) = hat.restore_snapshot(&snapshot, gl) {
+            self.redo.push(snapshot);
+            return Err(err);
+        }
+        if let Some(current) = self.current.replace(snapshot) {
+            self.push_undo(current);
+        }
+        Ok(true)
+    }
+
+    fn push_undo(&mut self, snapshot: HatSnapshot) {
+        if self.undo.len() == MAX_UNDO_STEPS {
+            self.undo.pop_front();
+        }
+        self.undo.push_back(snapshot);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use pixas::bitmap::Bitmap;
+
+    use super::UndoStack;
+    use crate::{
+        hats::{Hat, LoadHatElement, WearableHat},
+        test_utils::{HeadlessGl, TempDir},
+    };
+
+    #[test]
+    fn undoing_removal_keeps_element_id() {
+        let headless = HeadlessGl::new().unwrap();
+        let gl = &headless.gl;
+        let dir = TempDir::new().unwrap();
+        let image_path = dir.path().join("wearable.png");
+        Bitmap::empty(64, 32).save(&image_path).unwrap();
+
+        let mut hat = Hat::load(dir.path(), gl).unwrap();
+        let id = hat
+            .add_element(WearableHat::load_from_path(&image_path, gl).unwrap())
+            .unwrap();
+        let mut undo_stack = UndoStack::default();
+        undo_stack.record(&hat);
+        hat.remove_element(id);
+        undo_stack.record(&hat);
+
+        assert!(undo_stack.undo(&mut hat, gl).unwrap());
+        assert!(hat.element_exists(id));
+        assert!(undo_stack.redo(&mut hat, gl).unwrap());
+        assert!(!hat.element_exists(id));
+        assert!(!undo_stack.can_redo());
+    }
+}